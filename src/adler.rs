@@ -0,0 +1,251 @@
+// Adler-32 checksums, and forging them
+//
+// Adler-32 is two running sums mod 65521, a, one plus the sum of our
+// bytes, and b, the sum of every a along the way. A suffix s_1..s_n moves
+// these by
+//
+//   a' = a + sum s_i
+//   b' = b + n*a + sum (n-i+1)*s_i
+//
+// Both sums are plain integers until they wrap, so unlike CRCs, a short
+// suffix can only move a by a little. Instead we pick how much our
+// suffix adds to each sum, and build bytes that add exactly that. This
+// needs our bytes to be a contiguous range, and can need a long suffix,
+// up to 65521 over the size of our range.
+//
+
+use crate::charset::Charset;
+use crate::checksum::Checksum;
+
+/// Modulus of both of our sums, the largest prime below 2^16
+pub const MOD: u32 = 65521;
+
+/// Adler-32 of a message with nothing in it, this is what an Adler-32 of
+/// a whole message should start from
+pub const INIT: u32 = 1;
+
+/// Continue an Adler-32 over more data
+pub fn adler32(adler: u32, data: &[u8]) -> u32 {
+    let mut a = adler & 0xffff;
+    let mut b = adler >> 16;
+    // 5552 is the most bytes we can sum before overflowing a u32
+    for chunk in data.chunks(5552) {
+        for &x in chunk {
+            a += x as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// Is this a checksum Adler-32 can actually produce?
+pub fn is_valid(adler: u32) -> bool {
+    adler & 0xffff < MOD && adler >> 16 < MOD
+}
+
+/// Find the Adler-32 we need just before a postfix, so the whole message
+/// lands on our target
+pub fn unpostfix(target: u32, postfix: &[u8]) -> u32 {
+    let p = adler32(0, postfix);
+    let (a, b) = unsum(MOD as u64,
+        ((target & 0xffff) as u64, (target >> 16) as u64),
+        ((p & 0xffff) as u64, (p >> 16) as u64),
+        postfix.len() as u64);
+    ((b << 16) | a) as u32
+}
+
+/// Find the shortest suffix of bytes in lo..=hi that takes
+/// prefix || suffix || postfix to any of our targets, returning the
+/// suffix and which target it hit
+pub fn forge(
+    prefix: &[u8],
+    postfix: &[u8],
+    targets: &[u32],
+    lo: u8,
+    hi: u8,
+) -> Option<(Vec<u8>, u32)> {
+    let split = |x: u32| ((x & 0xffff) as u64, (x >> 16) as u64);
+    let befores = targets.iter()
+        .map(|&t| split(unpostfix(t, postfix)))
+        .collect::<Vec<_>>();
+
+    // with this many bytes every sum is reachable, so if we haven't found
+    // anything by then, we won't
+    let max_len = 2*(MOD as usize).div_ceil((hi - lo) as usize) + 2;
+    let (suffix, i) = solve(MOD as u64,
+        split(adler32(INIT, prefix)),
+        &befores,
+        lo as u64, hi as u64,
+        max_len)?;
+    Some((suffix.into_iter().map(|x| x as u8).collect(), targets[i]))
+}
+
+/// Adler-32 as a Checksum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adler32;
+
+impl Checksum for Adler32 {
+    fn width(&self) -> u32 {
+        32
+    }
+
+    fn init(&self) -> u64 {
+        INIT as u64
+    }
+
+    fn update(&self, state: u64, data: &[u8]) -> u64 {
+        adler32(state as u32, data) as u64
+    }
+
+    fn finalize(&self, state: u64) -> u32 {
+        state as u32
+    }
+
+    fn is_valid(&self, sum: u32) -> bool {
+        is_valid(sum)
+    }
+
+    fn solve_suffix(
+        &self,
+        prefix: &[u8],
+        postfix: &[u8],
+        targets: &[u32],
+        charset: Charset,
+    ) -> Option<Option<(Vec<u8>, u32)>> {
+        let (lo, hi) = charset.longest_run();
+        (lo < hi).then(|| forge(prefix, postfix, targets, lo, hi))
+    }
+}
+
+// The solver below works for any checksum made of these two running sums,
+// Fletcher's checksums are the same thing with a different modulus and
+// symbol size, and a starting a of zero.
+
+/// Undo a run of len symbols that adds (pa, pb) to sums starting at zero,
+/// giving the sums we need before them to land on (a, b) mod m
+pub fn unsum(m: u64, (a, b): (u64, u64), (pa, pb): (u64, u64), len: u64)
+        -> (u64, u64) {
+    // the run adds its own sums, plus len copies of our a
+    let a_ = (a + m - pa % m) % m;
+    let b_ = (b + 2*m - pb % m - (len % m) * a_ % m) % m;
+    (a_, b_)
+}
+
+// least and most sum w*t_w over weights 1..=len we can get with t_w in
+// 0..=r summing to u, least puts u in the smallest weights, most in the
+// largest
+fn least(r: u64, u: u64) -> u64 {
+    let (q, rem) = (u / r, u % r);
+    r*q*(q+1)/2 + rem*(q+1)
+}
+
+fn most(len: u64, r: u64, u: u64) -> u64 {
+    let (q, rem) = (u / r, u % r);
+    r*(q*len - q*q.saturating_sub(1)/2) + rem*len.saturating_sub(q)
+}
+
+/// Find the shortest run of symbols in lo..=hi, at most max_len long,
+/// that takes our sums (a, b) mod m to any of our targets, returning the
+/// symbols and the index of the target they hit
+///
+/// Moving one unit from a symbol to the symbol before it raises our
+/// weighted sum by exactly one, so for a given sum every weighted sum
+/// between the least and most is reachable, and we can build our symbols
+/// greedily.
+pub fn solve(
+    m: u64,
+    (a, b): (u64, u64),
+    targets: &[(u64, u64)],
+    lo: u64,
+    hi: u64,
+    max_len: usize,
+) -> Option<(Vec<u64>, usize)> {
+    let r = hi - lo;
+    assert!(r > 0);
+
+    for n in 1..=max_len as u64 {
+        // shift everything so our symbols are t_i in 0..=r
+        let base_a = a + n*lo;
+        let base_b = b + n*a + lo*n*(n+1)/2;
+        for (i, &(ta, tb)) in targets.iter().enumerate() {
+            let du = (ta + m - base_a % m) % m;
+            let dv = (tb + m - base_b % m) % m;
+
+            // any sum u congruent to du we can fit, with a weighted sum
+            // congruent to dv
+            let found = (0..)
+                .map(|k| du + k*m)
+                .take_while(|&u| u <= n*r)
+                .find_map(|u| {
+                    let least = least(r, u);
+                    let v = least + (dv + m - least % m) % m;
+                    (v <= most(n, r, u)).then_some((u, v))
+                });
+            let (mut u, mut v) = match found {
+                Some(found) => found,
+                None => continue,
+            };
+
+            // greedily place the most we can in each weight, as long as
+            // what's left is still reachable by the smaller weights
+            let mut symbols = vec![];
+            for w in (1..=n).rev() {
+                let t = (0..=r.min(u)).rev()
+                    .find(|&t| {
+                        let (u_, v_) = (u - t, v.wrapping_sub(w*t));
+                        w*t <= v
+                            && u_ <= (w-1)*r
+                            && least(r, u_) <= v_
+                            && v_ <= most(w-1, r, u_)
+                    })
+                    .unwrap();
+                symbols.push(lo + t);
+                u -= t;
+                v -= w*t;
+            }
+            return Some((symbols, i));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn known_values() {
+        assert_eq!(adler32(INIT, b""), 0x00000001);
+        assert_eq!(adler32(INIT, b"Wikipedia"), 0x11e60398);
+        // continuing is the same as summing everything at once
+        assert_eq!(adler32(adler32(INIT, b"Wiki"), b"pedia"), 0x11e60398);
+        // and long enough to wrap
+        let data = vec![0xff; 100000];
+        let (a, b) = data.iter().fold((1u64, 0u64), |(a, b), &x| {
+            let a = (a + x as u64) % MOD as u64;
+            (a, (b + a) % MOD as u64)
+        });
+        assert_eq!(adler32(INIT, &data), ((b << 16) | a) as u32);
+    }
+
+    #[test]
+    fn forge_round_trips() {
+        let mut rng = Rng::new(1);
+        for (lo, hi) in [(0x00, 0xff), (b'a', b'z'), (b'0', b'9')] {
+            for _ in 0..10 {
+                let target = (rng.below(MOD as u64) << 16
+                    | rng.below(MOD as u64)) as u32;
+                let (suffix, hit) = forge(b"hello", b"\n", &[target], lo, hi)
+                    .unwrap();
+                assert_eq!(hit, target);
+                assert!(suffix.iter().all(|b| (lo..=hi).contains(b)));
+                let whole = [&b"hello"[..], &suffix, b"\n"].concat();
+                assert_eq!(adler32(INIT, &whole), target);
+            }
+        }
+    }
+}
@@ -0,0 +1,359 @@
+// Analysis of CRC polynomials
+//
+// Tools for checking the error-detection properties of a polynomial,
+// useful when picking a CRC, or justifying one.
+//
+
+use crate::crc::Crc32;
+use crate::rng::Rng;
+use crate::pmul::gfmul;
+
+/// Results of an error-injection simulation
+#[derive(Debug, Clone, Copy)]
+pub struct Simulation {
+    pub trials: u64,
+    pub undetected: u64,
+}
+
+/// Inject k random bit errors into random len-byte messages and their
+/// CRCs, counting how many errors go undetected
+pub fn simulate(
+    crc32: &Crc32,
+    len: usize,
+    errors: usize,
+    trials: u64,
+    rng: &mut Rng,
+) -> Simulation {
+    // our codeword is our message followed by its CRC, which takes up as
+    // many bytes as it needs, but only its width in bits can be flipped
+    let n = crc32.width().div_ceil(8) as usize;
+    let bits = 8*len + crc32.width() as usize;
+    assert!(errors <= bits);
+
+    let mut codeword = vec![0; len+n];
+    let mut flipped = Vec::with_capacity(errors);
+    let mut undetected = 0;
+    for _ in 0..trials {
+        rng.fill(&mut codeword[..len]);
+        let crc = crc32.crc32(crc32.init(), &codeword[..len]);
+        codeword[len..].copy_from_slice(&crc.to_le_bytes()[..n]);
+
+        // flip k distinct bits
+        flipped.clear();
+        while flipped.len() < errors {
+            let i = rng.below(bits as u64) as usize;
+            if !flipped.contains(&i) {
+                flipped.push(i);
+                codeword[i/8] ^= 1 << (i%8);
+            }
+        }
+
+        let crc = crc32.crc32(crc32.init(), &codeword[..len]);
+        if crc.to_le_bytes()[..n] == codeword[len..] {
+            undetected += 1;
+        }
+    }
+
+    Simulation{trials, undetected}
+}
+
+/// Probability that at least one of n random candidates lands in a set of
+/// accepted values, out of 2^bits possible values
+pub fn success_probability(candidates: f64, accepted: f64, bits: u32) -> f64 {
+    let p = accepted / 2f64.powi(bits as i32);
+    if p >= 1.0 {
+        return 1.0;
+    }
+    // 1-(1-p)^n, but careful to avoid rounding when p is small
+    -(candidates * (-p).ln_1p()).exp_m1()
+}
+
+/// Number of values within a Hamming distance of a bits-wide value
+pub fn hamming_ball(bits: u32, distance: u32) -> f64 {
+    let mut sum = 0.0;
+    let mut binom = 1.0;
+    for d in 0..=distance.min(bits) {
+        sum += binom;
+        binom = binom * (bits - d) as f64 / (d + 1) as f64;
+    }
+    sum
+}
+
+/// Degree of a polynomial in explicit form, this is the CRC's width
+pub fn degree(poly: u64) -> u32 {
+    63 - poly.leading_zeros()
+}
+
+/// Transition matrix of our CRC's LFSR, shifting in a single zero bit
+///
+/// Returned as rows of bits, row i is bit i of the next state, so
+/// next = M * state over GF(2), with states in normal (non-reflected) form.
+pub fn transition_matrix(poly: u64) -> Vec<u64> {
+    let width = degree(poly);
+    let normal = poly & !(1 << width);
+    // column j is x*x^j mod P
+    let cols = (0..width)
+        .map(|j| gfmul(1 << j, 2, normal, width))
+        .collect::<Vec<_>>();
+    (0..width)
+        .map(|i| {
+            cols.iter().enumerate()
+                .fold(0, |row, (j, c)| row | (((c >> i) & 1) << j))
+        })
+        .collect()
+}
+
+// residues x^i mod P, an error pattern is undetectable if the residues of
+// its bits xor to zero
+fn residues(poly: u64, bits: usize) -> Vec<u64> {
+    let width = degree(poly);
+    let normal = poly & !(1 << width);
+    let mut r = 1;
+    (0..bits)
+        .map(|_| {
+            let r_ = r;
+            r = gfmul(r, 2, normal, width);
+            r_
+        })
+        .collect()
+}
+
+/// Count undetectable error patterns of each weight, up to max_weight <= 4,
+/// in a codeword of the given number of bits
+///
+/// Returns counts indexed by weight, so weights[0] is always zero.
+pub fn weights(poly: u64, bits: usize, max_weight: usize) -> Vec<u64> {
+    assert!(max_weight <= 4);
+    let r = residues(poly, bits);
+    let mut weights = vec![0u64; max_weight+1];
+
+    if max_weight >= 1 {
+        weights[1] = r.iter().filter(|&&r| r == 0).count() as u64;
+    }
+
+    // weight 2, pairs with equal residues
+    let mut sorted = r.clone();
+    sorted.sort_unstable();
+    let w2 = sorted.chunk_by(|a, b| a == b)
+        .map(|run| (run.len() * (run.len()-1) / 2) as u64)
+        .sum::<u64>();
+    if max_weight >= 2 {
+        weights[2] = w2;
+    }
+
+    if max_weight >= 3 {
+        // weight 3, pairs whose xor is the residue of a later bit
+        let mut positions = r.iter().copied()
+            .enumerate()
+            .map(|(i, r)| (r, i))
+            .collect::<Vec<_>>();
+        positions.sort_unstable();
+        for i in 0..bits {
+            for j in i+1..bits {
+                let v = r[i] ^ r[j];
+                let lo = positions.partition_point(|&p| p < (v, j+1));
+                let hi = positions.partition_point(|&p| p < (v, usize::MAX));
+                weights[3] += (hi - lo) as u64;
+            }
+        }
+    }
+
+    if max_weight >= 4 {
+        // weight 4, pairs of disjoint pairs with equal xors, each pattern
+        // shows up as 3 such pairs of pairs
+        let mut pairs = Vec::with_capacity(bits*bits/2);
+        for i in 0..bits {
+            for j in i+1..bits {
+                pairs.push(r[i] ^ r[j]);
+            }
+        }
+        pairs.sort_unstable();
+        let equal = pairs.chunk_by(|a, b| a == b)
+            .map(|run| (run.len() as u64) * (run.len() as u64 - 1) / 2)
+            .sum::<u64>();
+        // pairs of pairs that share a bit need two equal residues
+        let shared = w2 * (bits as u64).saturating_sub(2);
+        weights[4] = (equal - shared) / 3;
+    }
+
+    weights
+}
+
+/// Find the Hamming distance breakpoints of a polynomial
+///
+/// Returns (hd, max data bits) for each HD up to max_weight+1, where the
+/// polynomial provides at least that HD for data up to that many bits.
+/// Lengths are limited to max_bits, which is returned if no breakpoint is
+/// found.
+pub fn hd_table(poly: u64, max_weight: usize, max_bits: usize) -> Vec<(usize, usize)> {
+    let width = degree(poly) as usize;
+    let undetected = |w: usize, data: usize| {
+        weights(poly, data+width, w)[1..=w].iter().any(|&c| c > 0)
+    };
+
+    let mut table = vec![];
+    for w in 1..=max_weight {
+        // find the first data length with an undetectable weight-w error
+        // via binary search, note this is monotonic
+        if !undetected(w, max_bits) {
+            table.push((w+1, max_bits));
+            continue;
+        }
+        let mut lo = 0;
+        let mut hi = max_bits;
+        while lo + 1 < hi {
+            let mid = lo + (hi-lo)/2;
+            if undetected(w, mid) {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        table.push((w+1, lo));
+    }
+    table
+}
+
+/// Hamming distance of a polynomial for a codeword of the given number of
+/// bits, this is the weight of the lightest undetectable error
+///
+/// Only weights up to max_weight <= 4 are checked, returns None if every
+/// error up to that weight is detected.
+pub fn hamming_distance(poly: u64, bits: usize, max_weight: usize) -> Option<usize> {
+    weights(poly, bits, max_weight).iter()
+        .skip(1)
+        .position(|&c| c > 0)
+        .map(|w| w+1)
+}
+
+/// Format a polynomial in explicit form as a sum of terms
+pub fn poly_string(poly: u64) -> String {
+    (0..=degree(poly)).rev()
+        .filter(|i| (poly >> i) & 1 != 0)
+        .map(|i| match i {
+            0 => "1".to_string(),
+            1 => "x".to_string(),
+            i => format!("x^{}", i),
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+// helper for drawing, puts a string at a column, growing the line as needed
+fn put(line: &mut Vec<u8>, col: usize, s: &str) {
+    if line.len() < col + s.len() {
+        line.resize(col + s.len(), b' ');
+    }
+    line[col..col+s.len()].copy_from_slice(s.as_bytes());
+}
+
+fn lines(lines: Vec<Vec<u8>>) -> String {
+    lines.into_iter()
+        .map(|line| String::from_utf8(line).unwrap().trim_end().to_string() + "\n")
+        .collect()
+}
+
+/// Draw the Galois form of our LFSR, this is the form CRCs are usually
+/// implemented in, with xors between stages
+///
+/// ``` text
+///  +--------+-----------------------------+
+///  v        v                             |
+/// (+)-[ 0]-(+)-[ 1]-----[ 2]-----[ 3]-(+)-+
+///                                      ^
+///                                      in
+/// ```
+pub fn draw_galois(poly: u64) -> String {
+    let width = degree(poly) as usize;
+    let mut bus = vec![];
+    let mut down = vec![];
+    let mut chain = vec![];
+
+    let mut first = None;
+    for i in 0..width {
+        let col = 9*i;
+        if (poly >> i) & 1 != 0 {
+            put(&mut chain, col, "(+)-");
+            put(&mut down, col+1, "v");
+            put(&mut bus, col+1, "+");
+            first.get_or_insert(col+1);
+        } else {
+            put(&mut chain, col, "----");
+        }
+        put(&mut chain, col+4, &format!("[{:2}]", i));
+        put(&mut chain, col+8, "-");
+    }
+
+    // feedback from our last stage, xored with our input
+    let xor = 9*width;
+    put(&mut chain, xor, "(+)-+");
+    let end = xor+4;
+    let first = first.unwrap_or(end);
+    for col in first..end {
+        if bus.get(col).copied().unwrap_or(b' ') == b' ' {
+            put(&mut bus, col, "-");
+        }
+    }
+    put(&mut bus, end, "+");
+    put(&mut down, end, "|");
+
+    let mut arrow = vec![];
+    put(&mut arrow, xor+1, "^");
+    let mut input = vec![];
+    put(&mut input, xor+1, "in");
+
+    lines(vec![bus, down, chain, arrow, input])
+}
+
+/// Draw the Fibonacci form of our LFSR, with taps xored together and fed
+/// back into the first stage
+///
+/// ``` text
+/// +<----------------(+)-----+
+/// |                  ^      |
+/// +->[ 0]-->[ 1]-->[ 2]-->[ 3]--> out
+/// ```
+pub fn draw_fibonacci(poly: u64) -> String {
+    let width = degree(poly) as usize;
+    let mut bus = vec![];
+    let mut up = vec![];
+    let mut chain = vec![];
+
+    put(&mut chain, 0, "+->");
+    put(&mut up, 0, "|");
+    put(&mut bus, 0, "+<");
+
+    // our oldest stage holds the coefficient of x^0, so stage j is tapped
+    // if x^(width-1-j) is in our polynomial
+    let mut last = 0;
+    for j in 0..width {
+        let col = 3 + 7*j;
+        put(&mut chain, col, &format!("[{:2}]", j));
+        put(&mut chain, col+4, "-->");
+        if (poly >> (width-1-j)) & 1 != 0 {
+            last = col+2;
+        }
+    }
+    put(&mut chain, 3 + 7*width, " out");
+
+    for j in 0..width {
+        let col = 3 + 7*j + 2;
+        if (poly >> (width-1-j)) & 1 != 0 {
+            put(&mut up, col, "^");
+            if col == last {
+                put(&mut bus, col, "+");
+            } else {
+                put(&mut bus, col-1, "(+)");
+            }
+        }
+    }
+    for col in 2..last {
+        if bus.get(col).copied().unwrap_or(b' ') == b' ' {
+            put(&mut bus, col, "-");
+        }
+    }
+    // our rightmost tap is where the bus starts
+    put(&mut up, last, "|");
+
+    lines(vec![bus, up, chain])
+}
@@ -0,0 +1,220 @@
+// Batch job files
+//
+// A job file lists many searches to run in one process, one per line,
+// either as CSV rows of prefix, targets, and options:
+//
+//   prefix,target,options
+//   hello,0x12345678,--ascii
+//   "hello, world",0x1234 0x5678,--ascii --target-mask 0xffff
+//
+// Or as JSON lines, which can also express options containing spaces:
+//
+//   {"prefix":"hello","target":"0x12345678","options":["--ascii"]}
+//
+// Targets and options can be lists, space separated in CSV. A job can
+// leave out its prefix if it comes from an option, such as --prefix-hex.
+// Blank lines, lines starting with #, and a CSV header are skipped.
+//
+
+use crate::json::Value;
+
+/// A single search in a job file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    /// Line of our job file this came from
+    pub line: usize,
+    pub prefix: Option<String>,
+    pub targets: Vec<String>,
+    pub options: Vec<String>,
+}
+
+impl Job {
+    /// Arguments for crcbrute, options go first so a prefix starting with
+    /// a - isn't mistaken for one
+    pub fn args(&self) -> Vec<String> {
+        let mut args = self.options.clone();
+        args.push("--".into());
+        args.extend(self.prefix.iter().cloned());
+        args.extend(self.targets.iter().cloned());
+        args
+    }
+}
+
+// split a CSV row, fields can be quoted, with "" for a quote
+fn csv(row: &str) -> Result<Vec<String>, String> {
+    let mut fields = vec![];
+    let mut chars = row.chars().peekable();
+    loop {
+        let mut field = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') if chars.next_if_eq(&'"').is_some() => {
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err("unterminated quote".into()),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|&c| c != ',') {
+                field.push(c);
+            }
+        }
+        fields.push(field);
+
+        match chars.next() {
+            Some(',') => {}
+            Some(c) => return Err(format!("unexpected {:?} after quote", c)),
+            None => return Ok(fields),
+        }
+    }
+}
+
+fn csv_job(line: usize, fields: Vec<String>) -> Result<Job, String> {
+    if fields.len() > 3 {
+        return Err(format!("expected prefix, target, and options, found \
+            {} fields", fields.len()));
+    }
+    let mut fields = fields.into_iter();
+    let prefix = fields.next().filter(|prefix| !prefix.is_empty());
+    let mut split = || fields.next()
+        .map_or(vec![], |f| f.split_whitespace().map(String::from).collect());
+    Ok(Job{line, prefix, targets: split(), options: split()})
+}
+
+fn json_job(line: usize, row: &str) -> Result<Job, String> {
+    let fields = match Value::parse(row)? {
+        Value::Object(fields) => fields,
+        _ => return Err("expected a JSON object".into()),
+    };
+
+    let mut job = Job{line, prefix: None, targets: vec![], options: vec![]};
+    for (k, v) in fields {
+        match (k.as_str(), v) {
+            ("prefix", Value::Null) => {}
+            ("prefix", Value::String(prefix)) => job.prefix = Some(prefix),
+            ("target", Value::Array(vs)) => {
+                for v in vs {
+                    job.targets.push(json_target(v)?);
+                }
+            }
+            ("target", v) => job.targets.push(json_target(v)?),
+            ("options", Value::String(options)) => {
+                job.options = options.split_whitespace()
+                    .map(String::from)
+                    .collect();
+            }
+            ("options", Value::Array(vs)) => {
+                for v in vs {
+                    match v {
+                        Value::String(option) => job.options.push(option),
+                        v => return Err(format!("expected an option, \
+                            found {}", v)),
+                    }
+                }
+            }
+            (k, v) => return Err(format!("unexpected field {:?}: {}", k, v)),
+        }
+    }
+    Ok(job)
+}
+
+// targets can be strings, such as 0x1234 or residue, or plain numbers
+fn json_target(v: Value) -> Result<String, String> {
+    match v {
+        Value::String(target) => Ok(target),
+        v => v.as_u64()
+            .map(|target| target.to_string())
+            .ok_or_else(|| format!("expected a target, found {}", v)),
+    }
+}
+
+/// Parse a job file
+pub fn parse(s: &str) -> Result<Vec<Job>, String> {
+    let mut jobs = vec![];
+    for (i, row) in s.lines().enumerate() {
+        let line = i + 1;
+        let row = row.strip_suffix('\r').unwrap_or(row);
+        if row.trim().is_empty() || row.starts_with('#') {
+            continue;
+        }
+
+        let err = |err: String| format!("line {}: {}", line, err);
+        if row.trim_start().starts_with('{') {
+            jobs.push(json_job(line, row).map_err(err)?);
+            continue;
+        }
+
+        let fields = csv(row).map_err(err)?;
+        let header = fields.first().is_some_and(|f| f == "prefix")
+            && fields.get(1).is_none_or(|f| f == "target" || f == "targets")
+            && fields.get(2).is_none_or(|f| f == "options");
+        if jobs.is_empty() && header {
+            continue;
+        }
+        jobs.push(csv_job(line, fields).map_err(err)?);
+    }
+    Ok(jobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(line: usize, prefix: Option<&str>, targets: &[&str],
+            options: &[&str]) -> Job {
+        Job{
+            line,
+            prefix: prefix.map(String::from),
+            targets: targets.iter().map(|&t| t.into()).collect(),
+            options: options.iter().map(|&o| o.into()).collect(),
+        }
+    }
+
+    #[test]
+    fn parse_csv() {
+        let jobs = parse("prefix,target,options\r\n\
+            hello,0x12345678,--ascii\n\
+            \n\
+            # a comment\n\
+            \"hello, \"\"world\"\"\",0x1234 0x5678,\
+                --ascii --target-mask 0xffff\n\
+            ,residue,--prefix-hex 68656c6c6f\n").unwrap();
+        assert_eq!(jobs, [
+            job(2, Some("hello"), &["0x12345678"], &["--ascii"]),
+            job(5, Some("hello, \"world\""), &["0x1234", "0x5678"],
+                &["--ascii", "--target-mask", "0xffff"]),
+            job(6, None, &["residue"], &["--prefix-hex", "68656c6c6f"]),
+        ]);
+        assert_eq!(jobs[2].args(),
+            ["--prefix-hex", "68656c6c6f", "--", "residue"]);
+    }
+
+    #[test]
+    fn parse_json() {
+        let jobs = parse("{\"prefix\":\"hello\",\"target\":\"0x12345678\",\
+                \"options\":[\"--ascii\"]}\n\
+            {\"prefix\":null,\"target\":[305419896,\"residue\"],\
+                \"options\":\"--prefix-hex 68656c6c6f\"}\n").unwrap();
+        assert_eq!(jobs, [
+            job(1, Some("hello"), &["0x12345678"], &["--ascii"]),
+            job(2, None, &["305419896", "residue"],
+                &["--prefix-hex", "68656c6c6f"]),
+        ]);
+        assert_eq!(jobs[0].args(), ["--ascii", "--", "hello", "0x12345678"]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse("hello,0x1234\n\"unterminated\n"),
+            Err("line 2: unterminated quote".into()));
+        assert!(parse("\"hello\"x,0x1234\n").is_err());
+        assert!(parse("hello,0x1234,--ascii,extra\n").is_err());
+        assert!(parse("{\"prefix\":1}\n").is_err());
+        assert!(parse("{\"prefix\":\"hello\",\"bogus\":1}\n").is_err());
+        assert!(parse("{\"target\":true}\n").is_err());
+        assert!(parse("{\"options\":[1]}\n").is_err());
+    }
+}
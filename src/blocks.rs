@@ -0,0 +1,168 @@
+// Chunked CRC layouts
+//
+// Many storage formats and serial protocols split data into blocks, each
+// followed by its own CRC. Here every block is size bytes of data followed
+// by its CRC, except the last block which may be shorter. CRCs take up as
+// many bytes as their width needs, so 4 bytes for a 32-bit CRC.
+//
+
+use core::ops::Range;
+use crate::crc::Crc32;
+use crate::frame::Endian;
+use crate::gf2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Blocks {
+    /// Data bytes in each block, not including the CRC
+    pub size: usize,
+    pub endian: Endian,
+}
+
+/// A block's data and CRC ranges, and its expected and stored CRC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub data: Range<usize>,
+    pub crc: Range<usize>,
+    pub expected: u32,
+    pub stored: u32,
+}
+
+impl Block {
+    pub fn ok(&self) -> bool {
+        self.expected == self.stored
+    }
+}
+
+impl Blocks {
+    /// Split a message into blocks, complaining if there's a trailing
+    /// block too short to hold its CRC
+    pub fn split(&self, crc32: &Crc32, data: &[u8])
+            -> Result<Vec<Block>, String> {
+        assert!(self.size > 0);
+        let n = crc32.width().div_ceil(8) as usize;
+        let mut blocks = vec![];
+        let mut off = 0;
+        while off < data.len() {
+            let len = (data.len() - off).min(self.size + n);
+            if len <= n {
+                return Err(format!("trailing block at {} has no data", off));
+            }
+            let block = off .. off+len-n;
+            let crc = off+len-n .. off+len;
+            blocks.push(Block{
+                expected: crc32.crc32(crc32.init(), &data[block.clone()]),
+                stored: self.endian.load(&data[crc.clone()]),
+                data: block,
+                crc,
+            });
+            off += len;
+        }
+        Ok(blocks)
+    }
+
+    /// Store the correct CRC in every block, returns the number of blocks
+    /// we fixed
+    pub fn fix(&self, crc32: &Crc32, data: &mut [u8])
+            -> Result<usize, String> {
+        let mut fixed = 0;
+        for block in self.split(crc32, data)? {
+            if !block.ok() {
+                self.endian.store(block.expected, &mut data[block.crc]);
+                fixed += 1;
+            }
+        }
+        Ok(fixed)
+    }
+
+    /// Change the last free bytes of every mismatched block so it matches
+    /// the CRC already stored, returns the number of blocks we forged and
+    /// the blocks we couldn't
+    pub fn forge(&self, crc32: &Crc32, data: &mut [u8], free: usize)
+            -> Result<(usize, Vec<usize>), String> {
+        let free = free.min(16);
+        let mut forged = 0;
+        let mut failed = vec![];
+        for (i, block) in self.split(crc32, data)?.into_iter().enumerate() {
+            if block.ok() {
+                continue;
+            }
+
+            // our CRC is affine in the bits of our free bytes
+            let d = &mut data[block.data];
+            let n = free.min(d.len());
+            let base = block.expected;
+            let len = d.len();
+            let cols = (len-n .. len)
+                .flat_map(|i| (0..8).map(move |b| (i, b)))
+                .map(|(i, b)| {
+                    d[i] ^= 1 << b;
+                    let col = crc32.crc32(crc32.init(), d) ^ base;
+                    d[i] ^= 1 << b;
+                    col as u64
+                })
+                .collect::<Vec<_>>();
+
+            match gf2::solve(&cols, (block.stored ^ base) as u64) {
+                Some(x) => {
+                    for (j, i) in (len-n .. len).enumerate() {
+                        d[i] ^= (x >> (8*j)) as u8;
+                    }
+                    forged += 1;
+                }
+                None => failed.push(i),
+            }
+        }
+        Ok((forged, failed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn fix_then_split() {
+        let mut rng = Rng::new(1);
+        let crc32 = Crc32::new(0x104c11db7);
+        let blocks = Blocks{size: 16, endian: Endian::Big};
+        // three full blocks and a short one
+        let mut data = vec![0; 3*20 + 7];
+        rng.fill(&mut data);
+        assert_eq!(blocks.fix(&crc32, &mut data).unwrap(), 4);
+        assert_eq!(blocks.fix(&crc32, &mut data).unwrap(), 0);
+
+        let split = blocks.split(&crc32, &data).unwrap();
+        assert_eq!(split.len(), 4);
+        assert_eq!(split[3].data, 60..63);
+        for block in &split {
+            assert!(block.ok());
+            let crc = &data[block.crc.clone()];
+            assert_eq!(block.stored,
+                u32::from_be_bytes(crc.try_into().unwrap()));
+        }
+
+        // a trailing block needs at least one byte of data
+        assert!(blocks.split(&crc32, &data[..64]).is_err());
+    }
+
+    #[test]
+    fn forge_matches_stored() {
+        let mut rng = Rng::new(2);
+        let crc32 = Crc32::new(0x104c11db7);
+        let blocks = Blocks{size: 32, endian: Endian::Little};
+        let mut data = vec![0; 4*36];
+        rng.fill(&mut data);
+        let before = data.clone();
+        let (forged, failed) = blocks.forge(&crc32, &mut data, 4).unwrap();
+        assert_eq!((forged, failed), (4, vec![]));
+        for block in blocks.split(&crc32, &data).unwrap() {
+            assert!(block.ok());
+            // only our last 4 bytes of data change
+            let end = block.data.end;
+            assert_eq!(data[block.data.start..end-4],
+                before[block.data.start..end-4]);
+            assert_eq!(data[block.crc.clone()], before[block.crc]);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+// Catalog of common CRC algorithms
+//
+// Parameters follow the Rocksoft model, as used by Greg Cook's CRC RevEng
+// catalogue, so each entry can be checked against its published check
+// value, the CRC of the ASCII string "123456789".
+//
+
+use core::fmt;
+use core::str::FromStr;
+use crate::crc::{Crc32, Crc64};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Algorithm {
+    /// Name in the RevEng catalogue
+    pub name: &'static str,
+    /// Other names this algorithm goes by
+    pub aliases: &'static [&'static str],
+    /// Width of our CRC in bits
+    pub width: u32,
+    /// Polynomial without its top bit, unreflected
+    pub poly: u64,
+    /// Initial register, unreflected
+    pub init: u64,
+    /// Reflect each input byte
+    pub refin: bool,
+    /// Reflect the final register
+    pub refout: bool,
+    /// Value xored into the final CRC
+    pub xorout: u64,
+    /// CRC of "123456789"
+    pub check: u64,
+}
+
+// shorthand for our table
+#[allow(clippy::too_many_arguments)]
+const fn alg(
+    name: &'static str,
+    aliases: &'static [&'static str],
+    width: u32,
+    poly: u64,
+    init: u64,
+    refin: bool,
+    refout: bool,
+    xorout: u64,
+    check: u64,
+) -> Algorithm {
+    Algorithm{name, aliases, width, poly, init, refin, refout, xorout, check}
+}
+
+/// Every algorithm we know by name
+pub const ALGORITHMS: &[Algorithm] = &[
+    alg("CRC-3/GSM", &[],
+        3, 0x3, 0x0, false, false, 0x7, 0x4),
+    alg("CRC-3/ROHC", &[],
+        3, 0x3, 0x7, true, true, 0x0, 0x6),
+    alg("CRC-4/G-704", &["CRC-4/ITU"],
+        4, 0x3, 0x0, true, true, 0x0, 0x7),
+    alg("CRC-4/INTERLAKEN", &[],
+        4, 0x3, 0xf, false, false, 0xf, 0xb),
+    alg("CRC-5/EPC-C1G2", &["CRC-5/EPC"],
+        5, 0x09, 0x09, false, false, 0x00, 0x00),
+    alg("CRC-5/G-704", &["CRC-5/ITU"],
+        5, 0x15, 0x00, true, true, 0x00, 0x07),
+    alg("CRC-5/USB", &[],
+        5, 0x05, 0x1f, true, true, 0x1f, 0x19),
+    alg("CRC-6/DARC", &[],
+        6, 0x19, 0x00, true, true, 0x00, 0x26),
+    alg("CRC-6/G-704", &["CRC-6/ITU"],
+        6, 0x03, 0x00, true, true, 0x00, 0x06),
+    alg("CRC-7/MMC", &["CRC-7"],
+        7, 0x09, 0x00, false, false, 0x00, 0x75),
+    alg("CRC-7/ROHC", &[],
+        7, 0x4f, 0x7f, true, true, 0x00, 0x53),
+    alg("CRC-7/UMTS", &[],
+        7, 0x45, 0x00, false, false, 0x00, 0x61),
+    alg("CRC-8/SMBUS", &["CRC-8"],
+        8, 0x07, 0x00, false, false, 0x00, 0xf4),
+    alg("CRC-8/AUTOSAR", &[],
+        8, 0x2f, 0xff, false, false, 0xff, 0xdf),
+    alg("CRC-8/BLUETOOTH", &[],
+        8, 0xa7, 0x00, true, true, 0x00, 0x26),
+    alg("CRC-8/CDMA2000", &[],
+        8, 0x9b, 0xff, false, false, 0x00, 0xda),
+    alg("CRC-8/DARC", &[],
+        8, 0x39, 0x00, true, true, 0x00, 0x15),
+    alg("CRC-8/DVB-S2", &[],
+        8, 0xd5, 0x00, false, false, 0x00, 0xbc),
+    alg("CRC-8/I-432-1", &["CRC-8/ITU"],
+        8, 0x07, 0x00, false, false, 0x55, 0xa1),
+    alg("CRC-8/MAXIM-DOW", &["CRC-8/MAXIM", "DOW-CRC"],
+        8, 0x31, 0x00, true, true, 0x00, 0xa1),
+    alg("CRC-8/ROHC", &[],
+        8, 0x07, 0xff, true, true, 0x00, 0xd0),
+    alg("CRC-8/SAE-J1850", &[],
+        8, 0x1d, 0xff, false, false, 0xff, 0x4b),
+    alg("CRC-8/WCDMA", &[],
+        8, 0x9b, 0x00, true, true, 0x00, 0x25),
+    alg("CRC-10/ATM", &["CRC-10", "CRC-10/I-610"],
+        10, 0x233, 0x000, false, false, 0x000, 0x199),
+    alg("CRC-11/FLEXRAY", &["CRC-11"],
+        11, 0x385, 0x01a, false, false, 0x000, 0x5a3),
+    alg("CRC-12/DECT", &["X-CRC-12"],
+        12, 0x80f, 0x000, false, false, 0x000, 0xf5b),
+    alg("CRC-12/UMTS", &["CRC-12/3GPP"],
+        12, 0x80f, 0x000, false, true, 0x000, 0xdaf),
+    alg("CRC-15/CAN", &["CRC-15"],
+        15, 0x4599, 0x0000, false, false, 0x0000, 0x059e),
+    alg("CRC-16/ARC", &["ARC", "CRC-16", "CRC-16/LHA", "CRC-IBM"],
+        16, 0x8005, 0x0000, true, true, 0x0000, 0xbb3d),
+    alg("CRC-16/DECT-R", &["R-CRC-16"],
+        16, 0x0589, 0x0000, false, false, 0x0001, 0x007e),
+    alg("CRC-16/DNP", &[],
+        16, 0x3d65, 0x0000, true, true, 0xffff, 0xea82),
+    alg("CRC-16/GENIBUS", &["CRC-16/DARC", "CRC-16/EPC", "CRC-16/I-CODE"],
+        16, 0x1021, 0xffff, false, false, 0xffff, 0xd64e),
+    alg("CRC-16/IBM-3740",
+        &["CRC-16/AUTOSAR", "CRC-16/CCITT-FALSE", "CRC-CCITT-FALSE"],
+        16, 0x1021, 0xffff, false, false, 0x0000, 0x29b1),
+    alg("CRC-16/IBM-SDLC",
+        &["CRC-16/ISO-HDLC", "CRC-16/ISO-IEC-14443-3-B", "CRC-16/X-25", "X-25"],
+        16, 0x1021, 0xffff, true, true, 0xffff, 0x906e),
+    alg("CRC-16/KERMIT",
+        &["CRC-16/CCITT", "CRC-16/CCITT-TRUE", "CRC-16/V-41-LSB", "KERMIT"],
+        16, 0x1021, 0x0000, true, true, 0x0000, 0x2189),
+    alg("CRC-16/MAXIM-DOW", &["CRC-16/MAXIM"],
+        16, 0x8005, 0x0000, true, true, 0xffff, 0x44c2),
+    alg("CRC-16/MCRF4XX", &[],
+        16, 0x1021, 0xffff, true, true, 0x0000, 0x6f91),
+    alg("CRC-16/MODBUS", &["MODBUS"],
+        16, 0x8005, 0xffff, true, true, 0x0000, 0x4b37),
+    alg("CRC-16/SPI-FUJITSU", &["CRC-16/AUG-CCITT"],
+        16, 0x1021, 0x1d0f, false, false, 0x0000, 0xe5cc),
+    alg("CRC-16/T10-DIF", &[],
+        16, 0x8bb7, 0x0000, false, false, 0x0000, 0xd0db),
+    alg("CRC-16/UMTS", &["CRC-16/BUYPASS", "CRC-16/VERIFONE"],
+        16, 0x8005, 0x0000, false, false, 0x0000, 0xfee8),
+    alg("CRC-16/USB", &[],
+        16, 0x8005, 0xffff, true, true, 0xffff, 0xb4c8),
+    alg("CRC-16/XMODEM",
+        &["CRC-16/ACORN", "CRC-16/LTE", "CRC-16/V-41-MSB", "XMODEM", "ZMODEM"],
+        16, 0x1021, 0x0000, false, false, 0x0000, 0x31c3),
+    alg("CRC-24/BLE", &[],
+        24, 0x00065b, 0x555555, true, true, 0x000000, 0xc25a56),
+    alg("CRC-24/OPENPGP", &["CRC-24"],
+        24, 0x864cfb, 0xb704ce, false, false, 0x000000, 0x21cf02),
+    alg("CRC-32/AIXM", &["CRC-32Q"],
+        32, 0x814141ab, 0x00000000, false, false, 0x00000000, 0x3010bf7f),
+    alg("CRC-32/AUTOSAR", &[],
+        32, 0xf4acfb13, 0xffffffff, true, true, 0xffffffff, 0x1697d06a),
+    alg("CRC-32/BASE91-D", &["CRC-32D"],
+        32, 0xa833982b, 0xffffffff, true, true, 0xffffffff, 0x87315576),
+    alg("CRC-32/BZIP2", &["CRC-32/AAL5", "CRC-32/DECT-B", "B-CRC-32"],
+        32, 0x04c11db7, 0xffffffff, false, false, 0xffffffff, 0xfc891918),
+    alg("CRC-32/CD-ROM-EDC", &[],
+        32, 0x8001801b, 0x00000000, true, true, 0x00000000, 0x6ec2edc4),
+    alg("CRC-32/CKSUM", &["CKSUM", "CRC-32/POSIX"],
+        32, 0x04c11db7, 0x00000000, false, false, 0xffffffff, 0x765e7680),
+    alg("CRC-32/ISCSI",
+        &["CRC-32/BASE91-C", "CRC-32/CASTAGNOLI", "CRC-32/INTERLAKEN",
+            "CRC-32C"],
+        32, 0x1edc6f41, 0xffffffff, true, true, 0xffffffff, 0xe3069283),
+    alg("CRC-32/ISO-HDLC",
+        &["CRC-32", "CRC-32/ADCCP", "CRC-32/V-42", "CRC-32/XZ", "PKZIP"],
+        32, 0x04c11db7, 0xffffffff, true, true, 0xffffffff, 0xcbf43926),
+    alg("CRC-32/JAMCRC", &["JAMCRC"],
+        32, 0x04c11db7, 0xffffffff, true, true, 0x00000000, 0x340bc6d9),
+    alg("CRC-32/MEF", &[],
+        32, 0x741b8cd7, 0xffffffff, true, true, 0x00000000, 0xd2c22f51),
+    alg("CRC-32/MPEG-2", &[],
+        32, 0x04c11db7, 0xffffffff, false, false, 0x00000000, 0x0376e6e7),
+    alg("CRC-32/XFER", &["XFER"],
+        32, 0x000000af, 0x00000000, false, false, 0x00000000, 0xbd0be338),
+    alg("CRC-40/GSM", &[],
+        40, 0x0004820009, 0x0000000000, false, false, 0xffffffffff,
+        0xd4164fc646),
+    alg("CRC-64/ECMA-182", &["CRC-64"],
+        64, 0x42f0e1eba9ea3693, 0x0000000000000000, false, false,
+        0x0000000000000000, 0x6c40df5f0b497347),
+    alg("CRC-64/GO-ISO", &[],
+        64, 0x000000000000001b, 0xffffffffffffffff, true, true,
+        0xffffffffffffffff, 0xb90956c775a41001),
+    alg("CRC-64/MS", &[],
+        64, 0x259c84cba6426349, 0xffffffffffffffff, true, true,
+        0x0000000000000000, 0x75d4b74f024eceea),
+    alg("CRC-64/NVME", &[],
+        64, 0xad93d23594c93659, 0xffffffffffffffff, true, true,
+        0xffffffffffffffff, 0xae8b14860a799888),
+    alg("CRC-64/REDIS", &[],
+        64, 0xad93d23594c935a9, 0x0000000000000000, true, true,
+        0x0000000000000000, 0xe9c6d914c4b8d9ca),
+    alg("CRC-64/WE", &[],
+        64, 0x42f0e1eba9ea3693, 0xffffffffffffffff, false, false,
+        0xffffffffffffffff, 0x62ec59e3f1a4f00a),
+    alg("CRC-64/XZ", &["CRC-64/GO-ECMA"],
+        64, 0x42f0e1eba9ea3693, 0xffffffffffffffff, true, true,
+        0xffffffffffffffff, 0x995dc9bbdf1939fa),
+];
+
+// names are matched loosely, ignoring case and punctuation, so crc32c,
+// CRC-32C, and crc_32c all match
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Find an algorithm by name or alias
+pub fn find(name: &str) -> Option<&'static Algorithm> {
+    let name = normalize(name);
+    ALGORITHMS.iter().find(|a| {
+        normalize(a.name) == name
+            || a.aliases.iter().any(|alias| normalize(alias) == name)
+    })
+}
+
+impl Algorithm {
+    /// Polynomial with its top bit, as our engines expect
+    pub fn polynomial(&self) -> u128 {
+        self.poly as u128 | (1 << self.width)
+    }
+
+    /// Create a CRC for this algorithm, only for widths up to 32 bits
+    pub fn crc32(&self) -> Crc32 {
+        assert!(self.width <= 32);
+        Crc32::new(self.polynomial() as u64)
+            .with_init(self.init as u32)
+            .with_xorout(self.xorout as u32)
+            .with_reflect(self.refin, self.refout)
+    }
+
+    /// Create a 64-bit CRC for this algorithm
+    pub fn crc64(&self) -> Crc64 {
+        Crc64::new(self.polynomial())
+            .with_init(self.init)
+            .with_xorout(self.xorout)
+            .with_reflect(self.refin, self.refout)
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Algorithm, String> {
+        find(s).copied()
+            .ok_or_else(|| format!("unknown algorithm {:?}, \
+                try crcbrute list-algorithms", s))
+    }
+}
+
+impl fmt::Display for Algorithm {
+    // the same format RevEng uses
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.width.div_ceil(4) as usize;
+        write!(f, "width={} poly=0x{:0w$x} init=0x{:0w$x} refin={} \
+                refout={} xorout=0x{:0w$x} check=0x{:0w$x} name={:?}",
+            self.width, self.poly, self.init, self.refin, self.refout,
+            self.xorout, self.check, self.name,
+            w=digits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_values() {
+        for alg in ALGORITHMS {
+            let check = if alg.width <= 32 {
+                let crc32 = alg.crc32();
+                crc32.crc32(crc32.init(), b"123456789") as u64
+            } else {
+                let crc64 = alg.crc64();
+                crc64.crc64(crc64.init(), b"123456789")
+            };
+            assert_eq!(check, alg.check, "{}", alg.name);
+        }
+    }
+
+    #[test]
+    fn find_by_alias() {
+        assert_eq!(find("crc32c").map(|a| a.name), Some("CRC-32/ISCSI"));
+        assert_eq!(find("CRC_32C").map(|a| a.name), Some("CRC-32/ISCSI"));
+        assert_eq!(find("crc-16/ccitt-false").map(|a| a.check), Some(0x29b1));
+        assert_eq!(find("crc-32/bogus"), None);
+        assert!("crc-32/bogus".parse::<Algorithm>().is_err());
+    }
+}
@@ -0,0 +1,513 @@
+// Charsets we know how to efficiently map a counter into
+//
+// Each charset provides a packer that spreads the bits of a counter over
+// the characters of the suffix, so every counter value maps to a valid
+// suffix and we never waste time on candidates we would reject.
+//
+
+use core::fmt;
+use core::ops::Range;
+use core::str::FromStr;
+
+/// Anything that can map a counter into a suffix
+pub trait Pack {
+    /// Number of symbols each character can be
+    fn symbols(&self) -> u64;
+
+    /// Map a counter into the characters of buf
+    fn pack(&self, i: u64, buf: &mut [u8]);
+
+    /// Number of suffixes we can pack, this is the range of our counter
+    fn space(&self, len: usize) -> u64 {
+        self.symbols().saturating_pow(len as u32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// Any byte, 8 bits per character
+    Raw,
+    /// H..=W and h..=w, 5 bits per character
+    Ascii,
+    /// Space and tab, 1 bit per character, invisible in most text
+    Whitespace,
+    /// A..=Z, a..=z, and 0..=9, ~5.95 bits per character
+    Alnum,
+    /// Printable Latin-1, 0x20..=0x7e and 0xa0..=0xff, ~7.58 bits per
+    /// character
+    Latin1,
+    /// A, C, G, and T, 2 bits per character
+    Dna,
+    /// 0..=7, 3 bits per character
+    Octal,
+    /// 0..=9 and a..=f, 4 bits per character
+    Hex,
+    /// A..=Z, a..=z, 0..=9, +, and /, 6 bits per character
+    Base64,
+    /// Any 7-bit byte, 0x00..=0x7f, 7 bits per character
+    SevenBit,
+}
+
+// alnum symbols, in order
+const ALNUM: &[u8; 62] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+// dna symbols, in order
+const DNA: &[u8; 4] = b"ACGT";
+
+// base64 symbols, in order
+const BASE64: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// mask of the low bits of each lane
+const fn lanes(bits: u32, stride: u32) -> u64 {
+    let lane = (1u64 << bits) - 1;
+    let mut mask = 0;
+    let mut i = 0;
+    while i < 64 {
+        mask |= lane << i;
+        i += stride;
+    }
+    mask
+}
+
+/// Spread the low 8*bits bits of a counter into 8 byte lanes, bits per
+/// lane, for any power-of-two charset with fewer than 256 symbols
+///
+/// This moves groups of characters into position, halving the group size
+/// each step, so it's branch-free and only a handful of instructions when
+/// bits is constant.
+#[inline]
+pub fn spread(i: u64, bits: u32) -> u64 {
+    debug_assert!(bits > 0 && bits < 8);
+    let i = i & lanes(8*bits, 64);
+    let i = ((i << (32 - 4*bits)) & (lanes(4*bits, 64) << 32))
+        | (i & lanes(4*bits, 64));
+    let i = ((i << (16 - 2*bits)) & (lanes(2*bits, 32) << 16))
+        | (i & lanes(2*bits, 32));
+    ((i << (8 - bits)) & (lanes(bits, 16) << 8))
+        | (i & lanes(bits, 16))
+}
+
+// spread our counter over buf 8 characters at a time, mapping each lane
+// into its final bytes
+#[inline]
+fn pack_lanes(i: u64, bits: u32, buf: &mut [u8], map: impl Fn(u64) -> u64) {
+    for (j, chunk) in buf.chunks_mut(8).enumerate() {
+        let shift = 8*bits as usize * j;
+        let i = if shift < 64 { i >> shift } else { 0 };
+        let x = map(spread(i, bits));
+        chunk.copy_from_slice(&x.to_le_bytes()[..chunk.len()]);
+    }
+}
+
+// look up each lane in a table
+#[inline]
+fn lookup(x: u64, table: &[u8]) -> u64 {
+    u64::from_le_bytes(x.to_le_bytes().map(|b| table[b as usize]))
+}
+
+/// Decode base64, url-safe base64 works too, padding is optional, and
+/// whitespace is ignored so wrapped lines can be pasted as is
+pub fn unbase64(s: &str) -> Result<Vec<u8>, String> {
+    let symbols = s.bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect::<Vec<_>>();
+    let pad = symbols.iter().rev().take(2).take_while(|&&b| b == b'=').count();
+    let symbols = &symbols[..symbols.len()-pad];
+    if symbols.len() % 4 == 1 {
+        return Err(format!("invalid base64 {:?}, truncated", s));
+    }
+
+    let mut data = vec![];
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &b in symbols {
+        let x = match b {
+            b'-' => 62,
+            b'_' => 63,
+            b => BASE64.iter().position(|&c| c == b)
+                .ok_or_else(|| format!("invalid base64 {:?}, unexpected {:?}",
+                    s, b as char))? as u32,
+        };
+        acc = (acc << 6) | x;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Ok(data)
+}
+
+impl Charset {
+    /// Number of characters in our suffix for a CRC of this width
+    pub fn suffix_len(&self, width: u32) -> usize {
+        // bits of slack beyond our CRC, more makes a solution more likely
+        let slack = match self {
+            // every target is reachable
+            Charset::Raw => 0,
+            // a solution is very likely
+            Charset::Ascii => 8,
+            // note the solver may need to grow this if our system of
+            // equations is singular
+            Charset::Whitespace => 0,
+            // a solution is very likely
+            Charset::Alnum => 3,
+            // a solution is very likely
+            Charset::Latin1 => 5,
+            // a solution is very likely
+            Charset::Dna => 8,
+            // the solver may need to grow this
+            Charset::Octal => 0,
+            // a solution is very likely
+            Charset::Hex => 8,
+            // a solution is very likely
+            Charset::Base64 => 8,
+            // the solver may need to grow this
+            Charset::SevenBit => 0,
+        };
+
+        // for CRC-32, this gives 4 raw bytes, 8 ascii characters, 32
+        // whitespace characters, etc
+        let bits = (self.symbols() as f64).log2();
+        ((width + slack) as f64 / bits - 1e-9).ceil() as usize
+    }
+
+    /// Is our packer affine? If so the CRC of our suffix is affine in the
+    /// bits of our counter, which lets us solve for the counter directly
+    pub fn is_affine(&self) -> bool {
+        matches!(self,
+            Charset::Raw
+                | Charset::Whitespace
+                | Charset::Octal
+                | Charset::SevenBit)
+    }
+
+    /// Can a character be this byte?
+    pub fn contains(&self, b: u8) -> bool {
+        match self {
+            Charset::Raw => true,
+            Charset::Ascii => matches!(b, b'H'..=b'W' | b'h'..=b'w'),
+            Charset::Whitespace => b == b' ' || b == b'\t',
+            Charset::Alnum => b.is_ascii_alphanumeric(),
+            Charset::Latin1 => matches!(b, 0x20..=0x7e | 0xa0..=0xff),
+            Charset::Dna => DNA.contains(&b),
+            Charset::Octal => matches!(b, b'0'..=b'7'),
+            Charset::Hex => matches!(b, b'0'..=b'9' | b'a'..=b'f'),
+            Charset::Base64 => BASE64.contains(&b),
+            Charset::SevenBit => b < 0x80,
+        }
+    }
+
+    /// Longest run of consecutive bytes our characters can be, sum
+    /// solvers need a contiguous range of bytes
+    pub fn longest_run(&self) -> (u8, u8) {
+        (0..=255u8)
+            .filter(|&b| self.contains(b))
+            .map(|lo| (lo, (lo..=255).take_while(|&b| self.contains(b))
+                .last().unwrap()))
+            .max_by_key(|&(lo, hi)| (hi - lo, core::cmp::Reverse(lo)))
+            .unwrap()
+    }
+}
+
+impl Pack for Charset {
+    fn symbols(&self) -> u64 {
+        match self {
+            Charset::Raw => 256,
+            Charset::Ascii => 32,
+            Charset::Whitespace => 2,
+            Charset::Alnum => 62,
+            Charset::Latin1 => 95 + 96,
+            Charset::Dna => 4,
+            Charset::Octal => 8,
+            Charset::Hex => 16,
+            Charset::Base64 => 64,
+            Charset::SevenBit => 128,
+        }
+    }
+
+    #[inline]
+    fn pack(&self, i: u64, buf: &mut [u8]) {
+        match self {
+            Charset::Raw => {
+                // our counter only fills the first 8 bytes, anything past
+                // that stays zero
+                for (j, chunk) in buf.chunks_mut(8).enumerate() {
+                    let x = if j == 0 { i } else { 0 };
+                    chunk.copy_from_slice(&x.to_le_bytes()[..chunk.len()]);
+                }
+            }
+            Charset::Ascii => {
+                // since DEL (0x7f) is a control character, and space (0x20)
+                // is sort of a control character, we limit our characters
+                // to H..=W (0x48..=0x57) and h..=w (0x68..=0x77). This gives
+                // us 5 bits per per character to work with.
+                //
+                // first get all bits into the right position, and then add
+                // to array of 0x48s
+                pack_lanes(i, 5, buf, |x| {
+                    let x = ((x << 1) & 0x2020_2020_2020_2020)
+                        | (x & 0x0f0f_0f0f_0f0f_0f0f);
+                    x + 0x4848_4848_4848_4848
+                });
+            }
+            Charset::Whitespace => {
+                for (j, b) in buf.iter_mut().enumerate() {
+                    let bit = i.checked_shr(j as u32).unwrap_or(0) & 1;
+                    *b = if bit != 0 { b'\t' } else { b' ' };
+                }
+            }
+            Charset::Alnum => {
+                // not a power of two, so we treat our counter as a base-62
+                // number, fortunately division by a constant is cheap
+                let mut i = i;
+                for b in buf.iter_mut() {
+                    *b = ALNUM[(i % 62) as usize];
+                    i /= 62;
+                }
+            }
+            Charset::Latin1 => {
+                // treat our counter as a base-191 number, skipping over
+                // DEL and the C1 control characters (0x7f..=0x9f)
+                let mut i = i;
+                for b in buf.iter_mut() {
+                    let d = (i % 191) as u8;
+                    *b = 0x20 + d + (((d >= 95) as u8) * 0x21);
+                    i /= 191;
+                }
+            }
+            Charset::Dna => pack_lanes(i, 2, buf, |x| lookup(x, DNA)),
+            Charset::Octal => pack_lanes(i, 3, buf,
+                |x| x | 0x3030_3030_3030_3030),
+            Charset::Hex => pack_lanes(i, 4, buf, |x| {
+                // 0..=9 map to 0x30.., a..=f to 0x61.., so add 0x27 to
+                // any lane >= 10, bit 7 tells us without carries
+                let hi = ((x + 0x7676_7676_7676_7676) >> 7)
+                    & 0x0101_0101_0101_0101;
+                x + 0x3030_3030_3030_3030 + hi*0x27
+            }),
+            Charset::Base64 => pack_lanes(i, 6, buf, |x| lookup(x, BASE64)),
+            Charset::SevenBit => pack_lanes(i, 7, buf, |x| x),
+        }
+    }
+}
+
+impl FromStr for Charset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Charset, String> {
+        match s {
+            "raw" => Ok(Charset::Raw),
+            "ascii" => Ok(Charset::Ascii),
+            "whitespace" => Ok(Charset::Whitespace),
+            "alnum" => Ok(Charset::Alnum),
+            "latin1" => Ok(Charset::Latin1),
+            "dna" => Ok(Charset::Dna),
+            "octal" => Ok(Charset::Octal),
+            "hex" => Ok(Charset::Hex),
+            "base64" => Ok(Charset::Base64),
+            "7bit" => Ok(Charset::SevenBit),
+            _ => Err(format!("unknown charset {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Charset::Raw => write!(f, "raw"),
+            Charset::Ascii => write!(f, "ascii"),
+            Charset::Whitespace => write!(f, "whitespace"),
+            Charset::Alnum => write!(f, "alnum"),
+            Charset::Latin1 => write!(f, "latin1"),
+            Charset::Dna => write!(f, "dna"),
+            Charset::Octal => write!(f, "octal"),
+            Charset::Hex => write!(f, "hex"),
+            Charset::Base64 => write!(f, "base64"),
+            Charset::SevenBit => write!(f, "7bit"),
+        }
+    }
+}
+
+/// Iterator over every suffix a charset can pack, in counter order
+///
+/// This is the same enumeration our search uses, so index i here is
+/// candidate i in the search.
+#[derive(Debug, Clone)]
+pub struct CharsetIter<P> {
+    packer: P,
+    len: usize,
+    range: Range<u64>,
+}
+
+impl<P: Pack> CharsetIter<P> {
+    /// Iterate over every suffix of len characters
+    pub fn new(packer: P, len: usize) -> CharsetIter<P> {
+        let space = packer.space(len);
+        CharsetIter{packer, len, range: 0..space}
+    }
+
+    /// Iterate over only a range of counters, useful for splitting up
+    /// the search
+    pub fn with_range(packer: P, len: usize, range: Range<u64>) -> CharsetIter<P> {
+        let space = packer.space(len);
+        let range = range.start.min(space) .. range.end.min(space);
+        CharsetIter{packer, len, range}
+    }
+
+    /// Counter of the next suffix
+    pub fn index(&self) -> u64 {
+        self.range.start
+    }
+
+    /// Map a specific counter into a suffix
+    pub fn get(&self, i: u64) -> Vec<u8> {
+        let mut buf = vec![0; self.len];
+        self.packer.pack(i, &mut buf);
+        buf
+    }
+
+    /// Pack the next suffix into buf without allocating, returning its
+    /// counter
+    pub fn next_into(&mut self, buf: &mut [u8]) -> Option<u64> {
+        let i = self.range.next()?;
+        self.packer.pack(i, &mut buf[..self.len]);
+        Some(i)
+    }
+}
+
+impl<P: Pack> Iterator for CharsetIter<P> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let i = self.range.next()?;
+        Some(self.get(i))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Vec<u8>> {
+        let i = self.range.nth(n)?;
+        Some(self.get(i))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    const CHARSETS: [Charset; 10] = [
+        Charset::Raw, Charset::Ascii, Charset::Whitespace, Charset::Alnum,
+        Charset::Latin1, Charset::Dna, Charset::Octal, Charset::Hex,
+        Charset::Base64, Charset::SevenBit,
+    ];
+
+    // find the counter a suffix came from, each character is a digit in
+    // base symbols, least significant first
+    fn unpack(charset: Charset, buf: &[u8]) -> u64 {
+        let mut digits = [None; 256];
+        for d in 0..charset.symbols() {
+            let mut b = [0];
+            charset.pack(d, &mut b);
+            assert_eq!(digits[b[0] as usize], None, "{} repeats", charset);
+            digits[b[0] as usize] = Some(d);
+        }
+        buf.iter().rev().fold(0, |i, &b| {
+            i*charset.symbols() + digits[b as usize].unwrap()
+        })
+    }
+
+    #[test]
+    fn pack_round_trips() {
+        let mut rng = Rng::new(1);
+        for charset in CHARSETS {
+            // as many characters as fit in our counter
+            let len = (64.0 / (charset.symbols() as f64).log2()) as usize;
+            for len in [1, 2, 3, len] {
+                let space = charset.space(len);
+                let mut buf = vec![0; len];
+                for k in 0..1000 {
+                    let i = if k < 256 { k % space } else { rng.below(space) };
+                    charset.pack(i, &mut buf);
+                    assert!(buf.iter().all(|&b| charset.contains(b)),
+                        "{} packed {:02x?}", charset, buf);
+                    assert_eq!(unpack(charset, &buf), i, "{}", charset);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pack_covers_charset() {
+        for charset in CHARSETS {
+            let bytes = (0..=255u8).filter(|&b| charset.contains(b)).count();
+            assert_eq!(bytes as u64, charset.symbols(), "{}", charset);
+        }
+    }
+
+    #[test]
+    fn spread_matches_naive() {
+        let mut rng = Rng::new(2);
+        for bits in 1..8 {
+            for _ in 0..1000 {
+                let i = rng.next_u64();
+                let naive = (0..8).fold(0, |x, j| {
+                    x | (((i >> (bits*j)) & ((1 << bits) - 1)) << (8*j))
+                });
+                assert_eq!(spread(i, bits), naive, "bits {}", bits);
+            }
+        }
+    }
+
+    #[test]
+    fn iter_matches_pack() {
+        let iter = CharsetIter::with_range(Charset::Hex, 3, 0x100..0x110);
+        let mut buf = [0; 3];
+        for (i, suffix) in (0x100..).zip(iter) {
+            Charset::Hex.pack(i, &mut buf);
+            assert_eq!(suffix, buf);
+        }
+        assert_eq!(CharsetIter::new(Charset::Dna, 4).count(), 256);
+    }
+
+    #[test]
+    fn unbase64_round_trips() {
+        // RFC 4648's test vectors
+        for (data, b64) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="),
+                ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="),
+                ("foobar", "Zm9vYmFy")] {
+            assert_eq!(unbase64(b64), Ok(data.as_bytes().to_vec()));
+            assert_eq!(unbase64(b64.trim_end_matches('=')),
+                Ok(data.as_bytes().to_vec()));
+        }
+
+        // url-safe and wrapped
+        assert_eq!(unbase64("-_8="), Ok(vec![0xfb, 0xff]));
+        assert_eq!(unbase64("+/8="), Ok(vec![0xfb, 0xff]));
+        assert_eq!(unbase64("Zm9v\nYmFy\n"), Ok(b"foobar".to_vec()));
+
+        // and random data, 6 bits at a time
+        let mut rng = Rng::new(3);
+        let mut data = vec![0; 300];
+        rng.fill(&mut data);
+        let b64 = data.chunks(3)
+            .flat_map(|c| {
+                let x = c.iter().enumerate()
+                    .fold(0u32, |x, (i, &b)| x | ((b as u32) << (16 - 8*i)));
+                (0..=c.len())
+                    .map(move |i| BASE64[(x >> (18 - 6*i)) as usize & 0x3f])
+            })
+            .map(|b| b as char)
+            .collect::<String>();
+        assert_eq!(unbase64(&b64), Ok(data));
+
+        assert!(unbase64("Z").is_err());
+        assert!(unbase64("Zm9v!").is_err());
+    }
+}
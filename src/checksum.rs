@@ -0,0 +1,109 @@
+// A common interface for checksums, and forging them
+//
+// Each checksum is a running state we update over our message, and
+// finalize into the value we compare against our targets. Checksums that
+// know how to build a suffix directly can provide a solver, anything else
+// falls back to brute force over our charset, so a new checksum only
+// needs its own module and an impl of this trait.
+//
+// CRCs implement this too, but their linearity allows much faster
+// searches, so our CRC search has its own engine.
+//
+
+use crate::charset::{Charset, CharsetIter, Pack};
+use crate::progress::{self, Progress};
+
+pub trait Checksum {
+    /// Width of our checksum in bits
+    fn width(&self) -> u32;
+
+    /// Bytes in each symbol we sum, our state only continues on symbol
+    /// boundaries, except at the end of our message
+    fn symbol_len(&self) -> usize {
+        1
+    }
+
+    /// State of an empty message
+    fn init(&self) -> u64;
+
+    /// Continue our state over more data
+    fn update(&self, state: u64, data: &[u8]) -> u64;
+
+    /// Checksum from our state
+    fn finalize(&self, state: u64) -> u32;
+
+    /// Checksum of a whole message
+    fn checksum(&self, data: &[u8]) -> u32 {
+        self.finalize(self.update(self.init(), data))
+    }
+
+    /// Is this a checksum we can actually produce?
+    fn is_valid(&self, sum: u32) -> bool {
+        (sum as u64) >> self.width() == 0
+    }
+
+    /// Build a suffix in our charset that takes prefix || suffix ||
+    /// postfix to any of our targets, returning the suffix and which
+    /// target it hit
+    ///
+    /// Returns None if we don't know how for this charset, in which case
+    /// we fall back to brute force.
+    fn solve_suffix(
+        &self,
+        _prefix: &[u8],
+        _postfix: &[u8],
+        _targets: &[u32],
+        _charset: Charset,
+    ) -> Option<Option<(Vec<u8>, u32)>> {
+        None
+    }
+}
+
+/// Find a suffix in our charset that takes prefix || suffix || postfix to
+/// any of our targets, using our checksum's solver if it has one
+pub fn forge(
+    checksum: &dyn Checksum,
+    prefix: &[u8],
+    postfix: &[u8],
+    targets: &[u32],
+    charset: Charset,
+) -> Option<(Vec<u8>, u32)> {
+    checksum.solve_suffix(prefix, postfix, targets, charset)
+        .unwrap_or_else(|| brute(checksum, prefix, postfix, targets, charset))
+}
+
+/// Brute force the shortest suffix in our charset that takes
+/// prefix || suffix || postfix to any of our targets, giving up once a
+/// suffix has more than enough bits to reach every checksum
+pub fn brute(
+    checksum: &dyn Checksum,
+    prefix: &[u8],
+    postfix: &[u8],
+    targets: &[u32],
+    charset: Charset,
+) -> Option<(Vec<u8>, u32)> {
+    // only hash our prefix's whole symbols once, the rest we hash with
+    // each candidate
+    let n = checksum.symbol_len();
+    let (head, tail) = prefix.split_at(prefix.len() - prefix.len() % n);
+    let start = checksum.update(checksum.init(), head);
+
+    for len in 1 ..= charset.suffix_len(checksum.width()) + 1 {
+        let progress = Progress::new(charset.space(len));
+        let mut buf = [tail, &vec![0; len], postfix].concat();
+        let mut candidates = CharsetIter::new(charset, len);
+        while let Some(i) = candidates.next_into(&mut buf[tail.len()..]) {
+            if i & 0xffff == 0 && progress::requested() {
+                progress.report(i, &format!("0x{:x}", i));
+            }
+
+            let sum = checksum.finalize(checksum.update(start, &buf));
+            if let Some(&target) = targets.iter().find(|&&t| t == sum) {
+                let suffix = buf[tail.len() .. tail.len()+len].to_vec();
+                return Some((suffix, target));
+            }
+        }
+    }
+
+    None
+}
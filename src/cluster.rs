@@ -0,0 +1,451 @@
+// Distributing searches across machines
+//
+// A coordinator splits a search into shards and hands them out to workers
+// over TCP. Each worker runs its shards as child crcbrute processes with
+// --shard, and reports back, and once any worker finds a solution, the
+// coordinator tells everyone else to stop.
+//
+// The protocol is one JSON object per line, one request per connection:
+//
+//   worker:      {"op":"work","worker":"1234"}
+//   coordinator: {"op":"search","args":["hello","0x1234"],"shard":3,
+//                "shards":4096}, {"op":"wait"}, or {"op":"stop"}
+//
+//   worker:      {"op":"poll","worker":"1234"}
+//   coordinator: {"op":"ok"} or {"op":"stop"}
+//
+//   worker:      {"op":"done","worker":"1234","shard":3,"found":null}
+//   coordinator: {"op":"ok"} or {"op":"stop"}
+//
+//   worker:      {"op":"failed","worker":"1234","shard":3,"error":null}
+//   coordinator: {"op":"ok"} or {"op":"stop"}
+//
+// Any request can instead get {"op":"error","error":"..."}, and with a
+// token, every request also carries "token":"...".
+//
+// Where found is our child's output if it found a solution. A shard that
+// failed without an error, say its child crashed, is handed out again,
+// but an error, say a bad argument, would fail on every worker, so it
+// stops the whole search.
+//
+// Workers run whatever search their coordinator asks for, including
+// writing whatever files that search writes, so only point workers at
+// coordinators you trust. Coordinators only listen on localhost unless
+// told otherwise, and with a token, every request must carry the same
+// token, so strangers on the network can't claim shards or report fake
+// results.
+//
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::json::Value;
+use crate::progress::EXIT_NOT_FOUND;
+
+/// Port we listen on if we aren't told otherwise
+pub const DEFAULT_PORT: u16 = 7453;
+
+// how often workers check in
+const POLL: Duration = Duration::from_secs(1);
+
+// how long before we give up on a quiet worker and hand its shard to
+// someone else
+const QUIET: Duration = Duration::from_secs(30);
+
+// how long we keep telling workers to stop after we're done
+const LINGER: Duration = Duration::from_secs(3);
+
+// a message with just an op
+fn op(op: &str) -> Value {
+    Value::object([("op", Value::String(op.into()))])
+}
+
+// read one JSON line, and make sure it's an object with an op
+fn read_value(r: impl Read) -> io::Result<Value> {
+    let mut line = String::new();
+    // a request is never anywhere near this big, this just keeps a
+    // misbehaving peer from eating all of our memory
+    BufReader::new(r.take(1 << 20)).read_line(&mut line)?;
+    let v = Value::parse(&line)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    if v.get("op").and_then(Value::as_str).is_none() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+            "expected an op"));
+    }
+    Ok(v)
+}
+
+#[derive(Debug)]
+struct Coordinator {
+    token: Option<String>,
+    args: Vec<String>,
+    shards: u64,
+    // next shard we haven't handed out yet
+    next: u64,
+    // shards we need to hand out again, because their worker failed or
+    // went quiet
+    retry: Vec<u64>,
+    // shards being searched, and who's searching them
+    pending: BTreeMap<u64, String>,
+    // shards that have been exhausted
+    done: BTreeSet<u64>,
+    // when we last heard from each worker
+    seen: HashMap<String, Instant>,
+    found: Option<String>,
+    // the first shard that failed with an error, and its error
+    failed: Option<(u64, String)>,
+    finished: Option<Instant>,
+}
+
+impl Coordinator {
+    // should our workers keep going?
+    fn status(&self) -> Value {
+        if self.finished.is_some() {
+            op("stop")
+        } else {
+            op("ok")
+        }
+    }
+
+    // handle a single request, returning our response
+    fn handle(&mut self, worker: String, req: &Value) -> Value {
+        if let Some(token) = &self.token {
+            if req.get("token").and_then(Value::as_str) != Some(token.as_str()) {
+                return Value::object([
+                    ("op", Value::String("error".into())),
+                    ("error", Value::String("invalid token".into())),
+                ]);
+            }
+        }
+
+        let now = Instant::now();
+        self.seen.insert(worker.clone(), now);
+
+        // anyone we haven't heard from in a while is probably gone
+        let quiet = self.pending.iter()
+            .filter(|(_, w)| self.seen.get(*w)
+                .is_none_or(|&t| now - t > QUIET))
+            .map(|(&i, w)| (i, w.clone()))
+            .collect::<Vec<_>>();
+        for (i, w) in quiet {
+            eprintln!("coordinate: {} went quiet, retrying shard {}/{}",
+                w, i, self.shards);
+            self.pending.remove(&i);
+            self.retry.push(i);
+        }
+
+        let shard = req.get("shard").and_then(Value::as_u64)
+            .filter(|&i| i < self.shards);
+
+        match req.get("op").and_then(Value::as_str).unwrap() {
+            "work" if self.finished.is_some() => op("stop"),
+            "work" => {
+                let i = match self.retry.pop() {
+                    Some(i) => i,
+                    None if self.next < self.shards => {
+                        self.next += 1;
+                        self.next - 1
+                    }
+                    None => return op("wait"),
+                };
+                self.pending.insert(i, worker);
+                Value::object([
+                    ("op", Value::String("search".into())),
+                    ("args", Value::Array(self.args.iter()
+                        .map(|a| Value::String(a.clone()))
+                        .collect())),
+                    ("shard", Value::Number(i as f64)),
+                    ("shards", Value::Number(self.shards as f64)),
+                ])
+            }
+
+            "poll" => self.status(),
+
+            "done" if shard.is_some() => {
+                let i = shard.unwrap();
+                self.pending.remove(&i);
+                self.retry.retain(|&j| j != i);
+                match req.get("found").and_then(Value::as_str) {
+                    Some(found) => {
+                        if self.found.is_none() {
+                            eprintln!("coordinate: shard {}/{} found a \
+                                    solution on {}",
+                                i, self.shards, worker);
+                            self.found = Some(found.into());
+                            self.finished = Some(now);
+                        }
+                    }
+                    None => {
+                        self.done.insert(i);
+                        eprintln!("coordinate: shard {}/{} exhausted on {}, \
+                                {}/{} done",
+                            i, self.shards, worker,
+                            self.done.len(), self.shards);
+                        if self.done.len() as u64 == self.shards
+                                && self.finished.is_none() {
+                            self.finished = Some(now);
+                        }
+                    }
+                }
+                self.status()
+            }
+
+            "failed" if shard.is_some() => {
+                let i = shard.unwrap();
+                match req.get("error").and_then(Value::as_str) {
+                    Some(error) => {
+                        eprintln!("coordinate: shard {}/{} failed on {}: {}",
+                            i, self.shards, worker, error);
+                        self.pending.remove(&i);
+                        if self.failed.is_none() && self.found.is_none() {
+                            self.failed = Some((i, error.into()));
+                            self.finished.get_or_insert(now);
+                        }
+                    }
+                    None => {
+                        eprintln!("coordinate: shard {}/{} failed on {}, \
+                                retrying",
+                            i, self.shards, worker);
+                        if self.pending.remove(&i).is_some() {
+                            self.retry.push(i);
+                        }
+                    }
+                }
+                self.status()
+            }
+
+            op => Value::object([
+                ("op", Value::String("error".into())),
+                ("error", Value::String(format!("invalid request {:?}",
+                    op))),
+            ]),
+        }
+    }
+}
+
+/// How a distributed search ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// A worker found a solution, this is its output
+    Found(String),
+    /// Every shard was exhausted without finding a solution
+    Exhausted,
+    /// A shard failed with an error, which stops the whole search
+    Failed{shard: u64, error: String},
+}
+
+/// Hand out shards of a search until a worker finds a solution, every
+/// shard is exhausted, or a shard fails with an error
+///
+/// With a token, we only listen to workers with the same token.
+pub fn coordinate(
+    listen: &str,
+    token: Option<String>,
+    args: Vec<String>,
+    shards: u64,
+) -> io::Result<Outcome> {
+    let listener = TcpListener::bind(listen)?;
+    eprintln!("coordinate: listening on {}, {} shards",
+        listener.local_addr()?, shards);
+
+    let state = Arc::new(Mutex::new(Coordinator{
+        token,
+        args,
+        shards,
+        next: 0,
+        retry: vec![],
+        pending: BTreeMap::new(),
+        done: BTreeSet::new(),
+        seen: HashMap::new(),
+        found: None,
+        failed: None,
+        finished: None,
+    }));
+
+    // we need to notice when we're done even if no one is talking to us
+    listener.set_nonblocking(true)?;
+    loop {
+        let (stream, addr) = match listener.accept() {
+            Ok(accepted) => accepted,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                let state = state.lock().unwrap();
+                if state.finished.is_some_and(|t| t.elapsed() > LINGER) {
+                    return Ok(match (&state.found, &state.failed) {
+                        (Some(found), _) => Outcome::Found(found.clone()),
+                        (None, Some((shard, error))) => Outcome::Failed{
+                            shard: *shard,
+                            error: error.clone(),
+                        },
+                        (None, None) => Outcome::Exhausted,
+                    });
+                }
+                drop(state);
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            Err(_) => continue,
+        };
+
+        // a misbehaving worker shouldn't take down the coordinator, or
+        // leave us waiting forever
+        let state = state.clone();
+        thread::spawn(move || {
+            let mut stream = stream;
+            let _ = stream.set_nonblocking(false)
+                .and_then(|_| stream.set_read_timeout(Some(QUIET)))
+                .and_then(|_| read_value(&mut stream))
+                .and_then(|req| {
+                    // workers are only unique per machine
+                    let worker = format!("{}/{}", addr.ip(),
+                        req.get("worker").and_then(Value::as_str)
+                            .unwrap_or("?"));
+                    let res = state.lock().unwrap().handle(worker, &req);
+                    writeln!(stream, "{}", res)
+                });
+        });
+    }
+}
+
+// send a single request to our coordinator
+fn request(addr: &str, req: Value) -> io::Result<Value> {
+    let mut stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(QUIET))?;
+    writeln!(stream, "{}", req)?;
+    let res = read_value(&mut stream)?;
+    if let Some(err) = res.get("error").and_then(Value::as_str) {
+        return Err(io::Error::other(err.to_string()));
+    }
+    Ok(res)
+}
+
+/// Search shards for a coordinator until told to stop
+///
+/// Shards are searched by invoking exe with our coordinator's arguments
+/// and --shard, its exit status tells us if it found a solution, found
+/// nothing, or failed. With a token, we pass it along with every request.
+pub fn work(addr: &str, token: Option<String>, exe: PathBuf) -> io::Result<()> {
+    let id = std::process::id().to_string();
+    let req = |op: &str, extra: Vec<(&str, Value)>| Value::object(
+        [("op", Value::String(op.into())), ("worker", Value::String(
+            id.clone()))].into_iter()
+            .chain(token.clone().map(|t| ("token", Value::String(t))))
+            .chain(extra));
+
+    // wait for our coordinator to come up, but once it's up, losing it
+    // is an error
+    let res = loop {
+        match request(addr, req("work", vec![])) {
+            Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+                thread::sleep(POLL);
+            }
+            res => break res?,
+        }
+    };
+
+    let mut res = res;
+    loop {
+        match res.get("op").and_then(Value::as_str).unwrap() {
+            "search" => {}
+            "wait" => {
+                thread::sleep(POLL);
+                res = request(addr, req("work", vec![]))?;
+                continue;
+            }
+            _ => return Ok(()),
+        }
+
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData,
+            "invalid search from coordinator");
+        let args = match res.get("args") {
+            Some(Value::Array(args)) => args.iter()
+                .map(|a| a.as_str().map(String::from))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(invalid)?,
+            _ => return Err(invalid()),
+        };
+        let i = res.get("shard").and_then(Value::as_u64)
+            .ok_or_else(invalid)?;
+        let n = res.get("shards").and_then(Value::as_u64)
+            .ok_or_else(invalid)?;
+
+        // --shard goes first, in case our arguments end in --
+        eprintln!("worker: searching shard {}/{}", i, n);
+        let mut child = Command::new(&exe)
+            .arg(format!("--shard={}/{}", i, n))
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdout = child.stdout.take().unwrap();
+        let output = thread::spawn(move || {
+            let mut buf = vec![];
+            let _ = stdout.read_to_end(&mut buf);
+            buf
+        });
+        // pass our child's stderr through, but keep any errors so we can
+        // tell our coordinator what went wrong
+        let stderr = BufReader::new(child.stderr.take().unwrap());
+        let errors = thread::spawn(move || {
+            let mut errors = vec![];
+            for line in stderr.split(b'\n').map_while(Result::ok) {
+                let mut line = line;
+                line.push(b'\n');
+                let _ = io::stderr().write_all(&line);
+                if let Some(error) = line.strip_prefix(b"error: ") {
+                    errors.push(String::from_utf8_lossy(error)
+                        .trim_end().to_string());
+                }
+            }
+            errors
+        });
+
+        // check in while we search, someone else may have already found
+        // a solution
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            thread::sleep(POLL);
+            let res = request(addr, req("poll", vec![]));
+            if !matches!(res.as_ref().ok()
+                    .and_then(|res| res.get("op"))
+                    .and_then(Value::as_str), Some("ok")) {
+                let _ = child.kill();
+                let _ = child.wait();
+                return res.map(|_| ());
+            }
+        };
+        let output = output.join().unwrap();
+        let errors = errors.join().unwrap();
+
+        let shard = ("shard", Value::Number(i as f64));
+        res = match status.code() {
+            Some(0) => request(addr, req("done", vec![shard,
+                ("found", Value::String(
+                    String::from_utf8_lossy(&output).into_owned()))]))?,
+            Some(EXIT_NOT_FOUND) => request(addr, req("done", vec![shard,
+                ("found", Value::Null)]))?,
+            // errors are our search's fault, not our worker's, so
+            // retrying won't help
+            Some(1) => request(addr, req("failed", vec![shard,
+                ("error", Value::String(if errors.is_empty() {
+                    format!("{}", status)
+                } else {
+                    errors.join("; ")
+                }))]))?,
+            _ => request(addr, req("failed", vec![shard,
+                ("error", Value::Null)]))?,
+        };
+        if res.get("op").and_then(Value::as_str) != Some("ok") {
+            return Ok(());
+        }
+        res = request(addr, req("work", vec![]))?;
+    }
+}
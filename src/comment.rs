@@ -0,0 +1,51 @@
+// Comment syntax for wrapping our suffix
+//
+// This lets us append a suffix to source files without breaking
+// compilation, as long as our suffix is limited to printable characters
+// that can't end the comment early.
+//
+
+use core::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comment {
+    /// C++-style line comments, // ...
+    Slash,
+    /// Shell-style line comments, # ...
+    Hash,
+    /// C-style block comments, /* ... */
+    Block,
+}
+
+impl Comment {
+    /// Bytes that start our comment
+    pub fn leader(&self) -> &'static [u8] {
+        match self {
+            Comment::Slash => b"// ",
+            Comment::Hash => b"# ",
+            Comment::Block => b"/* ",
+        }
+    }
+
+    /// Bytes that end our comment
+    pub fn closer(&self) -> &'static [u8] {
+        match self {
+            Comment::Slash => b"\n",
+            Comment::Hash => b"\n",
+            Comment::Block => b" */",
+        }
+    }
+}
+
+impl FromStr for Comment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Comment, String> {
+        match s {
+            "//" | "c++" | "cpp" | "rust" | "go" | "js" => Ok(Comment::Slash),
+            "#" | "sh" | "python" | "ruby" | "toml" | "yaml" => Ok(Comment::Hash),
+            "/*" | "c" | "css" => Ok(Comment::Block),
+            _ => Err(format!("unknown comment syntax {:?}", s)),
+        }
+    }
+}
@@ -0,0 +1,326 @@
+// Coverage of sharded searches
+//
+// A search split into ranges, manually or across a cluster, can record
+// what each shard covered and found in a coverage file. These can then be
+// merged to find out how much of the keyspace is done, and what's left.
+//
+// The format is line-based text so it's easy to inspect and concatenate:
+//
+//   # crcbrute coverage
+//   search poly=0x11edc6f41 charset=ascii prefix=68656c6c6f ...
+//   domain 0..=0xffffffffff
+//   covered 0..=0x2820000
+//   solution 0x1234 6a4c6d70
+//
+// Where ranges are in candidate indices, and solutions are the index and
+// hex-encoded suffix.
+//
+
+use core::ops::RangeInclusive;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    /// Identifies the search, shards can only be merged if these match
+    pub search: String,
+    /// Every candidate index in the search
+    pub domain: RangeInclusive<u64>,
+    /// Ranges we've searched, sorted and non-overlapping after normalize
+    pub covered: Vec<RangeInclusive<u64>>,
+    /// Solutions we've found, by index
+    pub solutions: BTreeMap<u64, Vec<u8>>,
+}
+
+fn parse_u64(s: &str) -> Result<u64, String> {
+    let res = if let Some(s) = s.strip_prefix("0x") {
+        u64::from_str_radix(s, 16)
+    } else {
+        s.parse::<u64>()
+    };
+    res.map_err(|_| format!("invalid number {:?}", s))
+}
+
+fn parse_range(s: &str) -> Result<RangeInclusive<u64>, String> {
+    let (lo, hi) = s.split_once("..=")
+        .ok_or_else(|| format!("invalid range {:?}", s))?;
+    Ok(parse_u64(lo)? ..= parse_u64(hi)?)
+}
+
+/// Shard i of n of a range, each shard gets an equal slice, and the last
+/// shard picks up any remainder, None if our shard is empty
+pub fn shard(
+    range: &RangeInclusive<u64>,
+    i: u64,
+    n: u64,
+) -> Option<RangeInclusive<u64>> {
+    if range.is_empty() {
+        return None;
+    }
+    let lo = *range.start() as u128;
+    let len = *range.end() as u128 + 1 - lo;
+    let start = lo + len*i as u128/n as u128;
+    let end = lo + len*(i+1) as u128/n as u128;
+    (start < end).then(|| start as u64 ..= (end-1) as u64)
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn unhex(s: &str) -> Result<Vec<u8>, String> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err(format!("invalid hex {:?}", s));
+    }
+    (0..s.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i+2], 16)
+            .map_err(|_| format!("invalid hex {:?}", s)))
+        .collect()
+}
+
+impl Coverage {
+    pub fn new(search: String, domain: RangeInclusive<u64>) -> Coverage {
+        Coverage{
+            search,
+            domain,
+            covered: vec![],
+            solutions: BTreeMap::new(),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Coverage, String> {
+        let mut search = None;
+        let mut domain = None;
+        let mut covered = vec![];
+        let mut solutions = BTreeMap::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let err = |e: String| format!("line {}: {}", i+1, e);
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "search" => search = Some(value.to_string()),
+                "domain" => domain = Some(parse_range(value).map_err(err)?),
+                "covered" => covered.push(parse_range(value).map_err(err)?),
+                "solution" => {
+                    let (index, suffix) = value.split_once(' ')
+                        .unwrap_or((value, ""));
+                    solutions.insert(
+                        parse_u64(index).map_err(err)?,
+                        unhex(suffix).map_err(err)?);
+                }
+                _ => return Err(err(format!("unknown key {:?}", key))),
+            }
+        }
+
+        let mut coverage = Coverage{
+            search: search.ok_or("missing search")?,
+            domain: domain.ok_or("missing domain")?,
+            covered,
+            solutions,
+        };
+        coverage.normalize();
+        Ok(coverage)
+    }
+
+    /// Sort and coalesce our covered ranges
+    pub fn normalize(&mut self) {
+        self.covered.retain(|r| r.start() <= r.end());
+        self.covered.sort_by_key(|r| *r.start());
+        let mut merged: Vec<RangeInclusive<u64>> = vec![];
+        for r in self.covered.drain(..) {
+            match merged.last_mut() {
+                Some(last) if *r.start() <= last.end().saturating_add(1) => {
+                    *last = *last.start() ..= *last.end().max(r.end());
+                }
+                _ => merged.push(r),
+            }
+        }
+        self.covered = merged;
+    }
+
+    /// Merge another shard into this one
+    pub fn merge(&mut self, other: &Coverage) -> Result<(), String> {
+        if other.search != self.search {
+            return Err(format!("different searches:\n  {}\n  {}",
+                self.search, other.search));
+        }
+        if other.domain != self.domain {
+            return Err(format!("different domains, {:?} vs {:?}",
+                self.domain, other.domain));
+        }
+
+        self.covered.extend(other.covered.iter().cloned());
+        self.solutions.extend(other.solutions.iter()
+            .map(|(&i, s)| (i, s.clone())));
+        self.normalize();
+        Ok(())
+    }
+
+    /// Number of candidates covered, saturating, since a full 64-bit
+    /// domain doesn't fit
+    pub fn count(&self) -> u64 {
+        self.covered.iter()
+            .filter_map(|r| {
+                let lo = (*r.start()).max(*self.domain.start());
+                let hi = (*r.end()).min(*self.domain.end());
+                (lo <= hi).then(|| (hi - lo).saturating_add(1))
+            })
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Size of our domain, saturating
+    pub fn total(&self) -> u64 {
+        if self.domain.is_empty() {
+            return 0;
+        }
+        (self.domain.end() - self.domain.start()).saturating_add(1)
+    }
+
+    /// Ranges in our domain not yet covered, assumes we're normalized
+    pub fn remaining(&self) -> Vec<RangeInclusive<u64>> {
+        let mut remaining = vec![];
+        let mut next = Some(*self.domain.start());
+        for r in &self.covered {
+            let lo = match next {
+                Some(lo) if lo <= *self.domain.end() => lo,
+                _ => break,
+            };
+            if *r.start() > lo {
+                remaining.push(lo ..= (r.start()-1).min(*self.domain.end()));
+            }
+            next = r.end().checked_add(1).map(|hi| hi.max(lo));
+        }
+        if let Some(lo) = next {
+            if lo <= *self.domain.end() {
+                remaining.push(lo ..= *self.domain.end());
+            }
+        }
+        remaining
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# crcbrute coverage")?;
+        writeln!(f, "search {}", self.search)?;
+        writeln!(f, "domain 0x{:x}..=0x{:x}",
+            self.domain.start(), self.domain.end())?;
+        for r in &self.covered {
+            writeln!(f, "covered 0x{:x}..=0x{:x}", r.start(), r.end())?;
+        }
+        for (i, suffix) in &self.solutions {
+            writeln!(f, "solution 0x{:x} {}", i, hex(suffix))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(
+        domain: RangeInclusive<u64>,
+        covered: &[RangeInclusive<u64>],
+    ) -> Coverage {
+        let mut c = Coverage::new("test".into(), domain);
+        c.covered = covered.to_vec();
+        c.normalize();
+        c
+    }
+
+    #[test]
+    fn remaining_gaps() {
+        let c = coverage(0..=99, &[]);
+        assert_eq!(c.remaining(), vec![0..=99]);
+        let c = coverage(0..=99, &[10..=19, 30..=39, 15..=24]);
+        assert_eq!(c.covered, vec![10..=24, 30..=39]);
+        assert_eq!(c.remaining(), vec![0..=9, 25..=29, 40..=99]);
+        let c = coverage(0..=99, &[0..=49, 50..=99]);
+        assert_eq!(c.remaining(), vec![]);
+        assert_eq!(c.count(), 100);
+    }
+
+    #[test]
+    fn remaining_clips_to_domain() {
+        // covered ranges can stick out of our domain
+        let c = coverage(100..=199, &[0..=109, 150..=159, 190..=300]);
+        assert_eq!(c.remaining(), vec![110..=149, 160..=189]);
+        assert_eq!(c.count(), 30);
+        let c = coverage(100..=199, &[0..=9, 250..=260]);
+        assert_eq!(c.remaining(), vec![100..=199]);
+        assert_eq!(c.count(), 0);
+    }
+
+    #[test]
+    fn remaining_at_the_edges() {
+        let c = coverage(0..=u64::MAX, &[]);
+        assert_eq!(c.remaining(), vec![0..=u64::MAX]);
+        assert_eq!(c.total(), u64::MAX);
+        let c = coverage(0..=u64::MAX, &[5..=u64::MAX]);
+        assert_eq!(c.remaining(), vec![0..=4]);
+        let c = coverage(0..=u64::MAX, &[0..=u64::MAX]);
+        assert_eq!(c.remaining(), vec![]);
+        assert_eq!(c.count(), u64::MAX);
+        let c = coverage(u64::MAX-9..=u64::MAX, &[0..=u64::MAX-5]);
+        assert_eq!(c.remaining(), vec![u64::MAX-4..=u64::MAX]);
+    }
+
+    #[test]
+    fn shards_tile_their_range() {
+        for (range, n) in [(0..=9, 16), (0..=99, 7), (5..=5, 3),
+                (0..=u64::MAX, 4096), (u64::MAX-2..=u64::MAX, 2)] {
+            let shards = (0..n)
+                .filter_map(|i| shard(&range, i, n))
+                .collect::<Vec<_>>();
+            assert_eq!(shards.first().map(|r| *r.start()), Some(*range.start()));
+            assert_eq!(shards.last().map(|r| *r.end()), Some(*range.end()));
+            for w in shards.windows(2) {
+                assert_eq!(*w[0].end() + 1, *w[1].start());
+            }
+        }
+
+        // more shards than candidates leaves some shards empty
+        assert_eq!(shard(&(0..=9), 0, 16), None);
+        assert_eq!(shard(&(0..=9), 15, 16), Some(9..=9));
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 10..=9;
+        assert_eq!(shard(&empty, 0, 1), None);
+    }
+
+    #[test]
+    fn remaining_plus_count_is_total() {
+        let mut rng = crate::rng::Rng::new(1);
+        for _ in 0..100 {
+            let covered = (0..rng.below(8))
+                .map(|_| {
+                    let lo = rng.below(1000);
+                    lo ..= lo + rng.below(100)
+                })
+                .collect::<Vec<_>>();
+            let c = coverage(200..=899, &covered);
+            let left = c.remaining().iter()
+                .map(|r| r.end() - r.start() + 1)
+                .sum::<u64>();
+            assert_eq!(left + c.count(), c.total());
+        }
+    }
+
+    #[test]
+    fn merge_and_parse() {
+        let mut a = coverage(0..=99, &[0..=9]);
+        a.solutions.insert(5, vec![0xab]);
+        let b = coverage(0..=99, &[10..=19, 50..=59]);
+        a.merge(&b).unwrap();
+        assert_eq!(a.remaining(), vec![20..=49, 60..=99]);
+        assert_eq!(Coverage::parse(&a.to_string()).unwrap(), a);
+
+        let c = coverage(0..=98, &[]);
+        assert!(a.merge(&c).is_err());
+    }
+}
@@ -0,0 +1,1079 @@
+// CRC implementation using Barret reduction
+//
+
+use crate::pmul::{gfpow, pmul32, pmul32x2, pmul64};
+use crate::checksum::Checksum;
+
+// software polynomial division
+pub fn pdivmod64(a: u64, b: u64) -> Option<(u64, u64)> {
+    if b == 0 {
+        return None;
+    }
+
+    let mut q = 0;
+    let mut r = a;
+    while r.leading_zeros() <= b.leading_zeros() {
+        q ^= 1 << (b.leading_zeros()-r.leading_zeros());
+        r ^= b << (b.leading_zeros()-r.leading_zeros());
+    }
+    Some((q, r))
+}
+
+pub fn pdiv64(a: u64, b: u64) -> u64 {
+    pdivmod64(a, b).unwrap().0
+}
+
+pub fn pmod64(a: u64, b: u64) -> u64 {
+    pdivmod64(a, b).unwrap().1
+}
+
+pub fn pdivmod128(a: u128, b: u128) -> Option<(u128, u128)> {
+    if b == 0 {
+        return None;
+    }
+
+    let mut q = 0;
+    let mut r = a;
+    while r.leading_zeros() <= b.leading_zeros() {
+        q ^= 1 << (b.leading_zeros()-r.leading_zeros());
+        r ^= b << (b.leading_zeros()-r.leading_zeros());
+    }
+    Some((q, r))
+}
+
+pub fn pdiv128(a: u128, b: u128) -> u128 {
+    pdivmod128(a, b).unwrap().0
+}
+
+
+// CRC with precomputed Barret constants
+//
+// Narrower CRCs are just 32-bit CRCs with their polynomial shifted up, the
+// remainder mod p*x^(32-width) is our CRC times x^(32-width), which in our
+// reflected register lands in the low width bits. So the same reduction
+// works for any width up to 32 bits.
+pub struct Crc32 {
+    p: u64,
+    width: u32,
+    ones: u32,
+    b: u32,
+    p_r: u32,
+    b_r: u32,
+    init: u32,
+    xorout: u32,
+    refin: bool,
+    refout: bool,
+    castagnoli: bool,
+    // folding constants, x^(64+d-1) and x^(d-1) mod p reflected, for
+    // folding 512 and 128 bits
+    k512: (u64, u64),
+    k128: (u64, u64),
+}
+
+// messages at least this long are folded
+const FOLD_MIN: usize = 128;
+
+impl Crc32 {
+    /// Create a CRC from a polynomial with its top bit, its degree is the
+    /// width of our CRC, up to 32 bits
+    pub fn new(p: u64) -> Crc32 {
+        let width = 63 - p.leading_zeros();
+        assert!((1..=32).contains(&width));
+        let ones = (!0u64 >> (64 - width)) as u32;
+
+        // shift our polynomial up to 32 bits
+        let p_ = p << (32 - width);
+        // calculate our barret constant
+        let b = pdiv64(p_ << 32, p_) as u32;
+        // and bit-reversed representations
+        let p_r = (p_ as u32).reverse_bits();
+        let b_r = b.reverse_bits();
+        // and folding constants, one less than you'd expect since a
+        // reflected multiply leaves its product shifted down by one
+        let k = |e| gfpow(2, e, p_ & 0xffffffff, 32).reverse_bits();
+        let k512 = (k(64+512-1), k(512-1));
+        let k128 = (k(64+128-1), k(128-1));
+
+        Crc32{p, width, ones, b, p_r, b_r, init: ones, xorout: ones,
+            refin: true, refout: true, castagnoli: p == 0x11edc6f41,
+            k512, k128}
+    }
+
+    /// Start our register at a different value, by default this is all
+    /// ones. Note init is unreflected, as in most CRC catalogs
+    pub fn with_init(self, init: u32) -> Crc32 {
+        Crc32{init: self.reflect(init), ..self}
+    }
+
+    /// Xor our final register with a different value, by default this is
+    /// all ones
+    pub fn with_xorout(self, xorout: u32) -> Crc32 {
+        Crc32{xorout, ..self}
+    }
+
+    /// Start from the CRC of an earlier message, so our CRC picks up where
+    /// that message left off
+    pub fn with_crc(self, crc: u32) -> Crc32 {
+        Crc32{init: self.unfinalize(crc), ..self}
+    }
+
+    /// Choose whether we reflect input bytes and the final register, by
+    /// default we reflect both
+    ///
+    /// Our register is always reflected internally, so an unreflected
+    /// input is just bit-reversed bytes, and an unreflected output is a
+    /// bit-reversed register.
+    pub fn with_reflect(self, refin: bool, refout: bool) -> Crc32 {
+        Crc32{refin, refout, ..self}
+    }
+
+    // reflect a value in our width
+    #[inline]
+    fn reflect(&self, x: u32) -> u32 {
+        x.reverse_bits() >> (32 - self.width)
+    }
+
+    // CRC to register and back
+    #[inline]
+    fn unfinalize(&self, crc: u32) -> u32 {
+        let crc = crc ^ self.xorout;
+        if self.refout { crc } else { self.reflect(crc) }
+    }
+
+    #[inline]
+    fn finalize(&self, crc: u32) -> u32 {
+        let crc = if self.refout { crc } else { self.reflect(crc) };
+        crc ^ self.xorout
+    }
+
+    // bytes as our register sees them
+    #[inline]
+    fn word<const REFIN: bool>(word: &[u8]) -> u32 {
+        let word = u32::from_le_bytes(<[u8; 4]>::try_from(word).unwrap());
+        if REFIN { word } else { word.reverse_bits().swap_bytes() }
+    }
+
+    #[inline]
+    fn byte<const REFIN: bool>(b: u8) -> u32 {
+        (if REFIN { b } else { b.reverse_bits() }) as u32
+    }
+
+    #[inline]
+    fn chunk<const REFIN: bool>(chunk: &[u8]) -> u128 {
+        let chunk = u128::from_le_bytes(
+            <[u8; 16]>::try_from(chunk).unwrap());
+        if REFIN { chunk } else { chunk.reverse_bits().swap_bytes() }
+    }
+
+    // reduce a register that's had a word xored in
+    #[inline]
+    fn reduce(&self, crc: u32) -> u32 {
+        let (lo, _) = pmul32(crc, self.b_r);
+        let (lo, hi) = pmul32((lo << 1) ^ crc, self.p_r);
+        (hi << 1) | (lo >> 31)
+    }
+
+    // multiply 128 bits of message by x^d, without reducing all the way
+    //
+    // The low half of our reflected chunk holds its high terms, so:
+    //
+    //   x*x^d = x_hi*x^(64+d) + x_lo*x^d
+    //
+    // Where each term fits in 96 bits if we reduce our constants mod p.
+    #[inline]
+    fn fold(x: u128, k: (u64, u64)) -> u128 {
+        let (lo0, hi0) = pmul64(x as u64, k.0);
+        let (lo1, hi1) = pmul64((x >> 64) as u64, k.1);
+        ((lo0 ^ lo1) as u128) | (((hi0 ^ hi1) as u128) << 64)
+    }
+
+    // CRC a long message by folding
+    #[cold]
+    #[inline(never)]
+    fn crc32_long(&self, crc: u32, data: &[u8]) -> u32 {
+        if self.refin {
+            self.crc32_fold::<true>(crc, data)
+        } else {
+            self.crc32_fold::<false>(crc, data)
+        }
+    }
+
+    // fold a long message down to a register, and finish any leftover
+    // bytes with crc32_
+    //
+    // We fold 4 128-bit lanes at a time, so we keep 4 independent
+    // multiplies in flight, and only pay for Barret reduction at the end.
+    #[inline]
+    fn crc32_fold<const REFIN: bool>(&self, crc: u32, data: &[u8]) -> u32 {
+        let crc = self.unfinalize(crc);
+        let mut blocks = data.chunks_exact(64);
+        let block = blocks.next().unwrap();
+        let mut x = [0, 1, 2, 3]
+            .map(|i| Self::chunk::<REFIN>(&block[16*i..16*(i+1)]));
+        x[0] ^= crc as u128;
+        for block in &mut blocks {
+            for (i, x) in x.iter_mut().enumerate() {
+                *x = Self::fold(*x, self.k512)
+                    ^ Self::chunk::<REFIN>(&block[16*i..16*(i+1)]);
+            }
+        }
+
+        // fold our lanes together, and any leftover chunks
+        let mut chunks = blocks.remainder().chunks_exact(16);
+        let mut acc = x[0];
+        for chunk in x[1..].iter().copied()
+                .chain((&mut chunks).map(Self::chunk::<REFIN>)) {
+            acc = Self::fold(acc, self.k128) ^ chunk;
+        }
+
+        // what's left is just 16 bytes of message with a zero register
+        let mut crc = 0;
+        for i in 0..4 {
+            crc = self.reduce(crc ^ (acc >> (32*i)) as u32);
+        }
+        self.crc32_::<REFIN>(self.finalize(crc), chunks.remainder())
+    }
+
+    /// Width of our CRC in bits
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Mask of the bits our CRC can use
+    pub fn mask(&self) -> u32 {
+        self.ones
+    }
+
+    /// CRC of an empty message, this is what a CRC of a whole message
+    /// should start from, and is zero unless we have a different init or
+    /// xorout
+    pub fn init(&self) -> u32 {
+        self.finalize(self.init)
+    }
+
+    /// What we xor our final register with
+    pub fn xorout(&self) -> u32 {
+        self.xorout
+    }
+
+    /// Do we reflect input bytes?
+    pub fn refin(&self) -> bool {
+        self.refin
+    }
+
+    /// Do we reflect our final register?
+    pub fn refout(&self) -> bool {
+        self.refout
+    }
+
+    #[inline]
+    pub fn crc32(&self, crc: u32, data: &[u8]) -> u32 {
+        // long messages are folded out of line, so they don't bloat our
+        // search loop, which only ever sees short messages
+        if data.len() >= FOLD_MIN && !self.sse42() {
+            return self.crc32_long(crc, data);
+        }
+
+        // specialize for our reflection, so we only check it once
+        if self.refin {
+            self.crc32_::<true>(crc, data)
+        } else {
+            self.crc32_::<false>(crc, data)
+        }
+    }
+
+    // can we use x86's crc32 instruction? this only knows CRC-32C, and
+    // only with reflected input
+    #[inline]
+    fn sse42(&self) -> bool {
+        cfg!(all(
+            not(feature="sw-pmul"),
+            target_arch="x86_64",
+            target_feature="sse4.2"
+        )) && self.refin && self.castagnoli
+    }
+
+    #[inline]
+    fn crc32_<const REFIN: bool>(&self, crc: u32, data: &[u8]) -> u32 {
+        // undo our xorout, usually a bit invert, and any reflection
+        let mut crc = self.unfinalize(crc);
+
+        #[cfg(all(
+            not(feature="sw-pmul"),
+            target_arch="x86_64",
+            target_feature="sse4.2"
+        ))]
+        if REFIN && self.castagnoli {
+            return self.finalize(crc32c_sse42(crc, data));
+        }
+
+        // operate on 4-byte chunks first
+        let mut words = data.chunks_exact(4);
+        for word in &mut words {
+            crc = self.reduce(crc ^ Self::word::<REFIN>(word));
+        }
+
+        // now clean up any remaining bytes
+        for b in words.remainder() {
+            crc ^= Self::byte::<REFIN>(*b);
+            let (lo, _) = pmul32(crc << 24, self.b_r);
+            let (lo, hi) = pmul32((lo << 1) ^ (crc << 24), self.p_r);
+            crc = (crc >> 8) ^ ((hi << 1) | (lo >> 31));
+        }
+
+        // reflect and apply our xorout
+        self.finalize(crc)
+    }
+
+    /// CRC of only the first bits bits of our data, for messages that
+    /// aren't a whole number of bytes, the bits of a partial byte are
+    /// taken in the order we read them, lsb first if we reflect our input
+    pub fn crc32_bits(&self, crc: u32, data: &[u8], bits: usize) -> u32 {
+        let crc = self.crc32(crc, &data[..bits/8]);
+        if bits.is_multiple_of(8) {
+            return crc;
+        }
+
+        // a bit at a time is slow, but this is at most 7 bits
+        let mut crc = self.unfinalize(crc);
+        let b = data[bits/8];
+        let b = if self.refin { b } else { b.reverse_bits() };
+        for i in 0..bits%8 {
+            crc ^= ((b >> i) & 1) as u32;
+            let lsb = crc & 1;
+            crc >>= 1;
+            if lsb != 0 {
+                crc ^= self.p_r;
+            }
+        }
+        self.finalize(crc)
+    }
+
+    /// CRC two messages of the same length at once, this lets us make
+    /// full use of vector lanes when checking candidates
+    #[inline(always)]
+    pub fn crc32x2(&self, crc: [u32; 2], data: [&[u8]; 2]) -> [u32; 2] {
+        // specialize for our reflection, so we only check it once
+        if self.refin {
+            self.crc32x2_::<true>(crc, data)
+        } else {
+            self.crc32x2_::<false>(crc, data)
+        }
+    }
+
+    #[inline(always)]
+    fn crc32x2_<const REFIN: bool>(
+        &self,
+        crc: [u32; 2],
+        data: [&[u8]; 2],
+    ) -> [u32; 2] {
+        assert_eq!(data[0].len(), data[1].len());
+
+        // the crc32 instruction is faster than both our lanes
+        if self.sse42() {
+            return [
+                self.crc32_::<REFIN>(crc[0], data[0]),
+                self.crc32_::<REFIN>(crc[1], data[1]),
+            ];
+        }
+
+        // undo our xorout, usually a bit invert, and any reflection
+        let mut crc = [self.unfinalize(crc[0]), self.unfinalize(crc[1])];
+
+        // operate on 4-byte chunks first
+        let mut words0 = data[0].chunks_exact(4);
+        let mut words1 = data[1].chunks_exact(4);
+        for (word0, word1) in (&mut words0).zip(&mut words1) {
+            crc[0] ^= Self::word::<REFIN>(word0);
+            crc[1] ^= Self::word::<REFIN>(word1);
+            let [(lo0, _), (lo1, _)] = pmul32x2(crc, self.b_r);
+            let [(lo0, hi0), (lo1, hi1)] = pmul32x2(
+                [(lo0 << 1) ^ crc[0], (lo1 << 1) ^ crc[1]],
+                self.p_r);
+            crc = [(hi0 << 1) | (lo0 >> 31), (hi1 << 1) | (lo1 >> 31)];
+        }
+
+        // now clean up any remaining bytes
+        for (b0, b1) in words0.remainder().iter().zip(words1.remainder()) {
+            crc[0] ^= Self::byte::<REFIN>(*b0);
+            crc[1] ^= Self::byte::<REFIN>(*b1);
+            let [(lo0, _), (lo1, _)] = pmul32x2(
+                [crc[0] << 24, crc[1] << 24],
+                self.b_r);
+            let [(lo0, hi0), (lo1, hi1)] = pmul32x2(
+                [(lo0 << 1) ^ (crc[0] << 24), (lo1 << 1) ^ (crc[1] << 24)],
+                self.p_r);
+            crc = [
+                (crc[0] >> 8) ^ ((hi0 << 1) | (lo0 >> 31)),
+                (crc[1] >> 8) ^ ((hi1 << 1) | (lo1 >> 31)),
+            ];
+        }
+
+        // reflect and apply our xorout
+        [self.finalize(crc[0]), self.finalize(crc[1])]
+    }
+
+    /// CRC four messages of the same length at once, with AVX-512 and
+    /// vpclmulqdq all four fit in one vector register
+    #[inline(always)]
+    pub fn crc32x4(&self, crc: [u32; 4], data: [&[u8]; 4]) -> [u32; 4] {
+        // specialize for our reflection, so we only check it once
+        if self.refin {
+            self.crc32x4_::<true>(crc, data)
+        } else {
+            self.crc32x4_::<false>(crc, data)
+        }
+    }
+
+    #[inline(always)]
+    fn crc32x4_<const REFIN: bool>(
+        &self,
+        crc: [u32; 4],
+        data: [&[u8]; 4],
+    ) -> [u32; 4] {
+        // the crc32 instruction still beats vpclmulqdq for CRC-32C
+        #[cfg(all(
+            not(feature="sw-pmul"),
+            target_arch="x86_64",
+            target_feature="avx512f",
+            target_feature="vpclmulqdq"
+        ))]
+        if !self.sse42() {
+            return self.crc32x4_avx512::<REFIN>(crc, data);
+        }
+
+        let [c0, c1] = self.crc32x2_::<REFIN>(
+            [crc[0], crc[1]], [data[0], data[1]]);
+        let [c2, c3] = self.crc32x2_::<REFIN>(
+            [crc[2], crc[3]], [data[2], data[3]]);
+        [c0, c1, c2, c3]
+    }
+
+    // the same Barret reduction as crc32_, but with each CRC in the low
+    // qword of a 128-bit lane, since that's what vpclmulqdq multiplies
+    //
+    // our 32-bit products fit in a qword, so (hi << 1) | (lo >> 31) is
+    // just the product shifted down by 31
+    #[cfg(all(
+        not(feature="sw-pmul"),
+        target_arch="x86_64",
+        target_feature="avx512f",
+        target_feature="vpclmulqdq"
+    ))]
+    #[inline(always)]
+    fn crc32x4_avx512<const REFIN: bool>(
+        &self,
+        crc: [u32; 4],
+        data: [&[u8]; 4],
+    ) -> [u32; 4] {
+        use core::arch::x86_64::*;
+        let len = data[0].len();
+        assert!(data.iter().all(|d| d.len() == len));
+
+        let lanes = |x: [u32; 4]| unsafe {
+            _mm512_set_epi64(
+                0, x[3] as i64, 0, x[2] as i64,
+                0, x[1] as i64, 0, x[0] as i64)
+        };
+        unsafe {
+            let ones = _mm512_set1_epi64(0xffffffff);
+            let b_r = _mm512_set1_epi64(self.b_r as i64);
+            let p_r = _mm512_set1_epi64(self.p_r as i64);
+
+            // undo our xorout, usually a bit invert, and any reflection
+            let mut x = lanes(crc.map(|c| self.unfinalize(c)));
+
+            // operate on 4-byte chunks first
+            let mut i = 0;
+            while i + 4 <= len {
+                x = _mm512_xor_si512(x,
+                    lanes(data.map(|d| Self::word::<REFIN>(&d[i..i+4]))));
+                let lo = _mm512_and_si512(
+                    _mm512_clmulepi64_epi128::<0>(x, b_r), ones);
+                let y = _mm512_and_si512(
+                    _mm512_xor_si512(_mm512_slli_epi64::<1>(lo), x), ones);
+                x = _mm512_and_si512(_mm512_srli_epi64::<31>(
+                    _mm512_clmulepi64_epi128::<0>(y, p_r)), ones);
+                i += 4;
+            }
+
+            // now clean up any remaining bytes
+            while i < len {
+                x = _mm512_xor_si512(x,
+                    lanes(data.map(|d| Self::byte::<REFIN>(d[i]))));
+                let x24 = _mm512_and_si512(_mm512_slli_epi64::<24>(x), ones);
+                let lo = _mm512_and_si512(
+                    _mm512_clmulepi64_epi128::<0>(x24, b_r), ones);
+                let y = _mm512_and_si512(
+                    _mm512_xor_si512(_mm512_slli_epi64::<1>(lo), x24), ones);
+                x = _mm512_xor_si512(_mm512_srli_epi64::<8>(x),
+                    _mm512_and_si512(_mm512_srli_epi64::<31>(
+                        _mm512_clmulepi64_epi128::<0>(y, p_r)), ones));
+                i += 1;
+            }
+
+            // reflect and apply our xorout
+            let x = core::mem::transmute::<__m512i, [u64; 8]>(x);
+            [0, 1, 2, 3].map(|k| self.finalize(x[2*k] as u32))
+        }
+    }
+
+    /// Number of carry-less multiplies to CRC this many bytes, useful
+    /// for comparing backends
+    pub fn pmuls(&self, len: usize) -> u64 {
+        if self.sse42() {
+            return 0;
+        }
+        let words = |len: usize| 2 * (len/4 + len%4) as u64;
+        if len < FOLD_MIN {
+            return words(len);
+        }
+
+        // folding needs 2 64-bit multiplies per chunk, and 4 reductions to
+        // get back down to a register
+        let chunks = (len/64 - 1)*4 + 3 + (len%64)/16;
+        2*chunks as u64 + words(16) + words(len%16)
+    }
+
+    /// Which backend our CRC uses, for comparing machines
+    pub fn backend(&self) -> &'static str {
+        if self.sse42() {
+            "sse4.2 crc32"
+        } else if cfg!(all(
+            not(feature="sw-pmul"),
+            target_arch="x86_64",
+            target_feature="avx512f",
+            target_feature="vpclmulqdq"
+        )) {
+            "vpclmulqdq"
+        } else {
+            crate::pmul::BACKEND
+        }
+    }
+
+    /// CRC we get when the register ends up zero
+    pub fn zero(&self) -> u32 {
+        self.xorout
+    }
+
+    /// CRC we get for any message followed by its own CRC, this is what
+    /// receivers that check the whole codeword expect to see
+    ///
+    /// Unreflected CRCs are usually stored big-endian, so that's what we
+    /// assume here.
+    pub fn residue(&self) -> u32 {
+        let bytes = (self.width as usize).div_ceil(8);
+        let crc = if self.refin {
+            self.init().to_le_bytes()
+        } else {
+            (self.init() << (32 - 8*bytes)).to_be_bytes()
+        };
+        self.crc32(self.init(), &crc[..bytes])
+    }
+}
+
+// our CRCs are their own state, so these are just our CRC
+impl Checksum for Crc32 {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn init(&self) -> u64 {
+        Crc32::init(self) as u64
+    }
+
+    fn update(&self, state: u64, data: &[u8]) -> u64 {
+        self.crc32(state as u32, data) as u64
+    }
+
+    fn finalize(&self, state: u64) -> u32 {
+        state as u32
+    }
+}
+
+
+// CRC-32C via x86's crc32 instruction, this is the same reflected
+// register update as our Barret reduction, just 8 bytes at a time and
+// in hardware
+#[cfg(all(
+    not(feature="sw-pmul"),
+    target_arch="x86_64",
+    target_feature="sse4.2"
+))]
+#[inline]
+fn crc32c_sse42(crc: u32, data: &[u8]) -> u32 {
+    use core::arch::x86_64::*;
+    let mut crc = crc as u64;
+    let mut words = data.chunks_exact(8);
+    for word in &mut words {
+        let word = u64::from_le_bytes(<[u8; 8]>::try_from(word).unwrap());
+        crc = unsafe { _mm_crc32_u64(crc, word) };
+    }
+
+    let mut crc = crc as u32;
+    for &b in words.remainder() {
+        crc = unsafe { _mm_crc32_u8(crc, b) };
+    }
+    crc
+}
+
+
+// 64-bit CRC with precomputed Barret constants
+//
+// This is the same reduction as Crc32, just with 64-bit multiplies and a
+// 128-bit product. Our polynomial and Barret constant both have 65 bits,
+// so like Crc32 we leave their top bits implicit.
+pub struct Crc64 {
+    p: u128,
+    width: u32,
+    ones: u64,
+    b: u64,
+    p_r: u64,
+    b_r: u64,
+    init: u64,
+    xorout: u64,
+    refin: bool,
+    refout: bool,
+}
+
+impl Crc64 {
+    /// Create a CRC from a polynomial with its top bit, its degree is the
+    /// width of our CRC, up to 64 bits
+    pub fn new(p: u128) -> Crc64 {
+        let width = 127 - p.leading_zeros();
+        assert!((1..=64).contains(&width));
+        let ones = !0u64 >> (64 - width);
+
+        // shift our polynomial up to 64 bits
+        let p_ = p << (64 - width);
+        // calculate our barret constant, note p_ << 64 drops our top bit,
+        // which only drops the top bit of our quotient
+        let b = pdiv128(p_ << 64, p_) as u64;
+        // and bit-reversed representations
+        let p_r = (p_ as u64).reverse_bits();
+        let b_r = b.reverse_bits();
+
+        Crc64{p, width, ones, b, p_r, b_r, init: ones, xorout: ones,
+            refin: true, refout: true}
+    }
+
+    /// Start our register at a different value, by default this is all
+    /// ones. Note init is unreflected, as in most CRC catalogs
+    pub fn with_init(self, init: u64) -> Crc64 {
+        Crc64{init: self.reflect(init), ..self}
+    }
+
+    /// Xor our final register with a different value, by default this is
+    /// all ones
+    pub fn with_xorout(self, xorout: u64) -> Crc64 {
+        Crc64{xorout, ..self}
+    }
+
+    /// Start from the CRC of an earlier message, so our CRC picks up where
+    /// that message left off
+    pub fn with_crc(self, crc: u64) -> Crc64 {
+        Crc64{init: self.unfinalize(crc), ..self}
+    }
+
+    /// Choose whether we reflect input bytes and the final register, by
+    /// default we reflect both
+    pub fn with_reflect(self, refin: bool, refout: bool) -> Crc64 {
+        Crc64{refin, refout, ..self}
+    }
+
+    // reflect a value in our width
+    #[inline]
+    fn reflect(&self, x: u64) -> u64 {
+        x.reverse_bits() >> (64 - self.width)
+    }
+
+    // CRC to register and back
+    #[inline]
+    fn unfinalize(&self, crc: u64) -> u64 {
+        let crc = crc ^ self.xorout;
+        if self.refout { crc } else { self.reflect(crc) }
+    }
+
+    #[inline]
+    fn finalize(&self, crc: u64) -> u64 {
+        let crc = if self.refout { crc } else { self.reflect(crc) };
+        crc ^ self.xorout
+    }
+
+    /// Width of our CRC in bits
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Mask of the bits our CRC can use
+    pub fn mask(&self) -> u64 {
+        self.ones
+    }
+
+    /// CRC of an empty message, this is what a CRC of a whole message
+    /// should start from
+    pub fn init(&self) -> u64 {
+        self.finalize(self.init)
+    }
+
+    /// What we xor our final register with
+    pub fn xorout(&self) -> u64 {
+        self.xorout
+    }
+
+    #[inline]
+    pub fn crc64(&self, crc: u64, data: &[u8]) -> u64 {
+        // specialize for our reflection, so we only check it once
+        if self.refin {
+            self.crc64_::<true>(crc, data)
+        } else {
+            self.crc64_::<false>(crc, data)
+        }
+    }
+
+    #[inline]
+    fn crc64_<const REFIN: bool>(&self, crc: u64, data: &[u8]) -> u64 {
+        // undo our xorout, usually a bit invert, and any reflection
+        let mut crc = self.unfinalize(crc);
+
+        // operate on 8-byte chunks first
+        let mut words = data.chunks_exact(8);
+        for word in &mut words {
+            let word = u64::from_le_bytes(<[u8; 8]>::try_from(word).unwrap());
+            crc ^= if REFIN { word } else { word.reverse_bits().swap_bytes() };
+            let (lo, _) = pmul64(crc, self.b_r);
+            let (lo, hi) = pmul64((lo << 1) ^ crc, self.p_r);
+            crc = (hi << 1) | (lo >> 63);
+        }
+
+        // now clean up any remaining bytes
+        for &b in words.remainder() {
+            crc ^= (if REFIN { b } else { b.reverse_bits() }) as u64;
+            let (lo, _) = pmul64(crc << 56, self.b_r);
+            let (lo, hi) = pmul64((lo << 1) ^ (crc << 56), self.p_r);
+            crc = (crc >> 8) ^ ((hi << 1) | (lo >> 63));
+        }
+
+        // reflect and apply our xorout
+        self.finalize(crc)
+    }
+
+    /// CRC we get when the register ends up zero
+    pub fn zero(&self) -> u64 {
+        self.xorout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    // a bit at a time, straight from the Rocksoft model
+    #[allow(clippy::too_many_arguments)]
+    fn reference(
+        width: u32,
+        poly: u64,
+        init: u64,
+        refin: bool,
+        refout: bool,
+        xorout: u64,
+        data: &[u8],
+    ) -> u64 {
+        reference_bits(width, poly, init, refin, refout, xorout, data,
+            8*data.len())
+    }
+
+    // the same, but only the first bits bits of our data
+    #[allow(clippy::too_many_arguments)]
+    fn reference_bits(
+        width: u32,
+        poly: u64,
+        init: u64,
+        refin: bool,
+        refout: bool,
+        xorout: u64,
+        data: &[u8],
+        bits: usize,
+    ) -> u64 {
+        let mask = !0u64 >> (64 - width);
+        let mut crc = init;
+        for k in 0..bits {
+            let b = data[k/8];
+            let bit = (if refin { b >> (k%8) } else { b >> (7 - k%8) }) & 1;
+            let carry = (crc >> (width-1)) & 1 != bit as u64;
+            crc = (crc << 1) & mask;
+            if carry {
+                crc ^= poly & mask;
+            }
+        }
+        if refout {
+            crc = crc.reverse_bits() >> (64 - width);
+        }
+        crc ^ xorout
+    }
+
+    // some random messages, including long enough ones to fold
+    fn messages(rng: &mut Rng) -> Vec<Vec<u8>> {
+        [0, 1, 3, 4, 9, 64, 127, 128, 129, 200, 1000].iter()
+            .map(|&len| {
+                let mut data = vec![0; len];
+                rng.fill(&mut data);
+                data
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batched_matches_single() {
+        let mut rng = Rng::new(1);
+        for len in [0, 1, 3, 4, 7, 8, 33] {
+            let crc32 = Crc32::new(0x104c11db7);
+            let mut data = vec![0; 2*len];
+            rng.fill(&mut data);
+            let (a, b) = data.split_at(len);
+            let crc = [rng.next_u64() as u32, rng.next_u64() as u32];
+            assert_eq!(crc32.crc32x2(crc, [a, b]),
+                [crc32.crc32(crc[0], a), crc32.crc32(crc[1], b)]);
+        }
+    }
+
+    #[test]
+    fn widths_match_reference() {
+        let mut rng = Rng::new(2);
+        for width in 8..=32 {
+            let mask = !0u64 >> (64 - width);
+            let poly = (rng.next_u64() & mask) | 1;
+            let crc32 = Crc32::new(poly | (1 << width));
+            assert_eq!(crc32.width(), width);
+            for data in messages(&mut rng) {
+                assert_eq!(crc32.crc32(crc32.init(), &data) as u64,
+                    reference(width, poly, mask, true, true, mask, &data),
+                    "width {} poly 0x{:x} len {}", width, poly, data.len());
+            }
+        }
+    }
+
+    #[test]
+    fn init_matches_reference() {
+        let mut rng = Rng::new(3);
+        for width in [8, 16, 24, 31, 32] {
+            let mask = !0u64 >> (64 - width);
+            let poly = (rng.next_u64() & mask) | 1;
+            let init = rng.next_u64() & mask;
+            let crc32 = Crc32::new(poly | (1 << width))
+                .with_init(init as u32);
+            for data in messages(&mut rng) {
+                assert_eq!(crc32.crc32(crc32.init(), &data) as u64,
+                    reference(width, poly, init, true, true, mask, &data));
+            }
+        }
+    }
+
+    #[test]
+    fn xorout_matches_reference() {
+        let mut rng = Rng::new(4);
+        for width in [8, 16, 24, 31, 32] {
+            let mask = !0u64 >> (64 - width);
+            let poly = (rng.next_u64() & mask) | 1;
+            let init = rng.next_u64() & mask;
+            let xorout = rng.next_u64() & mask;
+            let crc32 = Crc32::new(poly | (1 << width))
+                .with_init(init as u32)
+                .with_xorout(xorout as u32);
+            assert_eq!(crc32.init() as u64,
+                reference(width, poly, init, true, true, xorout, b""));
+            for data in messages(&mut rng) {
+                assert_eq!(crc32.crc32(crc32.init(), &data) as u64,
+                    reference(width, poly, init, true, true, xorout, &data));
+            }
+
+            // continuing a CRC is the same as CRCing everything at once
+            let crc = crc32.crc32(crc32.init(), b"hello");
+            assert_eq!(crc32.crc32(crc, b" world"),
+                crc32.crc32(crc32.init(), b"hello world"));
+        }
+    }
+
+    #[test]
+    fn reflection_matches_reference() {
+        let mut rng = Rng::new(5);
+        for width in [8, 16, 24, 31, 32] {
+            for (refin, refout) in [(false, false), (false, true),
+                    (true, false), (true, true)] {
+                let mask = !0u64 >> (64 - width);
+                let poly = (rng.next_u64() & mask) | 1;
+                let init = rng.next_u64() & mask;
+                let xorout = rng.next_u64() & mask;
+                let crc32 = Crc32::new(poly | (1 << width))
+                    .with_init(init as u32)
+                    .with_xorout(xorout as u32)
+                    .with_reflect(refin, refout);
+                for data in messages(&mut rng) {
+                    assert_eq!(crc32.crc32(crc32.init(), &data) as u64,
+                        reference(width, poly, init, refin, refout, xorout,
+                            &data),
+                        "width {} refin {} refout {}", width, refin, refout);
+                }
+
+                // and our batched CRCs should agree
+                let data = messages(&mut rng);
+                let (a, b) = (&data[4][..], &data[5][..9]);
+                let crc = crc32.init();
+                assert_eq!(crc32.crc32x2([crc, crc], [a, b]),
+                    [crc32.crc32(crc, a), crc32.crc32(crc, b)]);
+            }
+        }
+    }
+
+    #[test]
+    fn sub_byte_widths_match_reference() {
+        let mut rng = Rng::new(6);
+        for width in 1..=7 {
+            for (refin, refout) in [(false, false), (true, true),
+                    (false, true)] {
+                let mask = !0u64 >> (64 - width);
+                let poly = (rng.next_u64() & mask) | 1;
+                let init = rng.next_u64() & mask;
+                let xorout = rng.next_u64() & mask;
+                let crc32 = Crc32::new(poly | (1 << width))
+                    .with_init(init as u32)
+                    .with_xorout(xorout as u32)
+                    .with_reflect(refin, refout);
+                for data in messages(&mut rng) {
+                    assert_eq!(crc32.crc32(crc32.init(), &data) as u64,
+                        reference(width, poly, init, refin, refout, xorout,
+                            &data),
+                        "width {} refin {} refout {}", width, refin, refout);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bits_match_reference() {
+        let mut rng = Rng::new(7);
+        let mut data = vec![0; 9];
+        rng.fill(&mut data);
+        for width in [3, 8, 16, 32] {
+            for refin in [false, true] {
+                let mask = !0u64 >> (64 - width);
+                let poly = (rng.next_u64() & mask) | 1;
+                let crc32 = Crc32::new(poly | (1 << width))
+                    .with_reflect(refin, refin);
+                for bits in 0..=8*data.len() {
+                    assert_eq!(crc32.crc32_bits(crc32.init(), &data, bits)
+                            as u64,
+                        reference_bits(width, poly, mask, refin, refin, mask,
+                            &data, bits),
+                        "width {} refin {} bits {}", width, refin, bits);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crc64_matches_reference() {
+        let mut rng = Rng::new(8);
+        for width in [33, 40, 48, 63, 64] {
+            for (refin, refout) in [(false, false), (true, true),
+                    (false, true)] {
+                let mask = !0u64 >> (64 - width);
+                let poly = (rng.next_u64() & mask) | 1;
+                let init = rng.next_u64() & mask;
+                let xorout = rng.next_u64() & mask;
+                let crc64 = Crc64::new(poly as u128 | (1 << width))
+                    .with_init(init)
+                    .with_xorout(xorout)
+                    .with_reflect(refin, refout);
+                for data in messages(&mut rng) {
+                    assert_eq!(crc64.crc64(crc64.init(), &data),
+                        reference(width, poly, init, refin, refout, xorout,
+                            &data),
+                        "width {} refin {} refout {}", width, refin, refout);
+
+                    // and we can pick up where we left off
+                    let (a, b) = data.split_at(data.len()/3);
+                    assert_eq!(crc64.crc64(crc64.crc64(crc64.init(), a), b),
+                        crc64.crc64(crc64.init(), &data));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crc32c_matches_reference() {
+        // CRC-32C can take the crc32 instruction, but only when reflected
+        let mut rng = Rng::new(9);
+        for (refin, refout) in [(true, true), (true, false), (false, false)] {
+            let init = rng.next_u64() as u32;
+            let xorout = rng.next_u64() as u32;
+            let crc32 = Crc32::new(0x11edc6f41)
+                .with_init(init)
+                .with_xorout(xorout)
+                .with_reflect(refin, refout);
+            for data in messages(&mut rng) {
+                assert_eq!(crc32.crc32(crc32.init(), &data) as u64,
+                    reference(32, 0x1edc6f41, init as u64, refin, refout,
+                        xorout as u64, &data),
+                    "refin {} refout {} len {}", refin, refout, data.len());
+
+                let (a, b) = data.split_at(data.len()/2);
+                assert_eq!(crc32.crc32x2([crc32.init(); 2], [a, &b[..a.len()]]),
+                    [crc32.crc32(crc32.init(), a),
+                        crc32.crc32(crc32.init(), &b[..a.len()])]);
+            }
+        }
+
+        let crc32 = Crc32::new(0x11edc6f41);
+        assert_eq!(crc32.crc32(crc32.init(), b"123456789"), 0xe3069283);
+    }
+
+    #[test]
+    fn batched4_matches_single() {
+        let mut rng = Rng::new(10);
+        for width in [8, 16, 31, 32] {
+            for (refin, refout) in [(false, false), (true, true),
+                    (true, false)] {
+                let mask = !0u64 >> (64 - width);
+                let poly = (rng.next_u64() & mask) | 1;
+                let crc32 = Crc32::new(poly | (1 << width))
+                    .with_reflect(refin, refout);
+                for len in [0, 1, 3, 4, 7, 8, 33, 200] {
+                    let mut data = vec![0; 4*len];
+                    rng.fill(&mut data);
+                    let data = [0, 1, 2, 3].map(|i| &data[i*len..(i+1)*len]);
+                    let crc = [0; 4]
+                        .map(|_| (rng.next_u64() & mask) as u32);
+                    assert_eq!(crc32.crc32x4(crc, data),
+                        [0, 1, 2, 3].map(|i| crc32.crc32(crc[i], data[i])),
+                        "width {} refin {} len {}", width, refin, len);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn folding_matches_unfolded() {
+        let mut rng = Rng::new(11);
+        let mut data = vec![0; 4096];
+        rng.fill(&mut data);
+        for width in [3, 8, 16, 24, 32] {
+            for (refin, refout) in [(false, false), (true, true),
+                    (false, true)] {
+                let mask = !0u64 >> (64 - width);
+                let poly = (rng.next_u64() & mask) | 1;
+                let crc32 = Crc32::new(poly | (1 << width))
+                    .with_reflect(refin, refout);
+                for len in (FOLD_MIN..FOLD_MIN+80).chain([1000, 4096]) {
+                    // short pieces are never folded
+                    let unfolded = data[..len].chunks(FOLD_MIN-1)
+                        .fold(crc32.init(), |crc, c| crc32.crc32(crc, c));
+                    assert_eq!(crc32.crc32(crc32.init(), &data[..len]),
+                        unfolded,
+                        "width {} refin {} len {}", width, refin, len);
+                }
+            }
+        }
+    }
+}
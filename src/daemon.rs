@@ -0,0 +1,503 @@
+// Background daemon for queueing long searches
+//
+// The daemon listens on a unix socket and runs each job as a child
+// crcbrute process, so searches survive the terminal that submitted them.
+// When there are more jobs than slots, we pick the user with the fewest
+// running jobs, so one user can't starve everyone else on a shared box.
+// Anyone on the box can connect, so we ask the kernel who's on the other
+// end of each connection instead of trusting them, and only let users see
+// and cancel their own jobs. Jobs run as whoever runs the daemon, so we
+// also check every submitted search ourselves, and refuse anything that
+// would read or write files on a user's behalf.
+//
+// The protocol is deliberately dumb, a request is a list of NUL-separated
+// words terminated by closing our half of the socket, and a response is a
+// "<exit code> <stdout len>" line followed by stdout then stderr.
+//
+
+#![cfg(unix)]
+
+use std::collections::BTreeMap;
+use std::fs::{self, Permissions};
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::progress::{self, EXIT_NOT_FOUND};
+
+// how long we wait on a quiet client
+const QUIET: Duration = Duration::from_secs(30);
+
+// the most a client can send us in one request
+const MAX_REQUEST: u64 = 1 << 20;
+
+// how long we keep finished jobs around for their results
+const EXPIRE: Duration = Duration::from_secs(24*60*60);
+
+/// Default socket, shared by everyone on the box
+pub fn default_socket() -> PathBuf {
+    std::env::temp_dir().join("crcbrute.sock")
+}
+
+mod sys {
+    use std::ffi::{c_char, c_void, CStr};
+
+    // avoid pulling in libc for a couple functions
+    #[cfg(any(target_os="linux", target_os="android"))]
+    #[cfg(not(any(target_arch="mips", target_arch="mips64")))]
+    const SOL_SOCKET: i32 = 1;
+    #[cfg(any(target_os="linux", target_os="android"))]
+    #[cfg(any(target_arch="mips", target_arch="mips64"))]
+    const SOL_SOCKET: i32 = 0xffff;
+    #[cfg(any(target_os="linux", target_os="android"))]
+    #[cfg(not(any(target_arch="mips", target_arch="mips64",
+        target_arch="powerpc", target_arch="powerpc64")))]
+    const SO_PEERCRED: i32 = 17;
+    #[cfg(any(target_os="linux", target_os="android"))]
+    #[cfg(any(target_arch="mips", target_arch="mips64"))]
+    const SO_PEERCRED: i32 = 18;
+    #[cfg(any(target_os="linux", target_os="android"))]
+    #[cfg(any(target_arch="powerpc", target_arch="powerpc64"))]
+    const SO_PEERCRED: i32 = 21;
+
+    #[cfg(any(target_os="linux", target_os="android"))]
+    #[repr(C)]
+    struct Ucred {
+        pid: i32,
+        uid: u32,
+        gid: u32,
+    }
+
+    // we only need the name, which comes first everywhere
+    #[repr(C)]
+    struct Passwd {
+        pw_name: *const c_char,
+    }
+
+    extern "C" {
+        #[cfg(any(target_os="linux", target_os="android"))]
+        fn getsockopt(fd: i32, level: i32, name: i32,
+            val: *mut c_void, len: *mut u32) -> i32;
+        #[cfg(not(any(target_os="linux", target_os="android")))]
+        fn getpeereid(fd: i32, uid: *mut u32, gid: *mut u32) -> i32;
+        fn getpwuid(uid: u32) -> *const Passwd;
+        fn getuid() -> u32;
+    }
+
+    #[cfg(any(target_os="linux", target_os="android"))]
+    pub fn peer_uid(fd: i32) -> Option<u32> {
+        let mut cred = Ucred{pid: 0, uid: 0, gid: 0};
+        let mut len = size_of::<Ucred>() as u32;
+        let res = unsafe {
+            getsockopt(fd, SOL_SOCKET, SO_PEERCRED,
+                &mut cred as *mut Ucred as *mut c_void, &mut len)
+        };
+        (res == 0).then_some(cred.uid)
+    }
+
+    #[cfg(not(any(target_os="linux", target_os="android")))]
+    pub fn peer_uid(fd: i32) -> Option<u32> {
+        let mut uid = 0;
+        let mut gid = 0;
+        let res = unsafe { getpeereid(fd, &mut uid, &mut gid) };
+        (res == 0).then_some(uid)
+    }
+
+    // note getpwuid isn't thread-safe, so only call this while holding
+    // our queue
+    pub fn user_name(uid: u32) -> Option<String> {
+        unsafe {
+            let pw = getpwuid(uid);
+            if pw.is_null() || (*pw).pw_name.is_null() {
+                return None;
+            }
+            Some(CStr::from_ptr((*pw).pw_name).to_string_lossy().into_owned())
+        }
+    }
+
+    pub fn uid() -> u32 {
+        unsafe { getuid() }
+    }
+}
+
+// who's on the other end of a connection, as far as fairness and
+// permissions are concerned
+fn peer(stream: &UnixStream) -> io::Result<(u32, String)> {
+    let uid = sys::peer_uid(stream.as_raw_fd())
+        .ok_or_else(io::Error::last_os_error)?;
+    let name = sys::user_name(uid).unwrap_or_else(|| uid.to_string());
+    Ok((uid, name))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Queued,
+    Running,
+    Done(i32),
+    Cancelled,
+}
+
+#[derive(Debug)]
+struct Job {
+    uid: u32,
+    user: String,
+    args: Vec<String>,
+    state: State,
+    child: Option<Child>,
+    started: Option<Instant>,
+    finished: Option<Instant>,
+    stdout: Arc<Mutex<Vec<u8>>>,
+    stderr: Arc<Mutex<Vec<u8>>>,
+    // threads collecting our output, a job isn't done until these are
+    drains: Vec<JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+struct Queue {
+    exe: PathBuf,
+    check: fn(&[String]) -> Result<(), String>,
+    slots: usize,
+    next_id: u64,
+    jobs: BTreeMap<u64, Job>,
+}
+
+// collect a child's output in the background
+fn drain(
+    mut r: impl Read + Send + 'static,
+    buf: Arc<Mutex<Vec<u8>>>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut chunk = [0; 4096];
+        while let Ok(n) = r.read(&mut chunk) {
+            if n == 0 {
+                break;
+            }
+            buf.lock().unwrap().extend_from_slice(&chunk[..n]);
+        }
+    })
+}
+
+impl Queue {
+    // reap finished jobs and start queued ones
+    fn schedule(&mut self) {
+        // forget jobs nobody's collected in a while
+        self.jobs.retain(|_, job| job.finished
+            .is_none_or(|finished| finished.elapsed() < EXPIRE));
+
+        let mut running = BTreeMap::<String, usize>::new();
+        for job in self.jobs.values_mut() {
+            if job.state != State::Running {
+                continue;
+            }
+
+            let child = job.child.as_mut().unwrap();
+            match child.try_wait() {
+                Ok(None) => {
+                    *running.entry(job.user.clone()).or_default() += 1;
+                }
+                status => {
+                    let code = status.ok().flatten()
+                        .and_then(|s| s.code())
+                        .unwrap_or(-1);
+                    // our child is gone, so this only waits for the last
+                    // of its output
+                    for drain in job.drains.drain(..) {
+                        let _ = drain.join();
+                    }
+                    job.state = State::Done(code);
+                    job.child = None;
+                    job.finished = Some(Instant::now());
+                }
+            }
+        }
+
+        while running.values().sum::<usize>() < self.slots {
+            // fairness, pick the oldest job of the user with the fewest
+            // running jobs
+            let next = self.jobs.iter()
+                .filter(|(_, job)| job.state == State::Queued)
+                .min_by_key(|(&id, job)| (
+                    running.get(&job.user).copied().unwrap_or(0),
+                    id,
+                ))
+                .map(|(&id, _)| id);
+            let id = match next {
+                Some(id) => id,
+                None => break,
+            };
+
+            let job = self.jobs.get_mut(&id).unwrap();
+            let child = Command::new(&self.exe)
+                .args(&job.args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn();
+            match child {
+                Ok(mut child) => {
+                    job.drains = vec![
+                        drain(child.stdout.take().unwrap(), job.stdout.clone()),
+                        drain(child.stderr.take().unwrap(), job.stderr.clone()),
+                    ];
+                    job.child = Some(child);
+                    job.state = State::Running;
+                    job.started = Some(Instant::now());
+                    *running.entry(job.user.clone()).or_default() += 1;
+                }
+                Err(err) => {
+                    job.stderr.lock().unwrap().extend_from_slice(
+                        format!("error: {}\n", err).as_bytes());
+                    job.state = State::Done(-1);
+                    job.finished = Some(Instant::now());
+                }
+            }
+        }
+    }
+
+    // handle a single request from a user, returning exit code, stdout,
+    // and stderr
+    fn handle(
+        &mut self,
+        (uid, user): (u32, String),
+        words: &[String],
+    ) -> (i32, Vec<u8>, Vec<u8>) {
+        fn err(msg: String) -> (i32, Vec<u8>, Vec<u8>) {
+            (1, vec![], format!("error: {}\n", msg).into_bytes())
+        }
+
+        let id = |word: Option<&String>| -> Result<u64, String> {
+            let id = word.ok_or("missing job id")?;
+            let id = id.parse::<u64>()
+                .map_err(|_| format!("invalid job id {:?}", id))?;
+            if self.jobs.contains_key(&id) {
+                Ok(id)
+            } else {
+                Err(format!("no job {}", id))
+            }
+        };
+
+        // only a job's owner, or whoever runs the daemon, can see or
+        // touch it
+        let owns = |job: &Job| job.uid == uid || uid == sys::uid();
+
+        match words.first().map(|w| w.as_str()) {
+            Some("submit") if words.len() >= 2 => {
+                if let Err(msg) = (self.check)(&words[1..]) {
+                    return err(msg);
+                }
+
+                let id = self.next_id;
+                self.next_id += 1;
+                self.jobs.insert(id, Job{
+                    uid,
+                    user,
+                    args: words[1..].to_vec(),
+                    state: State::Queued,
+                    child: None,
+                    started: None,
+                    finished: None,
+                    stdout: Default::default(),
+                    stderr: Default::default(),
+                    drains: vec![],
+                });
+                self.schedule();
+                (0, format!("{}\n", id).into_bytes(), vec![])
+            }
+
+            Some("status") => {
+                let mut out = String::new();
+                for (id, job) in &self.jobs {
+                    let state = match job.state {
+                        State::Queued => "queued".into(),
+                        State::Running => "running".into(),
+                        State::Done(0) => "done".into(),
+                        State::Done(EXIT_NOT_FOUND) => "not found".into(),
+                        State::Done(code) => format!("failed({})", code),
+                        State::Cancelled => "cancelled".into(),
+                    };
+                    let elapsed = match (job.started, job.finished) {
+                        (Some(s), Some(f)) => progress::format_duration(f - s),
+                        (Some(s), None) => progress::format_duration(
+                            s.elapsed()),
+                        _ => "-".into(),
+                    };
+                    let args = if owns(job) {
+                        job.args.join(" ")
+                    } else {
+                        "-".into()
+                    };
+                    out.push_str(&format!("{}\t{}\t{}\t{}\t{}\n",
+                        id, job.user, state, elapsed, args));
+                }
+                (0, out.into_bytes(), vec![])
+            }
+
+            Some("result") => match id(words.get(1)) {
+                Ok(id) if !owns(&self.jobs[&id]) => err(format!(
+                    "job {} belongs to {}", id, self.jobs[&id].user)),
+                Ok(id) => {
+                    let job = &self.jobs[&id];
+                    match job.state {
+                        State::Done(code) => (
+                            code,
+                            job.stdout.lock().unwrap().clone(),
+                            job.stderr.lock().unwrap().clone(),
+                        ),
+                        State::Cancelled => err(format!(
+                            "job {} was cancelled", id)),
+                        _ => err(format!(
+                            "job {} hasn't finished", id)),
+                    }
+                }
+                Err(msg) => err(msg),
+            },
+
+            Some("cancel") => match id(words.get(1)) {
+                Ok(id) if !owns(&self.jobs[&id]) => err(format!(
+                    "job {} belongs to {}", id, self.jobs[&id].user)),
+                Ok(id) => {
+                    let job = self.jobs.get_mut(&id).unwrap();
+                    match job.state {
+                        State::Queued | State::Running => {
+                            if let Some(mut child) = job.child.take() {
+                                let _ = child.kill();
+                                let _ = child.wait();
+                            }
+                            for drain in job.drains.drain(..) {
+                                let _ = drain.join();
+                            }
+                            job.state = State::Cancelled;
+                            job.finished = Some(Instant::now());
+                            self.schedule();
+                            (0, vec![], vec![])
+                        }
+                        _ => err(format!("job {} already finished", id)),
+                    }
+                }
+                Err(msg) => err(msg),
+            },
+
+            _ => err(format!("invalid request {:?}", words)),
+        }
+    }
+}
+
+fn read_words(stream: &mut UnixStream) -> io::Result<Vec<String>> {
+    let mut buf = vec![];
+    stream.take(MAX_REQUEST+1).read_to_end(&mut buf)?;
+    if buf.len() as u64 > MAX_REQUEST {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+            "request too large"));
+    }
+    Ok(buf.split(|&b| b == 0)
+        .map(|w| String::from_utf8_lossy(w).into_owned())
+        .collect())
+}
+
+fn write_response(
+    stream: &mut UnixStream,
+    (code, stdout, stderr): (i32, Vec<u8>, Vec<u8>),
+) -> io::Result<()> {
+    writeln!(stream, "{} {}", code, stdout.len())?;
+    stream.write_all(&stdout)?;
+    stream.write_all(&stderr)?;
+    stream.flush()
+}
+
+/// Run the daemon, this only returns on error
+///
+/// Jobs are run by invoking exe with the submitted arguments, with up to
+/// slots jobs running at once. Submitted arguments must pass check first.
+pub fn serve(
+    socket: &Path,
+    exe: PathBuf,
+    slots: usize,
+    check: fn(&[String]) -> Result<(), String>,
+) -> io::Result<()> {
+    // clean up stale sockets, but don't steal a live daemon's socket
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse,
+                format!("daemon already running on {}", socket.display())));
+        }
+        fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    // let everyone on the box submit jobs, regardless of our umask
+    fs::set_permissions(socket, Permissions::from_mode(0o666))?;
+
+    let queue = Arc::new(Mutex::new(Queue{
+        exe,
+        check,
+        slots: slots.max(1),
+        next_id: 1,
+        jobs: BTreeMap::new(),
+    }));
+
+    // poll our children in the background
+    {
+        let queue = queue.clone();
+        thread::spawn(move || loop {
+            queue.lock().unwrap().schedule();
+            thread::sleep(Duration::from_millis(100));
+        });
+    }
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+
+        // one connection per thread, so a slow client can't hold up
+        // everyone else
+        let queue = queue.clone();
+        thread::spawn(move || {
+            // a misbehaving client shouldn't take down the daemon
+            let _ = stream.set_read_timeout(Some(QUIET))
+                .and_then(|_| stream.set_write_timeout(Some(QUIET)))
+                .and_then(|_| read_words(&mut stream))
+                .and_then(|words| {
+                    let mut queue = queue.lock().unwrap();
+                    let peer = peer(&stream)?;
+                    Ok(queue.handle(peer, &words))
+                })
+                .and_then(|res| write_response(&mut stream, res));
+        });
+    }
+
+    Ok(())
+}
+
+/// Send a request to the daemon, returning exit code, stdout, and stderr
+pub fn request(
+    socket: &Path,
+    words: &[String],
+) -> io::Result<(i32, Vec<u8>, Vec<u8>)> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.write_all(words.join("\0").as_bytes())?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut buf = vec![];
+    stream.read_to_end(&mut buf)?;
+
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData,
+        "invalid response from daemon");
+    let nl = buf.iter().position(|&b| b == b'\n').ok_or_else(invalid)?;
+    let header = std::str::from_utf8(&buf[..nl]).map_err(|_| invalid())?;
+    let (code, len) = header.split_once(' ').ok_or_else(invalid)?;
+    let code = code.parse::<i32>().map_err(|_| invalid())?;
+    let len = len.parse::<usize>().map_err(|_| invalid())?;
+    let rest = &buf[nl+1..];
+    if len > rest.len() {
+        return Err(invalid());
+    }
+    Ok((code, rest[..len].to_vec(), rest[len..].to_vec()))
+}
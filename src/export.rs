@@ -0,0 +1,96 @@
+// Exporting analysis results
+//
+// So results can be picked up in other tools without re-deriving
+// everything: CSV for spreadsheets, .npy for NumPy, and Sage for, well,
+// Sage.
+//
+
+use core::str::FromStr;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Npy,
+    Sage,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "npy" => Ok(Format::Npy),
+            "sage" => Ok(Format::Sage),
+            _ => Err(format!("unknown format {:?}", s)),
+        }
+    }
+}
+
+/// A 2D table of integers
+#[derive(Debug, Clone)]
+pub struct Table {
+    /// Name, used as the variable name in Sage
+    pub name: String,
+    /// Column names, used as the header in CSV
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<u64>>,
+    /// Is this a matrix over GF(2)?
+    pub gf2: bool,
+}
+
+impl Table {
+    pub fn write(&self, f: &mut impl Write, format: Format) -> io::Result<()> {
+        match format {
+            Format::Csv => {
+                writeln!(f, "{}", self.columns.join(","))?;
+                for row in &self.rows {
+                    let row = row.iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>();
+                    writeln!(f, "{}", row.join(","))?;
+                }
+            }
+            Format::Npy => {
+                // npy v1.0, a magic string, a python dict describing our
+                // array, and then raw data
+                let (descr, size) = if self.gf2 { ("|u1", 1) } else { ("<u8", 8) };
+                let mut header = format!(
+                    "{{'descr': '{}', 'fortran_order': False, 'shape': ({}, {}), }}",
+                    descr,
+                    self.rows.len(),
+                    self.columns.len());
+                // pad so data is 64-byte aligned
+                while (10 + header.len() + 1) % 64 != 0 {
+                    header.push(' ');
+                }
+                header.push('\n');
+                f.write_all(b"\x93NUMPY\x01\x00")?;
+                f.write_all(&(header.len() as u16).to_le_bytes())?;
+                f.write_all(header.as_bytes())?;
+                for row in &self.rows {
+                    for x in row {
+                        f.write_all(&x.to_le_bytes()[..size])?;
+                    }
+                }
+            }
+            Format::Sage => {
+                writeln!(f, "# {}", self.columns.join(", "))?;
+                let rows = self.rows.iter()
+                    .map(|row| format!("[{}]", row.iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")))
+                    .collect::<Vec<_>>()
+                    .join(",\n    ");
+                if self.gf2 {
+                    writeln!(f, "{} = matrix(GF(2), [\n    {}])", self.name, rows)?;
+                } else {
+                    writeln!(f, "{} = [\n    {}]", self.name, rows)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+// Fletcher checksums, and forging them
+//
+// Fletcher-16 sums bytes mod 255, and Fletcher-32 sums little-endian
+// 16-bit words mod 65535, padding an odd trailing byte with zero. These
+// are the same two running sums as Adler-32, just starting from zero, so
+// we share its solver.
+//
+
+use core::fmt;
+use core::str::FromStr;
+use crate::adler;
+use crate::charset::Charset;
+use crate::checksum::Checksum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fletcher {
+    Fletcher16,
+    Fletcher32,
+}
+
+impl Fletcher {
+    /// Modulus of both of our sums
+    pub fn modulus(&self) -> u64 {
+        match self {
+            Fletcher::Fletcher16 => 255,
+            Fletcher::Fletcher32 => 65535,
+        }
+    }
+
+    /// Bytes in each symbol we sum
+    pub fn symbol_len(&self) -> usize {
+        match self {
+            Fletcher::Fletcher16 => 1,
+            Fletcher::Fletcher32 => 2,
+        }
+    }
+
+    // our data as symbols, padding any partial symbol with zeros
+    fn symbols<'a>(&self, data: &'a [u8]) -> impl Iterator<Item=u64> + 'a {
+        data.chunks(self.symbol_len())
+            .map(|s| s.iter().rev().fold(0, |x, &b| (x << 8) | b as u64))
+    }
+
+    // continue our sums over more data
+    fn sums(&self, (mut a, mut b): (u64, u64), data: &[u8]) -> (u64, u64) {
+        let m = self.modulus();
+        for x in self.symbols(data) {
+            a = (a + x) % m;
+            b = (b + a) % m;
+        }
+        (a, b)
+    }
+
+    fn split(&self, sum: u32) -> (u64, u64) {
+        let bits = 8*self.symbol_len();
+        ((sum & ((1 << bits) - 1)) as u64, (sum >> bits) as u64)
+    }
+
+    fn join(&self, (a, b): (u64, u64)) -> u32 {
+        ((b << (8*self.symbol_len())) | a) as u32
+    }
+
+    /// Fletcher checksum of a message
+    pub fn fletcher(&self, data: &[u8]) -> u32 {
+        self.join(self.sums((0, 0), data))
+    }
+
+    /// Is this a checksum we can actually produce?
+    pub fn is_valid(&self, sum: u32) -> bool {
+        let (a, b) = self.split(sum);
+        (sum as u64) >> (16*self.symbol_len()) == 0
+            && a < self.modulus()
+            && b < self.modulus()
+    }
+
+    /// Find the shortest suffix of bytes in lo..=hi that takes
+    /// prefix || suffix || postfix to any of our targets, returning the
+    /// suffix and which target it hit
+    ///
+    /// If our prefix ends mid-word, our suffix starts by finishing it.
+    /// Our solver needs a contiguous range of symbols, so unless we can
+    /// use every byte, the high byte of each word is fixed.
+    pub fn forge(
+        &self,
+        prefix: &[u8],
+        postfix: &[u8],
+        targets: &[u32],
+        lo: u8,
+        hi: u8,
+    ) -> Option<(Vec<u8>, u32)> {
+        let n = self.symbol_len();
+        let mut head = vec![lo; (n - prefix.len() % n) % n];
+        let mut data = prefix.to_vec();
+        data.extend_from_slice(&head);
+        let start = self.sums((0, 0), &data);
+
+        // work out what we need right before our postfix
+        let m = self.modulus();
+        let post = self.sums((0, 0), postfix);
+        let befores = targets.iter()
+            .map(|&t| adler::unsum(m, self.split(t), post,
+                postfix.len().div_ceil(n) as u64))
+            .collect::<Vec<_>>();
+
+        let (lo_, hi_) = match self {
+            _ if (lo, hi) == (0, 255) => (0, (1 << (8*n)) - 1),
+            Fletcher::Fletcher16 => (lo as u64, hi as u64),
+            Fletcher::Fletcher32 => (256*lo as u64 + lo as u64,
+                256*lo as u64 + hi as u64),
+        };
+        let max_len = 2*m.div_ceil(hi_ - lo_) as usize + 2;
+        let (symbols, i) = adler::solve(m, start, &befores, lo_, hi_,
+            max_len)?;
+        for x in symbols {
+            head.extend_from_slice(&x.to_le_bytes()[..n]);
+        }
+        Some((head, targets[i]))
+    }
+}
+
+impl Checksum for Fletcher {
+    fn width(&self) -> u32 {
+        16*self.symbol_len() as u32
+    }
+
+    fn symbol_len(&self) -> usize {
+        Fletcher::symbol_len(self)
+    }
+
+    fn init(&self) -> u64 {
+        0
+    }
+
+    fn update(&self, state: u64, data: &[u8]) -> u64 {
+        self.join(self.sums(self.split(state as u32), data)) as u64
+    }
+
+    fn finalize(&self, state: u64) -> u32 {
+        state as u32
+    }
+
+    fn is_valid(&self, sum: u32) -> bool {
+        Fletcher::is_valid(self, sum)
+    }
+
+    fn solve_suffix(
+        &self,
+        prefix: &[u8],
+        postfix: &[u8],
+        targets: &[u32],
+        charset: Charset,
+    ) -> Option<Option<(Vec<u8>, u32)>> {
+        let (lo, hi) = charset.longest_run();
+        (lo < hi).then(|| self.forge(prefix, postfix, targets, lo, hi))
+    }
+}
+
+impl FromStr for Fletcher {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Fletcher, String> {
+        match s {
+            "16" | "fletcher16" | "fletcher-16" => Ok(Fletcher::Fletcher16),
+            "32" | "fletcher32" | "fletcher-32" => Ok(Fletcher::Fletcher32),
+            _ => Err(format!("unknown fletcher checksum {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Fletcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fletcher::Fletcher16 => write!(f, "fletcher16"),
+            Fletcher::Fletcher32 => write!(f, "fletcher32"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn known_values() {
+        let f16 = Fletcher::Fletcher16;
+        assert_eq!(f16.fletcher(b"abcde"), 0xc8f0);
+        assert_eq!(f16.fletcher(b"abcdef"), 0x2057);
+        assert_eq!(f16.fletcher(b"abcdefgh"), 0x0627);
+        let f32 = Fletcher::Fletcher32;
+        assert_eq!(f32.fletcher(b"abcde"), 0xf04fc729);
+        assert_eq!(f32.fletcher(b"abcdef"), 0x56502d2a);
+        assert_eq!(f32.fletcher(b"abcdefgh"), 0xebe19591);
+    }
+
+    #[test]
+    fn forge_round_trips() {
+        let mut rng = Rng::new(1);
+        for fletcher in [Fletcher::Fletcher16, Fletcher::Fletcher32] {
+            let m = fletcher.modulus();
+            let bits = 8*fletcher.symbol_len();
+            for (lo, hi) in [(0x00, 0xff), (b'a', b'z'), (b'0', b'9')] {
+                // odd prefixes end mid-word for fletcher32
+                for prefix in [&b"hello"[..], b"hi"] {
+                    let target = ((rng.below(m) << bits) | rng.below(m)) as u32;
+                    let (suffix, hit) = fletcher.forge(prefix, b"\n",
+                        &[target], lo, hi).unwrap();
+                    assert_eq!(hit, target);
+                    assert!(suffix.iter().all(|b| (lo..=hi).contains(b)),
+                        "{} {:02x?}", fletcher, suffix);
+                    let whole = [prefix, &suffix, b"\n"].concat();
+                    assert_eq!(fletcher.fletcher(&whole), target,
+                        "{}", fletcher);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,553 @@
+// Frame-format descriptions
+//
+// Describes a packet layout, its fields, which fields the CRC covers,
+// where the CRC lives, and which fields we're free to change, so verifying,
+// encoding, and forging complex formats doesn't need a pile of flags.
+//
+// Specs are written in a small subset of TOML:
+//
+//   polynomial = 0x11edc6f41
+//
+//   [[field]]
+//   name = "magic"
+//   hex = "a55a"
+//
+//   [[field]]
+//   name = "seq"
+//   len = 2
+//   covered = false
+//
+//   [[field]]
+//   name = "payload"
+//   string = "hello"
+//
+//   [[field]]
+//   name = "spare"
+//   len = 4
+//   fill = 0xff
+//   free = true
+//
+//   [[field]]
+//   name = "crc"
+//   crc = "little"
+//
+// Fields are laid out in order. Content comes from hex, string, or fill,
+// and the CRC covers every field except the CRC itself unless covered is
+// false. At most one field may leave out its length, in which case it
+// takes up whatever's left of a message.
+//
+// Besides polynomial, a spec can set init, xorout, refin, and refout, or
+// name a catalogue algorithm with algorithm = "crc-16/ccitt-false", which
+// sets all of these, with any later keys overriding it. The CRC field
+// takes up as many bytes as the polynomial's width needs.
+//
+
+use core::str::FromStr;
+use crate::crc::Crc32;
+use crate::gf2;
+use crate::catalog;
+use crate::coverage::unhex;
+
+/// Byte order of a stored CRC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl FromStr for Endian {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Endian, String> {
+        match s {
+            "little" | "le" => Ok(Endian::Little),
+            "big" | "be" => Ok(Endian::Big),
+            _ => Err(format!("unknown endianness {:?}", s)),
+        }
+    }
+}
+
+impl Endian {
+    pub fn bytes(&self, crc: u32) -> [u8; 4] {
+        match self {
+            Endian::Little => crc.to_le_bytes(),
+            Endian::Big => crc.to_be_bytes(),
+        }
+    }
+
+    pub fn crc(&self, bytes: [u8; 4]) -> u32 {
+        match self {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        }
+    }
+
+    /// Store a CRC in a field of up to 4 bytes, narrower CRCs only take up
+    /// as many bytes as they need
+    pub fn store(&self, crc: u32, field: &mut [u8]) {
+        let n = field.len();
+        match self {
+            Endian::Little => field.copy_from_slice(&crc.to_le_bytes()[..n]),
+            Endian::Big => field.copy_from_slice(&crc.to_be_bytes()[4-n..]),
+        }
+    }
+
+    /// Load a CRC from a field of up to 4 bytes
+    pub fn load(&self, field: &[u8]) -> u32 {
+        let n = field.len();
+        let mut bytes = [0; 4];
+        match self {
+            Endian::Little => bytes[..n].copy_from_slice(field),
+            Endian::Big => bytes[4-n..].copy_from_slice(field),
+        }
+        self.crc(bytes)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub len: Option<usize>,
+    pub content: Option<Vec<u8>>,
+    pub fill: Option<u8>,
+    pub covered: bool,
+    pub free: bool,
+    pub crc: Option<Endian>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Polynomial including its top bit, which sets our width
+    pub polynomial: u64,
+    /// Initial register, unreflected, defaults to all ones
+    pub init: Option<u32>,
+    /// Value xored into the final CRC, defaults to all ones
+    pub xorout: Option<u32>,
+    pub refin: bool,
+    pub refout: bool,
+    pub fields: Vec<Field>,
+}
+
+// TOML values we understand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Int(u64),
+    Bool(bool),
+}
+
+pub fn parse_value(s: &str) -> Result<Value, String> {
+    if let Some(s) = s.strip_prefix('"') {
+        let s = s.strip_suffix('"')
+            .ok_or_else(|| format!("unterminated string {:?}", s))?;
+        let mut out = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('0') => out.push('\0'),
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some(c) => return Err(format!("unknown escape \\{}", c)),
+                None => return Err("trailing backslash".into()),
+            }
+        }
+        return Ok(Value::String(out));
+    }
+
+    match s {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+
+    let s_ = s.replace('_', "");
+    let res = if let Some(s) = s_.strip_prefix("0x") {
+        u64::from_str_radix(s, 16)
+    } else if let Some(s) = s_.strip_prefix("0o") {
+        u64::from_str_radix(s, 8)
+    } else if let Some(s) = s_.strip_prefix("0b") {
+        u64::from_str_radix(s, 2)
+    } else {
+        s_.parse()
+    };
+    res.map(Value::Int).map_err(|_| format!("invalid value {:?}", s))
+}
+
+// strip comments, being careful not to strip inside strings
+pub fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+impl Frame {
+    pub fn parse(spec: &str) -> Result<Frame, String> {
+        let mut frame = Frame{polynomial: 0x11edc6f41, init: None,
+            xorout: None, refin: true, refout: true, fields: vec![]};
+
+        for (i, line) in spec.lines().enumerate() {
+            let err = |e: String| format!("line {}: {}", i+1, e);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line == "[[field]]" {
+                frame.fields.push(Field{
+                    name: format!("field{}", frame.fields.len()),
+                    len: None,
+                    content: None,
+                    fill: None,
+                    covered: true,
+                    free: false,
+                    crc: None,
+                });
+                continue;
+            }
+
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| err(format!("expected key = value, found {:?}",
+                    line)))?;
+            let key = key.trim();
+            let value = parse_value(value.trim()).map_err(err)?;
+            let u32_ = |x: u64| u32::try_from(x)
+                .map_err(|_| err(format!("{} 0x{:x} doesn't fit in 32 bits",
+                    key, x)));
+            let field = frame.fields.last_mut();
+            match (key, value, field) {
+                ("polynomial", Value::Int(p), None) => frame.polynomial = p,
+                ("init", Value::Int(x), None) => frame.init = Some(u32_(x)?),
+                ("xorout", Value::Int(x), None)
+                    => frame.xorout = Some(u32_(x)?),
+                ("refin", Value::Bool(b), None) => frame.refin = b,
+                ("refout", Value::Bool(b), None) => frame.refout = b,
+                ("algorithm", Value::String(s), None) => {
+                    let alg = catalog::find(&s)
+                        .ok_or_else(|| err(format!("unknown algorithm {:?}",
+                            s)))?;
+                    if alg.width > 32 {
+                        return Err(err(format!("{} is {} bits, frames are \
+                            limited to 32 bits", alg.name, alg.width)));
+                    }
+                    frame.polynomial = alg.polynomial() as u64;
+                    frame.init = Some(alg.init as u32);
+                    frame.xorout = Some(alg.xorout as u32);
+                    frame.refin = alg.refin;
+                    frame.refout = alg.refout;
+                }
+                ("name", Value::String(s), Some(f)) => f.name = s,
+                ("len", Value::Int(n), Some(f)) => f.len = Some(n as usize),
+                // hex can be grouped with spaces or underscores
+                ("hex", Value::String(s), Some(f))
+                    => f.content = Some(unhex(&s.replace([' ', '_'], ""))
+                        .map_err(err)?),
+                ("string", Value::String(s), Some(f))
+                    => f.content = Some(s.into_bytes()),
+                ("fill", Value::Int(b), Some(f)) => f.fill = Some(
+                    u8::try_from(b)
+                        .map_err(|_| err(format!("fill {} isn't a byte", b)))?),
+                ("covered", Value::Bool(b), Some(f)) => f.covered = b,
+                ("free", Value::Bool(b), Some(f)) => f.free = b,
+                ("crc", Value::String(s), Some(f))
+                    => f.crc = Some(s.parse().map_err(err)?),
+                (key, value, _) => return Err(err(format!(
+                    "unexpected {} = {:?}", key, value))),
+            }
+        }
+
+        // sanity check
+        if !(2 .. 1 << 33).contains(&frame.polynomial) {
+            return Err(format!("polynomial 0x{:x} not supported, widths \
+                are limited to 1 to 32 bits", frame.polynomial));
+        }
+        let width = 63 - frame.polynomial.leading_zeros();
+        let ones = (!0u64 >> (64 - width)) as u32;
+        if frame.init.is_some_and(|init| init & !ones != 0) {
+            return Err(format!("init doesn't fit in {} bits", width));
+        }
+        if frame.xorout.is_some_and(|xorout| xorout & !ones != 0) {
+            return Err(format!("xorout doesn't fit in {} bits", width));
+        }
+        let n = width.div_ceil(8) as usize;
+        let mut crcs = 0;
+        let mut unsized_ = 0;
+        for f in &mut frame.fields {
+            if f.crc.is_some() {
+                crcs += 1;
+                if f.len.unwrap_or(n) != n {
+                    return Err(format!("{}: crc fields are {} bytes for a \
+                        {}-bit crc", f.name, n, width));
+                }
+                f.len = Some(n);
+                // a CRC can't cover itself
+                f.covered = false;
+            }
+            if let (Some(len), Some(content)) = (f.len, &f.content) {
+                if content.len() != len {
+                    return Err(format!("{}: len {} but content is {} bytes",
+                        f.name, len, content.len()));
+                }
+            }
+            if f.free && !f.covered {
+                return Err(format!("{}: free fields must be covered",
+                    f.name));
+            }
+            if f.len.is_none() && f.content.is_none() {
+                unsized_ += 1;
+            }
+        }
+        if crcs != 1 {
+            return Err(format!("expected 1 crc field, found {}", crcs));
+        }
+        if unsized_ > 1 {
+            return Err("at most one field can leave out its len".into());
+        }
+
+        Ok(frame)
+    }
+
+    /// Offsets and lengths of each field in a message of this length, or
+    /// from the spec alone if len is None
+    pub fn layout(&self, len: Option<usize>)
+            -> Result<Vec<(usize, usize)>, String> {
+        let known = self.fields.iter()
+            .map(|f| f.len.or(f.content.as_ref().map(|c| c.len())))
+            .collect::<Vec<_>>();
+        let fixed = known.iter().flatten().sum::<usize>();
+        let rest = match len {
+            Some(len) if len < fixed => return Err(format!(
+                "message is {} bytes, but the frame needs at least {}",
+                len, fixed)),
+            Some(len) if known.iter().all(|k| k.is_some()) && len != fixed
+                => return Err(format!(
+                    "message is {} bytes, but the frame is {}", len, fixed)),
+            Some(len) => len - fixed,
+            None if known.iter().any(|k| k.is_none()) => return Err(
+                "a field without a len needs content or a message".into()),
+            None => 0,
+        };
+
+        let mut off = 0;
+        Ok(known.iter()
+            .map(|k| {
+                let len = k.unwrap_or(rest);
+                off += len;
+                (off - len, len)
+            })
+            .collect())
+    }
+
+    /// Build a message from the spec alone
+    pub fn build(&self) -> Result<Vec<u8>, String> {
+        let layout = self.layout(None)?;
+        let mut data = vec![];
+        for (f, &(_, len)) in self.fields.iter().zip(&layout) {
+            match &f.content {
+                Some(content) => data.extend_from_slice(content),
+                None => data.extend(
+                    core::iter::repeat_n(f.fill.unwrap_or(0), len)),
+            }
+        }
+        Ok(data)
+    }
+
+    /// Our CRC, with any parameters from the spec
+    pub fn crc32(&self) -> Crc32 {
+        let mut crc32 = Crc32::new(self.polynomial);
+        if let Some(init) = self.init {
+            crc32 = crc32.with_init(init);
+        }
+        if let Some(xorout) = self.xorout {
+            crc32 = crc32.with_xorout(xorout);
+        }
+        crc32.with_reflect(self.refin, self.refout)
+    }
+
+    fn crc_field(&self, layout: &[(usize, usize)])
+            -> (Endian, core::ops::Range<usize>) {
+        let (i, f) = self.fields.iter().enumerate()
+            .find(|(_, f)| f.crc.is_some())
+            .unwrap();
+        let (off, len) = layout[i];
+        (f.crc.unwrap(), off .. off+len)
+    }
+
+    // bytes fed to our CRC, and where each came from
+    fn covered(&self, layout: &[(usize, usize)]) -> Vec<usize> {
+        self.fields.iter().zip(layout)
+            .filter(|(f, _)| f.covered)
+            .flat_map(|(_, &(off, len))| off .. off+len)
+            .collect()
+    }
+
+    /// Compute the CRC of a message, returning the expected and stored CRC
+    pub fn verify(&self, data: &[u8]) -> Result<(u32, u32), String> {
+        let layout = self.layout(Some(data.len()))?;
+        let covered = self.covered(&layout).into_iter()
+            .map(|i| data[i])
+            .collect::<Vec<_>>();
+        let crc32 = self.crc32();
+        let expected = crc32.crc32(crc32.init(), &covered);
+        let (endian, field) = self.crc_field(&layout);
+        let stored = endian.load(&data[field]);
+        Ok((expected, stored))
+    }
+
+    /// Store a CRC in a message
+    pub fn store(&self, data: &mut [u8], crc: u32) -> Result<(), String> {
+        let layout = self.layout(Some(data.len()))?;
+        let (endian, field) = self.crc_field(&layout);
+        endian.store(crc, &mut data[field]);
+        Ok(())
+    }
+
+    /// Store the correct CRC in a message
+    pub fn encode(&self, data: &mut [u8]) -> Result<(), String> {
+        let (expected, _) = self.verify(data)?;
+        self.store(data, expected)
+    }
+
+    /// Change the free fields of a message so that it matches the CRC
+    /// already stored in it, returns false if we can't
+    ///
+    /// We only use the last 16 free bytes, which is plenty for a 32-bit
+    /// CRC.
+    pub fn solve(&self, data: &mut [u8]) -> Result<bool, String> {
+        let layout = self.layout(Some(data.len()))?;
+        let (_, stored) = self.verify(data)?;
+        let crc32 = self.crc32();
+
+        let covered = self.covered(&layout);
+        let mut free = self.fields.iter().zip(&layout)
+            .filter(|(f, _)| f.free)
+            .flat_map(|(_, &(off, len))| off .. off+len)
+            .collect::<Vec<_>>();
+        free.drain(..free.len().saturating_sub(16));
+
+        // our CRC is affine in the bits of our free bytes
+        let crc = |data: &[u8]| {
+            crc32.crc32(crc32.init(), &covered.iter()
+                .map(|&i| data[i])
+                .collect::<Vec<_>>())
+        };
+        let base = crc(data);
+        let cols = free.iter()
+            .flat_map(|&i| (0..8).map(move |b| (i, b)))
+            .map(|(i, b)| {
+                data[i] ^= 1 << b;
+                let col = crc(data) ^ base;
+                data[i] ^= 1 << b;
+                col as u64
+            })
+            .collect::<Vec<_>>();
+
+        let x = match gf2::solve(&cols, (stored ^ base) as u64) {
+            Some(x) => x,
+            None => return Ok(false),
+        };
+        for (j, &i) in free.iter().enumerate() {
+            data[i] ^= (x >> (8*j)) as u8;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r#"
+        polynomial = 0x11edc6f41   # crc-32c
+
+        [[field]]
+        name = "magic"
+        hex = "a5 5a"
+
+        [[field]]
+        name = "seq"
+        len = 2
+        covered = false
+
+        [[field]]
+        name = "payload"
+        string = "hello"
+
+        [[field]]
+        name = "spare"
+        len = 4
+        fill = 0xff
+        free = true
+
+        [[field]]
+        name = "crc"
+        crc = "little"
+    "#;
+
+    #[test]
+    fn encode_verifies() {
+        let frame = Frame::parse(SPEC).unwrap();
+        let mut data = frame.build().unwrap();
+        assert_eq!(data.len(), 17);
+        frame.encode(&mut data).unwrap();
+
+        // the CRC covers everything but seq and itself
+        let crc32 = Crc32::new(0x11edc6f41);
+        let covered = [&b"\xa5\x5a"[..], b"hello", b"\xff\xff\xff\xff"]
+            .concat();
+        let crc = crc32.crc32(0, &covered);
+        assert_eq!(&data[13..], &crc.to_le_bytes());
+        assert_eq!(frame.verify(&data).unwrap(), (crc, crc));
+
+        data[2] ^= 0x55;
+        assert_eq!(frame.verify(&data).unwrap(), (crc, crc));
+        data[4] ^= 0x55;
+        assert_ne!(frame.verify(&data).unwrap(), (crc, crc));
+    }
+
+    #[test]
+    fn solve_forges_free_fields() {
+        let frame = Frame::parse(SPEC).unwrap();
+        for crc in [0, 0x12345678, 0xffffffff] {
+            let mut data = frame.build().unwrap();
+            frame.store(&mut data, crc).unwrap();
+            let before = data.clone();
+            assert!(frame.solve(&mut data).unwrap());
+            assert_eq!(frame.verify(&data).unwrap(), (crc, crc));
+            // only spare changed
+            assert_eq!(data[..9], before[..9]);
+            assert_eq!(data[13..], before[13..]);
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        for spec in [
+            "[[field]]\nlen = 4",
+            "[[field]]\ncrc = \"little\"\n[[field]]\ncrc = \"big\"",
+            "[[field]]\nfree = true\ncovered = false\n\
+                [[field]]\ncrc = \"little\"",
+            "[[field]]\n[[field]]\n[[field]]\ncrc = \"little\"",
+            "polynomial = 1\n[[field]]\ncrc = \"little\"",
+            "[[field]]\nhex = \"abc\"\n[[field]]\ncrc = \"little\"",
+            "bogus = 1",
+        ] {
+            assert!(Frame::parse(spec).is_err(), "{:?} parsed", spec);
+        }
+    }
+}
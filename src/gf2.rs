@@ -0,0 +1,262 @@
+// Linear algebra over GF(2)
+//
+// CRCs are affine over GF(2), so many of our searches reduce to solving
+// a system of linear equations where each column is the CRC contribution
+// of a single free bit.
+//
+
+/// Solve for x in A*x = b, where A is given as a set of columns
+///
+/// Returns a bitmask of the columns that xor to b, or None if b is not in
+/// the span of A. Any free variables are left as zero.
+pub fn solve(cols: &[u64], b: u64) -> Option<u128> {
+    assert!(cols.len() <= 128);
+
+    // build a basis indexed by leading bit, tracking which columns make up
+    // each basis vector
+    let mut basis: [Option<(u64, u128)>; 64] = [None; 64];
+    for (j, &c) in cols.iter().enumerate() {
+        let mut v = c;
+        let mut m = 1u128 << j;
+        while v != 0 {
+            let lead = 63 - v.leading_zeros() as usize;
+            match basis[lead] {
+                Some((bv, bm)) => {
+                    v ^= bv;
+                    m ^= bm;
+                }
+                None => {
+                    basis[lead] = Some((v, m));
+                    break;
+                }
+            }
+        }
+    }
+
+    // reduce b by our basis
+    let mut v = b;
+    let mut x = 0;
+    while v != 0 {
+        let lead = 63 - v.leading_zeros() as usize;
+        let (bv, bm) = basis[lead]?;
+        v ^= bv;
+        x ^= bm;
+    }
+    Some(x)
+}
+
+/// Rank of the matrix made up of these columns
+pub fn rank(cols: &[u64]) -> usize {
+    let mut basis = [0u64; 64];
+    let mut rank = 0;
+    for &c in cols {
+        let mut v = c;
+        while v != 0 {
+            let lead = 63 - v.leading_zeros() as usize;
+            if basis[lead] == 0 {
+                basis[lead] = v;
+                rank += 1;
+                break;
+            }
+            v ^= basis[lead];
+        }
+    }
+    rank
+}
+
+/// Solve for every x in A*x = b, where A is given as a set of rows, each
+/// row being the coefficients and right-hand side of one equation over
+/// n unknowns
+///
+/// Returns a particular solution and a basis of the nullspace, every
+/// solution is the particular solution xored with some subset of the
+/// basis. Returns None if the equations are inconsistent.
+pub fn solve_rows(rows: &[(u64, bool)], n: usize) -> Option<(u64, Vec<u64>)> {
+    assert!(n <= 64);
+
+    // build an echelon form indexed by lowest set bit
+    let mut pivots: [Option<(u64, bool)>; 64] = [None; 64];
+    for &(r, b) in rows {
+        let (mut r, mut b) = (r, b);
+        while r != 0 {
+            let lead = r.trailing_zeros() as usize;
+            match pivots[lead] {
+                Some((pr, pb)) => {
+                    r ^= pr;
+                    b ^= pb;
+                }
+                None => {
+                    pivots[lead] = Some((r, b));
+                    break;
+                }
+            }
+        }
+        if r == 0 && b {
+            return None;
+        }
+    }
+
+    // back substitute, each pivot only depends on higher unknowns, so
+    // work downwards
+    let substitute = |mut x: u64, rhs: bool| {
+        for j in (0..n).rev() {
+            if let Some((r, b)) = pivots[j] {
+                let parity = (r & x & !(1 << j)).count_ones() & 1 == 1;
+                if parity ^ (b && rhs) {
+                    x |= 1 << j;
+                }
+            }
+        }
+        x
+    };
+
+    // free unknowns make up our nullspace
+    let basis = (0..n)
+        .filter(|&j| pivots[j].is_none())
+        .map(|j| substitute(1 << j, false))
+        .collect();
+    Some((substitute(0, true), basis))
+}
+
+/// Solve for x in A*x = b for vectors wider than 64 bits, where A is given
+/// as a set of columns, and vectors are u64 limbs, least significant first
+///
+/// Returns a bitmask of the columns that xor to b, or None if b is not in
+/// the span of A. Any free variables are left as zero.
+pub fn solve_wide(cols: &[Vec<u64>], b: &[u64]) -> Option<Vec<u64>> {
+    let limbs = b.len();
+    let lead = |v: &[u64]| v.iter().enumerate().rev()
+        .find(|(_, &l)| l != 0)
+        .map(|(i, l)| 64*i + 63 - l.leading_zeros() as usize);
+    let xor = |a: &mut [u64], b: &[u64]| {
+        for (a, b) in a.iter_mut().zip(b) {
+            *a ^= b;
+        }
+    };
+
+    // build a basis indexed by leading bit, tracking which columns make up
+    // each basis vector
+    let mut basis: Vec<Option<(Vec<u64>, Vec<u64>)>> = vec![None; 64*limbs];
+    for (j, c) in cols.iter().enumerate() {
+        assert_eq!(c.len(), limbs);
+        let mut v = c.clone();
+        let mut m = vec![0; cols.len().div_ceil(64)];
+        m[j/64] |= 1 << (j%64);
+        while let Some(i) = lead(&v) {
+            match &basis[i] {
+                Some((bv, bm)) => {
+                    xor(&mut v, bv);
+                    xor(&mut m, bm);
+                }
+                None => {
+                    basis[i] = Some((v, m));
+                    break;
+                }
+            }
+        }
+    }
+
+    // reduce b by our basis
+    let mut v = b.to_vec();
+    let mut x = vec![0; cols.len().div_ceil(64)];
+    while let Some(i) = lead(&v) {
+        let (bv, bm) = basis[i].as_ref()?;
+        xor(&mut v, bv);
+        xor(&mut x, bm);
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    // xor the columns x selects
+    fn apply(cols: &[u64], x: u128) -> u64 {
+        cols.iter().enumerate()
+            .filter(|(j, _)| x & (1 << j) != 0)
+            .fold(0, |b, (_, c)| b ^ c)
+    }
+
+    #[test]
+    fn solve_round_trips() {
+        let mut rng = Rng::new(1);
+        for n in [1, 8, 32, 63, 64, 100, 128] {
+            let cols = (0..n).map(|_| rng.next_u64()).collect::<Vec<_>>();
+            for _ in 0..100 {
+                let x = ((rng.next_u64() as u128) << 64)
+                    | rng.next_u64() as u128;
+                let x = if n < 128 { x & ((1 << n) - 1) } else { x };
+                let b = apply(&cols, x);
+                let x_ = solve(&cols, b).unwrap();
+                assert_eq!(apply(&cols, x_), b);
+            }
+        }
+    }
+
+    #[test]
+    fn solve_outside_span() {
+        // nothing here can reach the top bit
+        let mut rng = Rng::new(2);
+        let cols = (0..32).map(|_| rng.next_u64() >> 1).collect::<Vec<_>>();
+        assert_eq!(solve(&cols, 1 << 63), None);
+        assert_eq!(solve(&[], 1), None);
+        assert_eq!(solve(&[], 0), Some(0));
+        assert_eq!(rank(&cols), 32);
+        assert_eq!(rank(&[1, 2, 3]), 2);
+    }
+
+    #[test]
+    fn solve_rows_round_trips() {
+        let mut rng = Rng::new(3);
+        for (n, m) in [(8, 4), (32, 32), (32, 40), (64, 48), (64, 64)] {
+            let mask = if n == 64 { !0 } else { (1u64 << n) - 1 };
+            let x = rng.next_u64() & mask;
+            let rows = (0..m)
+                .map(|_| {
+                    let r = rng.next_u64() & mask;
+                    (r, (r & x).count_ones() & 1 == 1)
+                })
+                .collect::<Vec<_>>();
+
+            let (x_, basis) = solve_rows(&rows, n).unwrap();
+            for &(r, b) in &rows {
+                assert_eq!((r & x_).count_ones() & 1 == 1, b);
+                for &k in &basis {
+                    assert_eq!((r & k).count_ones() & 1, 0);
+                }
+            }
+            // every unknown is either pinned down or free
+            let rank = rank(&rows.iter().map(|&(r, _)| r).collect::<Vec<_>>());
+            assert_eq!(basis.len(), n - rank);
+        }
+    }
+
+    #[test]
+    fn solve_rows_inconsistent() {
+        let rows = [(0b011, true), (0b110, false), (0b101, false)];
+        assert_eq!(solve_rows(&rows, 3), None);
+    }
+
+    #[test]
+    fn solve_wide_round_trips() {
+        let mut rng = Rng::new(4);
+        let cols = (0..200)
+            .map(|_| vec![rng.next_u64(), rng.next_u64(), rng.next_u64()])
+            .collect::<Vec<_>>();
+        let x = (0..cols.len()).filter(|_| rng.below(2) == 1)
+            .collect::<Vec<_>>();
+        let b = x.iter().fold(vec![0; 3], |b, &j| {
+            b.iter().zip(&cols[j]).map(|(b, c)| b ^ c).collect()
+        });
+
+        let x_ = solve_wide(&cols, &b).unwrap();
+        let b_ = (0..cols.len())
+            .filter(|&j| x_[j/64] & (1 << (j%64)) != 0)
+            .fold(vec![0; 3], |b, j| {
+                b.iter().zip(&cols[j]).map(|(b, c)| b ^ c).collect()
+            });
+        assert_eq!(b_, b);
+    }
+}
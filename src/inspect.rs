@@ -0,0 +1,146 @@
+// Detecting forged suffixes
+//
+// The inverse of our search. A forged region is usually a short run of
+// bytes, often from a constrained alphabet, right before a point where the
+// CRC lands on a suspiciously round value. Nothing here is proof, a
+// finding only means a region looks like something we would produce.
+//
+
+use core::ops::Range;
+use crate::crc::Crc32;
+use crate::predicate::Predicate;
+
+// a named test for bytes in an alphabet
+type Class = (&'static str, fn(u8) -> bool);
+
+/// Alphabets our charsets draw from, most specific first
+const CLASSES: &[Class] = &[
+    ("whitespace", |b| b == b' ' || b == b'\t'),
+    ("ascii", |b| matches!(b, b'H'..=b'W' | b'h'..=b'w')),
+    ("hex", |b| matches!(b, b'0'..=b'9' | b'a'..=b'f')),
+    ("alnum", |b| b.is_ascii_alphanumeric()),
+    ("base64", |b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/'),
+];
+
+/// A suspicious region
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// Bytes that look forged
+    pub region: Range<usize>,
+    /// Alphabet the region appears to be drawn from, or raw
+    pub class: &'static str,
+    /// CRC of the message up to the end of the region
+    pub crc: u32,
+    /// Why this CRC looks forced
+    pub reason: String,
+}
+
+/// Does this CRC look like someone picked it? Returns a reason and a
+/// rough number of bits of structure, higher is more suspicious
+pub fn round(crc32: &Crc32, crc: u32) -> Option<(String, u32)> {
+    // narrower CRCs have less room for structure
+    let width = crc32.width();
+    let n = width.div_ceil(8) as usize;
+    let leading = crc.leading_zeros() - (32 - width);
+    let trailing = crc.trailing_zeros().min(width);
+    if crc == 0 {
+        Some(("zero".into(), width))
+    } else if crc == crc32.mask() {
+        Some(("all ones".into(), width))
+    } else if crc == crc32.residue() {
+        Some(("residue, as if followed by its own CRC".into(), width))
+    } else if width.is_multiple_of(8) && n > 1
+            && crc.to_le_bytes()[..n].iter().all(|&b| b == crc as u8) {
+        Some((format!("repeated byte 0x{:02x}", crc as u8), width-8))
+    } else if width == 32 && Predicate::Palindrome.test(crc, width) {
+        Some(("palindromic hex".into(), 16))
+    } else if leading >= 16 {
+        Some((format!("{} leading zero bits", leading), leading))
+    } else if trailing >= 16 {
+        Some((format!("{} trailing zero bits", trailing), trailing))
+    } else {
+        None
+    }
+}
+
+// find the run of constrained characters ending at end, if any, up to
+// max bytes
+fn run(data: &[u8], end: usize, max: usize) -> Option<(Range<usize>, &'static str)> {
+    CLASSES.iter()
+        .filter_map(|&(name, class)| {
+            let len = data[..end].iter().rev()
+                .take(max)
+                .take_while(|&&b| class(b))
+                .count();
+            (len >= 4).then(|| (end-len .. end, name))
+        })
+        .next()
+}
+
+/// Inspect a message for regions that look like forged suffixes
+///
+/// Every prefix of our message is checked, since forged regions can be
+/// followed by unconstrained data, but we require more structure from
+/// embedded CRCs than from the CRC of the whole message, otherwise large
+/// messages would be full of false positives.
+pub fn inspect(crc32: &Crc32, data: &[u8]) -> Vec<Finding> {
+    // bits of structure needed, roughly the number of false positives we
+    // expect is the message length over 2^bits
+    let needed = |end: usize| if end == data.len() {
+        16
+    } else {
+        (usize::BITS - data.len().leading_zeros()) + 12
+    };
+
+    // a raw forged block is as wide as our CRC
+    let n = crc32.width().div_ceil(8) as usize;
+    let mut findings: Vec<Finding> = vec![];
+    let mut crc = crc32.init();
+    for end in 1 ..= data.len() {
+        crc = crc32.crc32(crc, &data[end-1 .. end]);
+        if end < n {
+            continue;
+        }
+
+        let reason = match round(crc32, crc) {
+            Some((reason, bits)) if bits >= needed(end) => reason,
+            _ => continue,
+        };
+
+        // a region of constrained characters suggests a charset, otherwise
+        // assume a raw block, note suffixes are often followed by a
+        // newline or comment/JSON closer
+        let closers = data[..end].iter().rev()
+            .take(4)
+            .take_while(|b| b"\r\n*/\"} ".contains(b))
+            .count();
+        let (region, class) = (0 ..= closers)
+            .filter_map(|k| run(data, end-k, 64))
+            .max_by_key(|(r, _)| r.len())
+            .unwrap_or((end-n .. end, "raw"));
+        findings.push(Finding{region, class, crc, reason});
+    }
+
+    // long runs of mixed spaces and tabs are a classic way to hide a
+    // suffix, even if we can't tell what it forces
+    let mut i = 0;
+    while i < data.len() {
+        let len = data[i..].iter()
+            .take_while(|&&b| b == b' ' || b == b'\t')
+            .count();
+        let run = &data[i .. i+len];
+        if len >= 16 && run.contains(&b' ') && run.contains(&b'\t')
+                && !findings.iter().any(|f| f.region.end == i+len) {
+            findings.push(Finding{
+                region: i .. i+len,
+                class: "whitespace",
+                crc: crc32.crc32(crc32.init(), &data[..i+len]),
+                reason: "long run of mixed spaces and tabs".into(),
+            });
+        }
+        i += len.max(1);
+    }
+
+    findings.sort_by_key(|f| f.region.start);
+    findings
+}
@@ -0,0 +1,364 @@
+// JSON fields for wrapping our suffix
+//
+// This lets us append a suffix to a JSON object as the value of a new
+// string field, keeping the message valid JSON, as long as our suffix is
+// limited to characters that don't need escaping.
+//
+// We also need to read and write a bit of JSON ourselves, for our cluster
+// protocol and batch job files, which is what Value is for.
+//
+
+use core::fmt;
+
+/// Split a JSON object into the bytes before and after the value of a
+/// new string field
+pub fn field(doc: &[u8], name: &str) -> Result<(Vec<u8>, Vec<u8>), String> {
+    // find the end of our object, allowing trailing whitespace
+    let end = doc.iter()
+        .rposition(|&b| b == b'}')
+        .filter(|&i| doc[i+1..].iter().all(u8::is_ascii_whitespace))
+        .ok_or("expected a JSON object ending in }")?;
+    if doc.iter().find(|b| !b.is_ascii_whitespace()) != Some(&b'{') {
+        return Err("expected a JSON object starting with {".into());
+    }
+
+    // do we need a comma?
+    let empty = doc[..end].iter()
+        .rfind(|b| !b.is_ascii_whitespace())
+        == Some(&b'{');
+
+    let mut prefix = doc[..end].to_vec();
+    if !empty {
+        prefix.push(b',');
+    }
+    prefix.push(b'"');
+    for c in name.chars() {
+        match c {
+            '"' => prefix.extend_from_slice(b"\\\""),
+            '\\' => prefix.extend_from_slice(b"\\\\"),
+            c if (c as u32) < 0x20 => prefix.extend_from_slice(
+                format!("\\u{:04x}", c as u32).as_bytes()),
+            c => prefix.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    prefix.extend_from_slice(b"\":\"");
+
+    let mut postfix = b"\"".to_vec();
+    postfix.extend_from_slice(&doc[end..]);
+
+    Ok((prefix, postfix))
+}
+
+/// A parsed JSON value, objects keep their order
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Build an object from its fields
+    pub fn object<'a>(
+        fields: impl IntoIterator<Item=(&'a str, Value)>,
+    ) -> Value {
+        Value::Object(fields.into_iter()
+            .map(|(k, v)| (k.into(), v))
+            .collect())
+    }
+
+    /// Look up a field of an object
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// The contents of a string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Integers that fit in a u64 exactly
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::Number(n) if n >= 0.0 && n.fract() == 0.0
+                && n < 2f64.powi(53) => Some(n as u64),
+            _ => None,
+        }
+    }
+
+    /// Parse a single JSON value
+    pub fn parse(s: &str) -> Result<Value, String> {
+        let mut p = Parser{s: s.as_bytes(), i: 0};
+        let v = p.value()?;
+        p.ws();
+        if p.i != p.s.len() {
+            return Err(format!("trailing characters at {}", p.i));
+        }
+        Ok(v)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+            write!(f, "\"")?;
+            for c in s.chars() {
+                match c {
+                    '"' => write!(f, "\\\"")?,
+                    '\\' => write!(f, "\\\\")?,
+                    c if (c as u32) < 0x20 => write!(f, "\\u{:04x}",
+                        c as u32)?,
+                    c => write!(f, "{}", c)?,
+                }
+            }
+            write!(f, "\"")
+        }
+
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => string(f, s),
+            Value::Array(vs) => {
+                write!(f, "[")?;
+                for (i, v) in vs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    string(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    i: usize,
+}
+
+impl Parser<'_> {
+    fn ws(&mut self) {
+        while self.s.get(self.i).is_some_and(u8::is_ascii_whitespace) {
+            self.i += 1;
+        }
+    }
+
+    fn eat(&mut self, c: u8) -> Result<(), String> {
+        self.ws();
+        if self.s.get(self.i) != Some(&c) {
+            return Err(format!("expected {:?} at {}", c as char, self.i));
+        }
+        self.i += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.ws();
+        let rest = &self.s[self.i..];
+        for (word, v) in [
+            ("null", Value::Null),
+            ("true", Value::Bool(true)),
+            ("false", Value::Bool(false)),
+        ] {
+            if rest.starts_with(word.as_bytes()) {
+                self.i += word.len();
+                return Ok(v);
+            }
+        }
+
+        match rest.first() {
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'[') => {
+                self.i += 1;
+                let mut vs = vec![];
+                self.ws();
+                if self.s.get(self.i) == Some(&b']') {
+                    self.i += 1;
+                    return Ok(Value::Array(vs));
+                }
+                loop {
+                    vs.push(self.value()?);
+                    self.ws();
+                    match self.s.get(self.i) {
+                        Some(b',') => self.i += 1,
+                        Some(b']') => {
+                            self.i += 1;
+                            return Ok(Value::Array(vs));
+                        }
+                        _ => return Err(format!("expected , or ] at {}",
+                            self.i)),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.i += 1;
+                let mut fields = vec![];
+                self.ws();
+                if self.s.get(self.i) == Some(&b'}') {
+                    self.i += 1;
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    self.ws();
+                    let k = self.string()?;
+                    self.eat(b':')?;
+                    fields.push((k, self.value()?));
+                    self.ws();
+                    match self.s.get(self.i) {
+                        Some(b',') => self.i += 1,
+                        Some(b'}') => {
+                            self.i += 1;
+                            return Ok(Value::Object(fields));
+                        }
+                        _ => return Err(format!("expected , or }} at {}",
+                            self.i)),
+                    }
+                }
+            }
+            _ => {
+                let len = rest.iter()
+                    .position(|b| !b"+-.0123456789eE".contains(b))
+                    .unwrap_or(rest.len());
+                let n = std::str::from_utf8(&rest[..len]).unwrap()
+                    .parse::<f64>()
+                    .map_err(|_| format!("expected a value at {}", self.i))?;
+                self.i += len;
+                Ok(Value::Number(n))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.eat(b'"')?;
+        let mut s = String::new();
+        loop {
+            // find the next quote or escape, everything before it is
+            // copied as is
+            let len = self.s[self.i..].iter()
+                .position(|&b| b == b'"' || b == b'\\')
+                .ok_or("unterminated string")?;
+            s.push_str(std::str::from_utf8(&self.s[self.i..self.i+len])
+                .map_err(|_| "invalid UTF-8")?);
+            self.i += len;
+            if self.s[self.i] == b'"' {
+                self.i += 1;
+                return Ok(s);
+            }
+
+            let c = match self.s.get(self.i+1) {
+                Some(b'"') => '"',
+                Some(b'\\') => '\\',
+                Some(b'/') => '/',
+                Some(b'b') => '\x08',
+                Some(b'f') => '\x0c',
+                Some(b'n') => '\n',
+                Some(b'r') => '\r',
+                Some(b't') => '\t',
+                Some(b'u') => {
+                    // surrogate pairs aren't worth the trouble here
+                    let c = self.s.get(self.i+2..self.i+6)
+                        .and_then(|h| std::str::from_utf8(h).ok())
+                        .and_then(|h| u32::from_str_radix(h, 16).ok())
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| format!("invalid escape at {}",
+                            self.i))?;
+                    self.i += 4;
+                    c
+                }
+                _ => return Err(format!("invalid escape at {}", self.i)),
+            };
+            s.push(c);
+            self.i += 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_values() {
+        let v = Value::parse(r#" {"a": [1, -2.5, 3e2], "b": {"c": null},
+            "d": true, "e": "x\"y\\z\n\u0041"} "#).unwrap();
+        assert_eq!(v.get("a"), Some(&Value::Array(vec![
+            Value::Number(1.0), Value::Number(-2.5), Value::Number(300.0)])));
+        assert_eq!(v.get("b").and_then(|b| b.get("c")), Some(&Value::Null));
+        assert_eq!(v.get("d"), Some(&Value::Bool(true)));
+        assert_eq!(v.get("e").and_then(Value::as_str), Some("x\"y\\z\nA"));
+        assert_eq!(v.get("f"), None);
+        assert_eq!(Value::parse("[]").unwrap(), Value::Array(vec![]));
+        assert_eq!(Value::parse("{}").unwrap(), Value::Object(vec![]));
+    }
+
+    #[test]
+    fn parse_errors() {
+        for s in ["", "[1,]", "[1 2]", "{\"a\" 1}", "{\"a\": 1,}", "\"abc",
+                "\"\\q\"", "1 2", "nul", "{1: 2}"] {
+            assert!(Value::parse(s).is_err(), "{:?} parsed", s);
+        }
+    }
+
+    #[test]
+    fn as_u64() {
+        assert_eq!(Value::Number(42.0).as_u64(), Some(42));
+        assert_eq!(Value::Number(-1.0).as_u64(), None);
+        assert_eq!(Value::Number(1.5).as_u64(), None);
+        assert_eq!(Value::Number(2f64.powi(53)).as_u64(), None);
+        assert_eq!(Value::String("1".into()).as_u64(), None);
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let v = Value::object([
+            ("op", Value::String("claim\t\"x\"\u{1}".into())),
+            ("shard", Value::Number(3.0)),
+            ("found", Value::Null),
+            ("list", Value::Array(vec![Value::Bool(false),
+                Value::object([])])),
+        ]);
+        assert_eq!(Value::parse(&v.to_string()).unwrap(), v);
+    }
+
+    #[test]
+    fn field_splices() {
+        for (doc, want) in [
+            (&b"{}"[..], r#"{"crc":"XYZ"}"#),
+            (b" { } \n", r#" { "crc":"XYZ"}"#),
+            (b"{\"a\":1}", r#"{"a":1,"crc":"XYZ"}"#),
+        ] {
+            let (prefix, postfix) = field(doc, "crc").unwrap();
+            let whole = [&prefix[..], b"XYZ", &postfix].concat();
+            let whole = String::from_utf8(whole).unwrap();
+            assert_eq!(whole.trim_end(), want.trim_end());
+            assert_eq!(Value::parse(&whole).unwrap().get("crc")
+                .and_then(Value::as_str), Some("XYZ"));
+        }
+        assert!(field(b"[1]", "crc").is_err());
+        assert!(field(b"{} x", "crc").is_err());
+    }
+}
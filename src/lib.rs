@@ -0,0 +1,97 @@
+// Brute force CRC collisions
+//
+// The CRC engine and the building blocks of our search, the crcbrute
+// binary is a thin CLI over these.
+//
+
+#![allow(dead_code)]
+
+// hardware polynomial multiplication
+pub mod pmul;
+
+// CRC implementation using Barret reduction
+pub mod crc;
+
+// charsets we can pack a counter into
+pub mod charset;
+
+// linear algebra over GF(2)
+pub mod gf2;
+
+// comment syntax for wrapping our suffix
+pub mod comment;
+
+// JSON fields for wrapping our suffix, and a bit of JSON parsing
+pub mod json;
+
+// small PRNG for simulations and search order
+pub mod rng;
+
+// analysis of CRC polynomials
+pub mod analyze;
+
+// exporting analysis results
+pub mod export;
+
+// live progress reports for long searches
+pub mod progress;
+
+// background daemon for queueing searches
+pub mod daemon;
+
+// coverage of sharded searches
+pub mod coverage;
+
+// recovery of unknown CRC parameters
+pub mod recover;
+
+// frame-format descriptions
+pub mod frame;
+
+// chunked CRC layouts
+pub mod blocks;
+
+// predicates on CRC values
+pub mod predicate;
+
+// CRCs wider than 64 bits
+pub mod wide;
+
+// exporting constraints to SAT/SMT solvers
+pub mod sat;
+
+// detecting forged suffixes
+pub mod inspect;
+
+// wire-order views of our message
+pub mod wire;
+
+// catalog of common CRC algorithms
+pub mod catalog;
+
+// user-defined CRC profiles
+pub mod profile;
+
+// adler-32 checksums
+pub mod adler;
+
+// fletcher checksums
+pub mod fletcher;
+
+// simple checksums
+pub mod sum;
+
+// a common interface for checksums
+pub mod checksum;
+
+// persistent meet-in-the-middle tables
+pub mod table;
+
+// distributing searches across machines
+pub mod cluster;
+
+// batch job files
+pub mod batch;
+
+// memory-mapped files
+pub mod mmap;
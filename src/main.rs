@@ -13,220 +13,5664 @@
 use structopt::StructOpt;
 use core::num;
 use core::str::FromStr;
+use core::ops::RangeInclusive;
+use std::fs;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::io::{self, Read, BufRead, IsTerminal};
+use std::time::{Duration, Instant};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
 
-// hardware polynomial multiplication
-mod pmul;
-use pmul::pmul32;
+use crcbrute::crc::{Crc32, Crc64};
+use crcbrute::charset::{self, Charset, CharsetIter, Pack};
+use crcbrute::comment::Comment;
+use crcbrute::gf2;
+use crcbrute::json;
+use crcbrute::analyze;
+use crcbrute::rng::Rng;
+use crcbrute::export::{self, Table};
+use crcbrute::progress::{self, Progress, Stats};
+use crcbrute::coverage::{self, Coverage};
+use crcbrute::recover::{self, Sample};
+use crcbrute::frame::{Frame, Endian};
+use crcbrute::blocks::Blocks;
+use crcbrute::inspect;
+use crcbrute::predicate::{self, Predicate};
+use crcbrute::wide::{self, Wide};
+use crcbrute::sat::{self, Constraints};
+use crcbrute::wire::{self, View};
+use crcbrute::catalog::{self, Algorithm};
+use crcbrute::profile;
+use crcbrute::cluster::{self, Outcome};
+use crcbrute::batch;
+use crcbrute::mmap::Mmap;
+use crcbrute::adler::Adler32;
+use crcbrute::fletcher::Fletcher;
+use crcbrute::sum::{Sum, SumTarget};
+use crcbrute::checksum::{self, Checksum};
+use crcbrute::table;
+#[cfg(unix)]
+use crcbrute::daemon;
 
-// software polynomial division
-fn pdivmod64(a: u64, b: u64) -> Option<(u64, u64)> {
-    if b == 0 {
-        return None;
+// more parsers
+fn parse_u32(s: &str) -> Result<u32, num::ParseIntError> {
+    if let Some(s) = s.strip_prefix("0x") {
+        u32::from_str_radix(s, 16)
+    } else if let Some(s) = s.strip_prefix("0o") {
+        u32::from_str_radix(s, 8)
+    } else if let Some(s) = s.strip_prefix("0b") {
+        u32::from_str_radix(s, 2)
+    } else {
+        u32::from_str(s)
+    }
+}
+
+fn parse_u64(s: &str) -> Result<u64, num::ParseIntError> {
+    if let Some(s) = s.strip_prefix("0x") {
+        u64::from_str_radix(s, 16)
+    } else if let Some(s) = s.strip_prefix("0o") {
+        u64::from_str_radix(s, 8)
+    } else if let Some(s) = s.strip_prefix("0b") {
+        u64::from_str_radix(s, 2)
+    } else {
+        u64::from_str(s)
     }
+}
 
-    let mut q = 0;
-    let mut r = a;
-    while r.leading_zeros() <= b.leading_zeros() {
-        q ^= 1 << (b.leading_zeros()-r.leading_zeros());
-        r ^= b << (b.leading_zeros()-r.leading_zeros());
+fn parse_u128(s: &str) -> Result<u128, num::ParseIntError> {
+    if let Some(s) = s.strip_prefix("0x") {
+        u128::from_str_radix(s, 16)
+    } else if let Some(s) = s.strip_prefix("0o") {
+        u128::from_str_radix(s, 8)
+    } else if let Some(s) = s.strip_prefix("0b") {
+        u128::from_str_radix(s, 2)
+    } else {
+        u128::from_str(s)
     }
-    Some((q, r))
 }
 
-fn pdiv64(a: u64, b: u64) -> u64 {
-    pdivmod64(a, b).unwrap().0
+fn parse_u8(s: &str) -> Result<u8, String> {
+    let x = parse_u32(s).map_err(|e| format!("{:?}: {}", s, e))?;
+    u8::try_from(x).map_err(|_| format!("{:?}: doesn't fit in a byte", s))
 }
 
-fn pmod64(a: u64, b: u64) -> u64 {
-    pdivmod64(a, b).unwrap().1
+// ranges, either lo..hi or lo..=hi
+fn parse_range(s: &str) -> Result<RangeInclusive<u64>, String> {
+    let err = |e: num::ParseIntError| format!("{:?}: {}", s, e);
+    if let Some((lo, hi)) = s.split_once("..=") {
+        Ok(parse_u64(lo).map_err(err)? ..= parse_u64(hi).map_err(err)?)
+    } else if let Some((lo, hi)) = s.split_once("..") {
+        let (lo, hi) = (parse_u64(lo).map_err(err)?, parse_u64(hi).map_err(err)?);
+        if hi <= lo {
+            return Err(format!("{:?}: empty range", s));
+        }
+        Ok(lo ..= hi-1)
+    } else {
+        Err(format!("{:?}: expected lo..hi or lo..=hi", s))
+    }
 }
 
+// shards, i/n where i counts from 0
+fn parse_shard(s: &str) -> Result<(u64, u64), String> {
+    let err = |e: num::ParseIntError| format!("{:?}: {}", s, e);
+    let (i, n) = s.split_once('/')
+        .ok_or_else(|| format!("{:?}: expected i/n", s))?;
+    let (i, n) = (parse_u64(i).map_err(err)?, parse_u64(n).map_err(err)?);
+    if i >= n {
+        return Err(format!("{:?}: shard must be less than {}", s, n));
+    }
+    Ok((i, n))
+}
 
-// CRC implementation using Barret reduction
-struct Crc32 {
-    p: u64,
-    b: u32,
-    p_r: u32,
-    b_r: u32,
+// find the full polynomial and its width, a polynomial can leave out its
+// top bit if we're told its width
+fn resolve_polynomial(p: u64, width: Option<u32>) -> Result<(u64, u32), String> {
+    resolve_polynomial_upto(p as u128, width, 32)
+        .map(|(p, width)| (p as u64, width))
 }
 
-impl Crc32 {
-    fn new(p: u64) -> Crc32 {
-        // calculate our barret constant
-        let b = pdiv64(p << 32, p) as u32;
-        // and bit-reversed representations
-        let p_r = (p as u32).reverse_bits();
-        let b_r = b.reverse_bits();
+// same as resolve_polynomial, but for widths up to max, which crc64 needs
+fn resolve_polynomial_upto(
+    p: u128,
+    width: Option<u32>,
+    max: u32,
+) -> Result<(u128, u32), String> {
+    let width = match width {
+        Some(width) => width,
+        None if p > 1 => 127 - p.leading_zeros(),
+        None => return Err(format!("invalid polynomial 0x{:x}", p)),
+    };
+    if !(1..=max).contains(&width) {
+        return Err(format!("width {} not supported, widths are limited to \
+            1 to {} bits", width, max));
+    }
+    if p >> width > 1 {
+        return Err(format!("polynomial 0x{:x} doesn't fit in {} bits",
+            p, width));
+    }
+    Ok((p | (1 << width), width))
+}
 
-        Crc32{p, b, p_r, b_r}
+// reversed polynomials are reflected and leave out their top bit, but
+// their +1 becomes their top bit, so they still tell us their width
+fn unreverse_polynomial(p: u64, width: Option<u32>) -> Result<u64, String> {
+    let width = width.unwrap_or(64 - p.leading_zeros());
+    if !(1..64).contains(&width) || p >> width != 0 {
+        return Err(format!("invalid reversed polynomial 0x{:x}", p));
     }
+    Ok((p.reverse_bits() >> (64 - width)) | (1 << width))
+}
 
-    fn crc32(&self, crc: u32, data: &[u8]) -> u32 {
-        // bit invert
-        let mut crc = crc ^ 0xffffffff;
+// CRC values we want, some of these depend on the CRC so we resolve them
+// later
+#[derive(Debug, Clone)]
+enum Target {
+    Value(u32),
+    Zero,
+    Residue,
+    // the CRC of another message
+    Of(Vec<u8>),
+}
 
-        // operate on 4-byte chunks first
-        let mut words = data.chunks_exact(4);
-        for word in &mut words {
-            crc ^= u32::from_le_bytes(<[u8; 4]>::try_from(word).unwrap());
-            let (lo, _) = pmul32(crc, self.b_r);
-            let (lo, hi) = pmul32((lo << 1) ^ crc, self.p_r);
-            crc = (hi << 1) | (lo >> 31);
+impl Target {
+    fn resolve(&self, crc32: &Crc32) -> u32 {
+        match self {
+            Target::Value(v) => *v,
+            Target::Zero => crc32.zero(),
+            Target::Residue => crc32.residue(),
+            Target::Of(data) => crc32.crc32(crc32.init(), data),
         }
+    }
+}
+
+fn parse_target(s: &str) -> Result<Target, num::ParseIntError> {
+    match s {
+        "zero" => Ok(Target::Zero),
+        "residue" => Ok(Target::Residue),
+        _ => Ok(Target::Value(parse_u32(s)?)),
+    }
+}
+
+fn parse_targets_file(path: &PathBuf) -> Result<Vec<Target>, String> {
+    let targets = fs::read_to_string(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    targets.lines()
+        // allow comments and blank lines
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| parse_target(line)
+            .map_err(|e| format!("{}: {:?}: {}", path.display(), line, e)))
+        .collect()
+}
+
+// a second CRC our message should also hit, either a named algorithm, or
+// a polynomial with our default parameters
+#[derive(Debug, Clone, Copy)]
+struct AndCrc {
+    polynomial: u64,
+    init: u32,
+    xorout: u32,
+    refin: bool,
+    refout: bool,
+    target: u32,
+}
 
-        // now clean up any remaining bytes
-        for b in words.remainder() {
-            crc ^= *b as u32;
-            let (lo, _) = pmul32(crc << 24, self.b_r);
-            let (lo, hi) = pmul32((lo << 1) ^ (crc << 24), self.p_r);
-            crc = (crc >> 8) ^ ((hi << 1) | (lo >> 31));
+impl AndCrc {
+    fn crc32(&self) -> Crc32 {
+        Crc32::new(self.polynomial)
+            .with_init(self.init)
+            .with_xorout(self.xorout)
+            .with_reflect(self.refin, self.refout)
+    }
+}
+
+impl FromStr for AndCrc {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AndCrc, String> {
+        let (crc, target) = s.split_once('=')
+            .ok_or_else(|| format!("expected <crc>=<target>, found {:?}", s))?;
+        let target = parse_u32(target)
+            .map_err(|_| format!("invalid target {:?}", target))?;
+        let and_crc = match parse_u64(crc) {
+            Ok(p) => {
+                let (polynomial, width) = resolve_polynomial(p, None)?;
+                let ones = (!0u64 >> (64 - width)) as u32;
+                AndCrc{polynomial, init: ones, xorout: ones,
+                    refin: true, refout: true, target}
+            }
+            Err(_) => {
+                let alg = crc.parse::<Algorithm>()?;
+                if alg.width > 32 {
+                    return Err(format!("{} is {} bits, only CRCs up to 32 \
+                        bits are supported", alg.name, alg.width));
+                }
+                AndCrc{polynomial: alg.polynomial() as u64,
+                    init: alg.init as u32, xorout: alg.xorout as u32,
+                    refin: alg.refin, refout: alg.refout, target}
+            }
+        };
+        if target & !and_crc.crc32().mask() != 0 {
+            return Err(format!("target 0x{:x} doesn't fit in {} bits",
+                target, and_crc.crc32().width()));
         }
+        Ok(and_crc)
+    }
+}
+
+fn parse_ranges_file(path: &PathBuf) -> Result<Vec<RangeInclusive<u64>>, String> {
+    let ranges = fs::read_to_string(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    ranges.lines()
+        // allow comments and blank lines
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| parse_range(line)
+            .map_err(|e| format!("{}: {}", path.display(), e)))
+        .collect()
+}
 
-        // bit invert
-        crc ^ 0xffffffff
+// which bytes of a message are covered by a set of ranges, for CRCs that
+// only cover some fields
+fn covered(len: usize, ranges: &[RangeInclusive<u64>])
+        -> Result<Vec<bool>, String> {
+    let mut covered = vec![false; len];
+    for r in ranges {
+        if *r.end() >= len as u64 {
+            return Err(format!("range {}..={} doesn't fit in {} bytes",
+                r.start(), r.end(), len));
+        }
+        for c in &mut covered[*r.start() as usize ..= *r.end() as usize] {
+            *c = true;
+        }
     }
+    Ok(covered)
 }
 
+// gather the covered bytes of a message
+fn select(data: &[u8], covered: &[bool]) -> Vec<u8> {
+    data.iter().zip(covered)
+        .filter(|(_, &c)| c)
+        .map(|(&b, _)| b)
+        .collect()
+}
 
+// search strategies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Strategy {
+    /// Pick the cheapest strategy that works
+    Auto,
+    /// Solve a linear system over GF(2), only works for affine charsets
+    Algebraic,
+    /// Try every candidate
+    Brute,
+    /// Search with a SAT-style solver, slower than algebraic, but works
+    /// for any charset and knows when there's no solution
+    Sat,
+    /// Meet in the middle, trading memory for the square root of a brute
+    /// force search
+    Mitm,
+}
 
-// more parsers
-fn parse_u32(s: &str) -> Result<u32, num::ParseIntError> {
-    if s.starts_with("0x") {
-        Ok(u32::from_str_radix(&s[2..], 16)?)
-    } else if s.starts_with("0o") {
-        Ok(u32::from_str_radix(&s[2..], 8)?)
-    } else if s.starts_with("0b") {
-        Ok(u32::from_str_radix(&s[2..], 2)?)
-    } else {
-        Ok(u32::from_str(s)?)
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Strategy, String> {
+        match s {
+            "auto" => Ok(Strategy::Auto),
+            "algebraic" => Ok(Strategy::Algebraic),
+            "brute" => Ok(Strategy::Brute),
+            "mitm" => Ok(Strategy::Mitm),
+            #[cfg(feature="sat")]
+            "sat" => Ok(Strategy::Sat),
+            #[cfg(not(feature="sat"))]
+            "sat" => Err("sat strategy needs the sat feature".into()),
+            _ => Err(format!("unknown strategy {:?}", s)),
+        }
     }
 }
 
-fn parse_u64(s: &str) -> Result<u64, num::ParseIntError> {
-    if s.starts_with("0x") {
-        Ok(u64::from_str_radix(&s[2..], 16)?)
-    } else if s.starts_with("0o") {
-        Ok(u64::from_str_radix(&s[2..], 8)?)
-    } else if s.starts_with("0b") {
-        Ok(u64::from_str_radix(&s[2..], 2)?)
-    } else {
-        Ok(u64::from_str(s)?)
+// pick the cheapest strategy that works for our constraints
+//
+// ranged searches only make sense when brute forcing, since the algebraic
+// solver doesn't enumerate candidates, and the same goes for predicates
+// that aren't just masks
+//
+// for other charsets, meeting in the middle takes about the square root of
+// brute force's work, and our tables are small enough for any 32-bit CRC
+fn plan(
+    strategy: Strategy,
+    charset: Charset,
+    numeric: bool,
+    ranged: bool,
+    predicated: bool,
+    random: bool,
+) -> Result<Strategy, String> {
+    match strategy {
+        // algebraic is effectively free, so use it whenever we can
+        Strategy::Auto
+            if !numeric && !ranged && !predicated && charset.is_affine()
+            => Ok(Strategy::Algebraic),
+        // mitm has no order to randomize
+        Strategy::Auto if random => Ok(Strategy::Brute),
+        Strategy::Auto if !numeric && !ranged && !predicated
+            => Ok(Strategy::Mitm),
+        Strategy::Auto => Ok(Strategy::Brute),
+        Strategy::Algebraic if numeric
+            => Err("numeric suffixes aren't affine".into()),
+        Strategy::Algebraic if ranged
+            => Err("algebraic searches can't be split into ranges".into()),
+        Strategy::Algebraic if predicated
+            => Err("algebraic searches only support mask predicates".into()),
+        Strategy::Algebraic if !charset.is_affine()
+            => Err(format!("charset {} isn't affine", charset)),
+        Strategy::Sat if numeric
+            => Err("sat searches don't support numeric suffixes".into()),
+        Strategy::Sat if ranged
+            => Err("sat searches can't be split into ranges".into()),
+        Strategy::Sat if predicated
+            => Err("sat searches only support mask predicates".into()),
+        Strategy::Mitm if numeric
+            => Err("mitm searches don't support numeric suffixes".into()),
+        Strategy::Mitm if ranged
+            => Err("mitm searches can't be split into ranges".into()),
+        Strategy::Mitm if predicated
+            => Err("mitm searches only support mask predicates".into()),
+        Strategy::Sat | Strategy::Mitm if random
+            => Err("only brute force and algebraic searches can be \
+                random".into()),
+        strategy => Ok(strategy),
+    }
+}
+
+// text encodings for our prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Utf8,
+    Utf16le,
+    Utf16be,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Encoding, String> {
+        match s {
+            "utf8" | "utf-8" => Ok(Encoding::Utf8),
+            "utf16le" | "utf-16le" => Ok(Encoding::Utf16le),
+            "utf16be" | "utf-16be" => Ok(Encoding::Utf16be),
+            _ => Err(format!("unknown encoding {:?}", s)),
+        }
+    }
+}
+
+impl Encoding {
+    fn encode(&self, s: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf8 => s.as_bytes().to_vec(),
+            Encoding::Utf16le => s.encode_utf16()
+                .flat_map(|c| c.to_le_bytes())
+                .collect(),
+            Encoding::Utf16be => s.encode_utf16()
+                .flat_map(|c| c.to_be_bytes())
+                .collect(),
+        }
     }
 }
 
+// where a piece of our prefix comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Prefix {
+    Text(String),
+    File(PathBuf),
+    Hex(String),
+    Base64(String),
+}
+
 // CLI arguments
 #[derive(Debug, StructOpt)]
-#[structopt(rename_all="kebab")]
+#[structopt(rename_all="kebab", usage="\
+crcbrute [FLAGS] [OPTIONS] <prefix> [--] [target]...
+    crcbrute [FLAGS] [OPTIONS] --prefix-file <file>... [--] [target]...")]
 struct Opt {
-    /// Prefix of the message we want to find a specific CRC value for
-    prefix: String,
+    /// Prefix of the message we want to find a specific CRC value for,
+    /// with --prefix, --prefix-file, --prefix-hex, or --prefix-base64 this
+    /// is our first target instead
+    #[structopt(required_unless_one=&[
+        "prefix-text", "prefix-file", "prefix-hex", "prefix-base64"])]
+    prefix: Option<String>,
 
-    /// CRC value we want
-    #[structopt(parse(try_from_str=parse_u32))]
-    target: u32,
+    /// Take our prefix as an option, so it can be mixed with other prefix
+    /// options. These can be repeated, and are concatenated in order
+    #[structopt(long="prefix", number_of_values=1)]
+    prefix_text: Vec<String>,
+
+    /// Read our prefix from a file, as raw bytes
+    #[structopt(long, number_of_values=1, parse(from_os_str))]
+    prefix_file: Vec<PathBuf>,
+
+    /// Take our prefix as hex instead, such as bytes copied out of a
+    /// hexdump or packet capture
+    #[structopt(long, number_of_values=1)]
+    prefix_hex: Vec<String>,
+
+    /// Or as base64, for binary prefixes passed through tools that mangle
+    /// raw bytes
+    #[structopt(long, number_of_values=1)]
+    prefix_base64: Vec<String>,
+
+    // our prefix options in the order they were given
+    #[structopt(skip)]
+    prefixes: Vec<Prefix>,
+
+    /// Decode \xNN, \n, \r, \t, \0, and \\ escapes in our text prefixes,
+    /// the same escapes we print our message with
+    #[structopt(short, long)]
+    escapes: bool,
+
+    /// Encode our text prefixes as utf8, utf16le, or utf16be before
+    /// hashing, as in Windows formats that CRC UTF-16 strings. UCS-2 is the
+    /// same as UTF-16 as long as our prefix stays in the basic multilingual
+    /// plane. Note only our prefix is encoded, not our suffix
+    #[structopt(long)]
+    encoding: Option<Encoding>,
+
+    /// CRC values we want, we stop when we find any of these. Can also be
+    /// zero, to leave the CRC register zero, or residue, to make the
+    /// message look like it ends with its own CRC
+    #[structopt(parse(try_from_str=parse_target))]
+    target: Vec<Target>,
+
+    /// Accept any CRC in this range, lo..hi or lo..=hi, can be repeated.
+    /// These ignore --target-mask and --target-hd
+    #[structopt(long, number_of_values=1, parse(try_from_str=parse_range))]
+    target_range: Vec<RangeInclusive<u64>>,
 
-    /// CRC polynomial, currently limited to 32-bits
+    /// Accept any CRC satisfying all of these predicates, can be repeated.
+    /// One of printable, alnum, decimal, palindrome, top-byte=<byte>,
+    /// bottom-byte=<byte>, or leading-zeros=<n>
+    #[structopt(long, number_of_values=1)]
+    predicate: Vec<Predicate>,
+
+    /// Read more CRC values we want from a file, one per line
+    #[structopt(long, parse(from_os_str))]
+    targets_file: Option<PathBuf>,
+
+    /// Accept the CRC of this file under the same parameters, so our
+    /// message collides with it, can be repeated
+    #[structopt(long, number_of_values=1, parse(from_os_str))]
+    target_from_file: Vec<PathBuf>,
+
+    /// Accept the CRC of this message under the same parameters, can be
+    /// repeated
+    #[structopt(long, number_of_values=1)]
+    target_of: Vec<String>,
+
+    /// Only require the bits in this mask to match the target, useful for
+    /// truncated CRCs
+    #[structopt(long,
+        default_value="0xffffffff",
+        parse(try_from_str=parse_u32)
+    )]
+    target_mask: u32,
+
+    /// Accept any CRC within this Hamming distance of the target
+    #[structopt(long, default_value="0")]
+    target_hd: u32,
+
+    /// CRC polynomial, including its top bit, which sets the width of our
+    /// CRC. Multiple polynomials, comma-separated or repeated, forge a
+    /// suffix for each in turn
     #[structopt(short, long,
         default_value="0x11edc6f41",
+        use_delimiter=true,
+        number_of_values=1,
         parse(try_from_str=parse_u64)
     )]
-    polynomial: u64,
+    polynomial: Vec<u64>,
+
+    /// Width of the CRC in bits, 1 to 32, defaults to the degree of the
+    /// polynomial. With this the polynomial's top bit can be left out
+    #[structopt(short, long)]
+    width: Option<u32>,
+
+    /// Polynomials are in Koopman notation, with their top bit but without
+    /// the implicit +1, as in 0x82608edb for CRC-32
+    #[structopt(long)]
+    koopman: bool,
+
+    /// Polynomials are reversed, reflected and without their top bit, as
+    /// in 0xedb88320 for CRC-32
+    #[structopt(long, conflicts_with="koopman")]
+    reversed_poly: bool,
+
+    /// Initial value of the CRC register, unreflected, defaults to all
+    /// ones
+    #[structopt(long, parse(try_from_str=parse_u32))]
+    init: Option<u32>,
+
+    /// Value xored into the final CRC, defaults to all ones
+    #[structopt(long, parse(try_from_str=parse_u32))]
+    xorout: Option<u32>,
+
+    /// Reflect each input byte, true or false, defaults to true
+    #[structopt(long, parse(try_from_str))]
+    refin: Option<bool>,
+
+    /// Reflect the final register, true or false, defaults to true
+    #[structopt(long, parse(try_from_str))]
+    refout: Option<bool>,
+
+    /// Named CRC algorithm, such as crc32c or crc16-ccitt-false. Replaces
+    /// our polynomial and width, and sets any of init, xorout, refin, or
+    /// refout not given. See list-algorithms for what we know
+    #[structopt(short, long)]
+    algorithm: Option<Algorithm>,
+
+    /// Forge a CRC-16/CCITT-FALSE, the unreflected CRC-16 with an init of
+    /// 0xffff, same as --algorithm=crc-16/ccitt-false
+    #[structopt(long, conflicts_with_all=&["algorithm", "profile"])]
+    ccitt: bool,
+
+    /// Named CRC profile from our profiles file, this works the same as
+    /// --algorithm, but for CRCs we define ourselves
+    #[structopt(long, conflicts_with="algorithm")]
+    profile: Option<String>,
+
+    /// File to load profiles from, defaults to $CRCBRUTE_PROFILES or
+    /// ~/.config/crcbrute/profiles.toml
+    #[structopt(long, parse(from_os_str))]
+    profiles_file: Option<PathBuf>,
+
+    /// Forge an Adler-32, as used by zlib, instead of a CRC
+    #[structopt(long)]
+    adler32: bool,
+
+    /// Forge a Fletcher checksum instead of a CRC, either 16 or 32
+    #[structopt(long, conflicts_with="adler32")]
+    fletcher: Option<Fletcher>,
+
+    /// Forge a simple sum instead of a CRC, one of internet, the RFC 1071
+    /// checksum used by IP, UDP, and TCP, sum8, sum16, lrc, or xor8
+    #[structopt(long, conflicts_with_all=&["adler32", "fletcher"])]
+    sum: Option<Sum>,
+
+    /// Also make the whole message hit a byte sum, as <sum>=<target>, one
+    /// of sum8, sum16, or lrc, for example sum16=0x1234. Sums aren't
+    /// linear, so this can't be combined with the sat strategy
+    #[structopt(long, conflicts_with_all=&["adler32", "fletcher", "sum"])]
+    with_sum: Option<SumTarget>,
+
+    /// Also make the message hit a second CRC, as <algorithm>=<target> or
+    /// <polynomial>=<target>, for example crc-16/xmodem=0x1234. A bare
+    /// polynomial uses our default parameters. Needs an affine charset,
+    /// unless we're using the sat strategy
+    #[structopt(long,
+        conflicts_with_all=&["adler32", "fletcher", "sum", "with-sum"])]
+    and_crc: Option<AndCrc>,
 
     /// Limit results to ascii characters, note this doubles the brute
-    /// force suffix
+    /// force suffix, same as --charset-preset=ascii
     #[structopt(long)]
     ascii: bool,
+
+    /// Limit results to a specific charset, one of raw, ascii, whitespace,
+    /// alnum, latin1, dna, octal, hex, base64, or 7bit. Whitespace only
+    /// uses spaces and tabs, so the suffix is invisible in most text
+    #[structopt(long, default_value="raw")]
+    charset_preset: Charset,
+
+    /// Only use the first n bits of our prefix, for frames that aren't a
+    /// whole number of bytes. Our suffix is packed right after these bits,
+    /// in the order our CRC reads them, and the last byte padded with zeros
+    #[structopt(long)]
+    prefix_bits: Option<usize>,
+
+    /// Append a newline after the suffix, so text files still end in a
+    /// newline
+    #[structopt(long)]
+    trailing_newline: bool,
+
+    /// Limit the suffix to a decimal number, such as an ID or timestamp
+    #[structopt(long)]
+    numeric: bool,
+
+    /// Limit the numeric suffix to this range, either lo..hi or lo..=hi,
+    /// implies --numeric
+    #[structopt(long, parse(try_from_str=parse_range))]
+    numeric_range: Option<RangeInclusive<u64>>,
+
+    /// Zero-pad the numeric suffix to exactly this many digits, implies
+    /// --numeric
+    #[structopt(long)]
+    numeric_digits: Option<usize>,
+
+    /// Treat the prefix as a JSON object, and put the suffix in a new
+    /// string field with this name, keeping the message valid JSON. This
+    /// limits the suffix to characters that don't need escaping
+    #[structopt(long)]
+    json_field: Option<String>,
+
+    /// Search strategy, one of auto, algebraic, brute, mitm, or sat.
+    /// Algebraic only works for affine charsets (raw, whitespace, octal,
+    /// and 7bit), mitm needs memory for about the square root of our
+    /// search, sat needs the sat feature, and handles --and-crc but not
+    /// --with-sum. Auto picks algebraic when it can, then mitm, then brute
+    #[structopt(long, default_value="auto")]
+    strategy: Strategy,
+
+    /// Search candidates in a random order, or for algebraic searches,
+    /// pick any free characters at random, so repeated searches find
+    /// different suffixes instead of always the first one. Only applies
+    /// to brute force and algebraic searches
+    #[structopt(long)]
+    random: bool,
+
+    /// Seed for --random, makes a random search reproducible, implies
+    /// --random
+    #[structopt(long, parse(try_from_str=parse_u64))]
+    seed: Option<u64>,
+
+    /// Keep the mitm strategy's table in this file, building it if it
+    /// doesn't exist yet, so later searches with the same CRC, charset,
+    /// and postfix only need lookups. Implies --strategy=mitm
+    #[structopt(long, parse(from_os_str))]
+    mitm_table: Option<PathBuf>,
+
+    /// Only search this range of candidate indices, lo..hi or lo..=hi, for
+    /// splitting a search into shards. For numeric searches the indices
+    /// are the numbers themselves
+    #[structopt(long, parse(try_from_str=parse_range))]
+    range: Option<RangeInclusive<u64>>,
+
+    /// Start searching at this candidate index, an alternative to
+    /// --range. When searching a range we report where we stopped
+    #[structopt(long, conflicts_with="range", parse(try_from_str=parse_u64))]
+    start: Option<u64>,
+
+    /// Stop searching before this candidate index, an alternative to
+    /// --range
+    #[structopt(long, conflicts_with="range", parse(try_from_str=parse_u64))]
+    end: Option<u64>,
+
+    /// Only search shard i of n, counting from 0, each shard gets an equal
+    /// slice of our range so a fleet of machines can split a search
+    /// without coordinating
+    #[structopt(long, parse(try_from_str=parse_shard))]
+    shard: Option<(u64, u64)>,
+
+    /// Stop once this file exists, and create it when we find a solution,
+    /// so shards on a shared filesystem can stop each other early
+    #[structopt(long, parse(from_os_str))]
+    found_file: Option<PathBuf>,
+
+    /// Instead of a suffix, append this many bytes of padding, where only
+    /// a few positions are allowed to differ from the filler byte
+    #[structopt(long)]
+    pad_len: Option<usize>,
+
+    /// Filler byte for padding
+    #[structopt(long, default_value="0xff", parse(try_from_str=parse_u8))]
+    pad_byte: u8,
+
+    /// Positions in the padding allowed to differ from the filler byte,
+    /// comma-separated, defaults to just enough bytes at the end of the
+    /// padding to solve for any CRC. At most 16 positions
+    #[structopt(long, use_delimiter=true, number_of_values=1)]
+    pad_positions: Vec<usize>,
+
+    /// Views of our message the CRC actually sees, bitrev, swap16, or
+    /// swap32, comma-separated or repeated, applied in order
+    #[structopt(long, use_delimiter=true, number_of_values=1)]
+    wire: Vec<View>,
+
+    /// Don't forge anything, just compute the CRC of the prefix and append
+    /// it, or store it at --crc-offset, producing a valid frame
+    #[structopt(long)]
+    append_crc: bool,
+
+    /// Byte offset to store the CRC at with --append-crc, the CRC covers
+    /// everything before this unless --include says otherwise
+    #[structopt(long)]
+    crc_offset: Option<usize>,
+
+    /// Byte order of the CRC with --append-crc, little or big
+    #[structopt(long, default_value="little")]
+    crc_endian: Endian,
+
+    /// Only feed these byte ranges of the message to the CRC, lo..hi or
+    /// lo..=hi, can be repeated. Offsets are into the prefix followed by the
+    /// postfix, the suffix itself is always covered
+    #[structopt(long, number_of_values=1, parse(try_from_str=parse_range))]
+    include: Vec<RangeInclusive<u64>>,
+
+    /// Read more byte ranges to feed the CRC from a file, one per line
+    #[structopt(long, parse(from_os_str))]
+    include_file: Option<PathBuf>,
+
+    /// Skip this range of candidate indices, lo..hi or lo..=hi, can be
+    /// repeated
+    #[structopt(long, number_of_values=1, parse(try_from_str=parse_range))]
+    exclude: Vec<RangeInclusive<u64>>,
+
+    /// Skip everything covered by a previous search's coverage file, can
+    /// be repeated
+    #[structopt(long, number_of_values=1, parse(from_os_str))]
+    exclude_coverage: Vec<PathBuf>,
+
+    /// Write what this search covered and found to a coverage file, which
+    /// can be combined with other shards with crcbrute merge
+    #[structopt(long, parse(from_os_str))]
+    coverage: Option<PathBuf>,
+
+    /// Format of our progress reports, either human or json. Json reports
+    /// are one object per line on stderr
+    #[structopt(long, default_value="human")]
+    progress: ProgressFormat,
+
+    /// Report progress every this many seconds, by default we report
+    /// every 5 seconds if stderr is a terminal
+    #[structopt(long)]
+    progress_interval: Option<f64>,
+
+    /// Don't report progress on our own, progress can still be requested
+    /// with kill -USR1 or enter
+    #[structopt(short, long, conflicts_with="progress-interval")]
+    quiet: bool,
+
+    /// Give up after this many seconds, exiting with status 2. Searches
+    /// that find nothing exit with 3, and errors with 1
+    #[structopt(long)]
+    max_time: Option<f64>,
+
+    /// Give up after trying this many candidates, exiting with status 2
+    #[structopt(long, parse(try_from_str=parse_u64))]
+    max_iters: Option<u64>,
+
+    /// Periodically write what this search has covered to a checkpoint
+    /// file, in the same format as --coverage
+    #[structopt(long, parse(from_os_str))]
+    checkpoint: Option<PathBuf>,
+
+    /// Seconds between checkpoints
+    #[structopt(long, default_value="60")]
+    checkpoint_interval: f64,
+
+    /// Continue a search from a checkpoint, refusing checkpoints from a
+    /// different search. Keeps checkpointing to the same file unless
+    /// --checkpoint says otherwise
+    #[structopt(long, parse(from_os_str))]
+    resume: Option<PathBuf>,
+
+    /// Estimate the probability a solution exists before searching
+    #[structopt(long)]
+    estimate: bool,
+
+    /// Estimate how long our search would take without searching, this
+    /// measures our throughput on a small part of our search first.
+    /// Implies --estimate
+    #[structopt(long)]
+    dry_run: bool,
+
+    /// Number of threads to brute force with, defaults to the number of
+    /// physical cores
+    #[structopt(long)]
+    threads: Option<usize>,
+
+    /// Report performance counters after each search
+    #[structopt(long)]
+    stats: bool,
+
+    /// Also report the CRC register before and after our suffix, before
+    /// xorout, so multi-stage forgeries can pick up where we left off
+    #[structopt(short, long)]
+    verbose: bool,
+
+    /// Explain the math behind our suffix, the CRCs we derive from our
+    /// prefix and postfix, what our suffix needs to contribute, and why
+    /// our solution works
+    #[structopt(long)]
+    explain: bool,
+
+    /// Check candidate suffixes from a file, or - for stdin, instead of
+    /// generating our own. Every match is reported
+    #[structopt(long, parse(from_os_str))]
+    candidates: Option<PathBuf>,
+
+    /// How candidates are framed, either lines, newline-separated, or
+    /// length, each prefixed with a 4-byte little-endian length
+    #[structopt(long, default_value="lines")]
+    candidates_format: CandidateFormat,
+
+    /// Instead of searching, print the constraints on our suffix for an
+    /// external solver, either dimacs or smtlib
+    #[structopt(long)]
+    export_constraints: Option<ConstraintFormat>,
+
+    /// Check a solver's assignment for our exported constraints, either a
+    /// DIMACS model or SMT-LIB values
+    #[structopt(long, parse(from_os_str))]
+    assignment: Option<PathBuf>,
+
+    /// Wrap the suffix in a comment so source files still compile, one of
+    /// //, #, or /*, or a language such as rust, python, or c. This limits
+    /// the suffix to printable characters
+    #[structopt(long)]
+    comment: Option<Comment>,
 }
 
-// entry point
-fn main() {
-    let opt = Opt::from_args();
+impl Opt {
+    // structopt loses track of how our prefix options are interleaved, so
+    // we need to ask clap what order they were given in
+    fn from_iter_ordered<I>(args: I) -> Result<Opt, structopt::clap::Error>
+    where
+        I: IntoIterator,
+        I::Item: Into<std::ffi::OsString> + Clone,
+    {
+        let matches = Opt::clap().get_matches_from_safe(args)?;
+        let mut opt = Opt::from_clap(&matches);
 
-    // create our CRC
-    let crc32 = Crc32::new(opt.polynomial);
-
-    // find the CRC of our prefix
-    let mut x = crc32.crc32(0, &opt.prefix.as_bytes());
-    // find CRC of just our implicit xor
-    let mut c = 0;
-    // + space for suffix
-    if opt.ascii {
-        x = crc32.crc32(x, &[0, 0, 0, 0, 0, 0, 0, 0]);
-        c = crc32.crc32(c, &[0, 0, 0, 0, 0, 0, 0, 0]);
-    } else {
-        x = crc32.crc32(x, &[0, 0, 0, 0]);
-        c = crc32.crc32(c, &[0, 0, 0, 0]);
-    }
-
-    // this xor is our target value
-    let target = x ^ opt.target ^ c;
-
-    if opt.ascii {
-        // brute force find a 64-bit suffix that makes our CRC work, skipping
-        // any non-ascii and non-control characters
-        //
-        // since DEL (0x7f) is a control character, and space (0x20) is sort of
-        // a control character, we limit our characters to H..=W (0x48..=0x57)
-        // and h..=w (0x68..=0x77). This gives us 5 bits per per character to
-        // work with.
-        for i in 0x00_0000_0000u64 ..= 0xff_ffff_ffffu64 {
-            // convert into a guaranteed ascii representation
-            // first get all bits into the right position
-            let i = ((i << 12) & 0x000f_ffff_0000_0000) | (i & 0x0000_0000_000f_ffff);
-            let i = ((i <<  6) & 0x03ff_0000_03ff_0000) | (i & 0x0000_03ff_0000_03ff);
-            let i = ((i <<  3) & 0x1f00_1f00_1f00_1f00) | (i & 0x001f_001f_001f_001f);
-            let i = ((i <<  1) & 0x2020_2020_2020_2020) | (i & 0x0f0f_0f0f_0f0f_0f0f);
-            // and then add to array of 0x48s
-            let i = i + 0x48_48_48_48_48_48_48_48;
-
-            if crc32.crc32(0, &i.to_le_bytes()) == target {
-                for b in
-                    opt.prefix.as_bytes().iter().copied()
-                        .chain(i.to_le_bytes())
-                {
-                    if b >= ' ' as u8 && b <= '~' as u8 {
-                        print!("{}", b as char);
-                    } else {
-                        print!("\\x{:02x}", b);
-                    }
-                }
-                println!();
+        let indices = |name| matches.indices_of(name).into_iter().flatten();
+        let mut prefixes = indices("prefix-text")
+            .zip(opt.prefix_text.iter().cloned().map(Prefix::Text))
+            .chain(indices("prefix-file")
+                .zip(opt.prefix_file.iter().cloned().map(Prefix::File)))
+            .chain(indices("prefix-hex")
+                .zip(opt.prefix_hex.iter().cloned().map(Prefix::Hex)))
+            .chain(indices("prefix-base64")
+                .zip(opt.prefix_base64.iter().cloned().map(Prefix::Base64)))
+            .collect::<Vec<_>>();
+        prefixes.sort_by_key(|(i, _)| *i);
+        opt.prefixes = prefixes.into_iter().map(|(_, p)| p).collect();
+        Ok(opt)
+    }
+}
 
-                // validate that the checksum matches
-                assert_eq!(
-                    crc32.crc32(crc32.crc32(0,
-                        opt.prefix.as_bytes()),
-                        &i.to_le_bytes()),
-                    opt.target
-                );
-                break;
+// analysis CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+enum Analyze {
+    /// Inject random bit errors into random messages, and count how many
+    /// go undetected
+    Simulate {
+        /// CRC polynomial, including its top bit, which sets the width of
+        /// our CRC, up to 32 bits
+        #[structopt(short, long, alias="poly",
+            default_value="0x11edc6f41",
+            parse(try_from_str=parse_u64)
+        )]
+        polynomial: u64,
+
+        /// Length of each message in bytes, not including the CRC
+        #[structopt(short, long)]
+        len: usize,
+
+        /// Maximum number of bit errors to inject into each message, each
+        /// weight up to this is simulated separately
+        #[structopt(short, long)]
+        errors: usize,
+
+        /// Number of messages to try at each weight
+        #[structopt(short, long, default_value="1000000")]
+        trials: u64,
+
+        /// Seed for our PRNG, defaults to something random
+        #[structopt(long, parse(try_from_str=parse_u64))]
+        seed: Option<u64>,
+    },
+
+    /// Draw the shift-register diagram of a polynomial, in both Galois
+    /// and Fibonacci forms
+    Draw {
+        /// CRC polynomial, including its top bit
+        #[structopt(parse(try_from_str=parse_u64))]
+        polynomial: u64,
+    },
+
+    /// Export analysis results for use in other tools
+    Export {
+        /// CRC polynomial, including its top bit
+        #[structopt(short, long, alias="poly",
+            default_value="0x11edc6f41",
+            parse(try_from_str=parse_u64)
+        )]
+        polynomial: u64,
+
+        /// What to export, one of matrix (the LFSR transition matrix),
+        /// weights (undetectable errors per weight), or hd (Hamming
+        /// distance breakpoints)
+        #[structopt(short, long, default_value="hd")]
+        what: String,
+
+        /// Format to export, one of csv, npy, or sage
+        #[structopt(short, long, default_value="csv")]
+        format: export::Format,
+
+        /// Length of messages in bytes, for hd this is the longest message
+        /// we consider
+        #[structopt(short, long, default_value="64")]
+        len: usize,
+
+        /// Largest error weight we consider, at most 4
+        #[structopt(long, default_value="4")]
+        max_weight: usize,
+
+        /// File to write to, defaults to stdout
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+}
+
+// merge CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+struct Merge {
+    /// Coverage files from each shard
+    #[structopt(required=true, parse(from_os_str))]
+    files: Vec<PathBuf>,
+
+    /// Write the merged coverage file here
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+fn merge_main(opt: Merge) {
+    let mut merged: Option<Coverage> = None;
+    for path in &opt.files {
+        let coverage = fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|s| Coverage::parse(&s));
+        let res = coverage.and_then(|coverage| match &mut merged {
+            Some(merged) => merged.merge(&coverage),
+            None => {
+                merged = Some(coverage);
+                Ok(())
             }
+        });
+        if let Err(err) = res {
+            eprintln!("error: {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+    }
+    let merged = merged.unwrap();
+
+    println!("covered {}/{} ({:.6}%)",
+        merged.count(), merged.total(),
+        100.0 * merged.count() as f64 / merged.total() as f64);
+    for (i, suffix) in &merged.solutions {
+        print!("solution 0x{:x} ", i);
+        print_escaped(suffix.iter().copied());
+    }
+    for r in merged.remaining() {
+        println!("remaining 0x{:x}..=0x{:x}", r.start(), r.end());
+    }
+
+    if let Some(path) = &opt.output {
+        if let Err(err) = fs::write(path, merged.to_string()) {
+            eprintln!("error: {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// CRC parameters for commands that work with a single CRC
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+struct CrcArgs {
+    /// CRC polynomial, including its top bit, which sets the width of our
+    /// CRC, defaults to CRC-32C
+    #[structopt(short, long, parse(try_from_str=parse_u64))]
+    polynomial: Option<u64>,
+
+    /// Width of the CRC in bits, 1 to 32, defaults to the degree of the
+    /// polynomial. With this the polynomial's top bit can be left out
+    #[structopt(short, long)]
+    width: Option<u32>,
+
+    /// Initial value of the CRC register, unreflected, defaults to all
+    /// ones
+    #[structopt(long, parse(try_from_str=parse_u32))]
+    init: Option<u32>,
+
+    /// Value xored into the final CRC, defaults to all ones
+    #[structopt(long, parse(try_from_str=parse_u32))]
+    xorout: Option<u32>,
+
+    /// Reflect each input byte, true or false, defaults to true
+    #[structopt(long, parse(try_from_str))]
+    refin: Option<bool>,
+
+    /// Reflect the final register, true or false, defaults to true
+    #[structopt(long, parse(try_from_str))]
+    refout: Option<bool>,
+
+    /// Named CRC algorithm, such as crc32c or crc16-ccitt-false, see
+    /// list-algorithms. Anything else we're given overrides its parameters
+    #[structopt(short, long, alias="preset")]
+    algorithm: Option<Algorithm>,
+}
+
+impl CrcArgs {
+    // build our CRC, a named algorithm fills in whatever parameters we
+    // weren't given
+    fn crc32(&self) -> Result<Crc32, String> {
+        let alg = self.algorithm;
+        if let Some(alg) = alg.filter(|alg| alg.width > 32) {
+            return Err(format!("{} is {} bits, try crcbrute crc64 -a {}",
+                alg.name, alg.width, alg.name));
+        }
+        let (polynomial, width) = match (self.polynomial, alg) {
+            (Some(p), _) => resolve_polynomial(p, self.width)?,
+            (None, Some(alg)) => resolve_polynomial(alg.poly,
+                Some(self.width.unwrap_or(alg.width)))?,
+            (None, None) => resolve_polynomial(0x11edc6f41, self.width)?,
+        };
+        let ones = (!0u64 >> (64 - width)) as u32;
+        let init = self.init.or(alg.map(|alg| alg.init as u32));
+        let xorout = self.xorout.or(alg.map(|alg| alg.xorout as u32));
+        if init.is_some_and(|init| init & !ones != 0) {
+            return Err(format!("init doesn't fit in {} bits", width));
+        }
+        if xorout.is_some_and(|xorout| xorout & !ones != 0) {
+            return Err(format!("xorout doesn't fit in {} bits", width));
+        }
+
+        let mut crc32 = Crc32::new(polynomial);
+        if let Some(init) = init {
+            crc32 = crc32.with_init(init);
+        }
+        if let Some(xorout) = xorout {
+            crc32 = crc32.with_xorout(xorout);
         }
+        Ok(crc32.with_reflect(
+            self.refin.or(alg.map(|alg| alg.refin)).unwrap_or(true),
+            self.refout.or(alg.map(|alg| alg.refout)).unwrap_or(true)))
+    }
+}
+
+// check CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+struct Check {
+    /// Message with an embedded CRC
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+
+    /// Byte offset of the CRC field, which takes up as many bytes as our
+    /// width needs, defaults to the end of the message
+    #[structopt(long, parse(try_from_str=parse_u64))]
+    crc_offset: Option<u64>,
+
+    /// Byte ranges covered by the CRC, lo..hi or lo..=hi, can be repeated
+    /// to skip fields. Defaults to everything before the CRC field
+    #[structopt(long, number_of_values=1, parse(try_from_str=parse_range))]
+    covered: Vec<RangeInclusive<u64>>,
+
+    /// Read more byte ranges covered by the CRC from a file, one per line
+    #[structopt(long, parse(from_os_str))]
+    covered_file: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    crc: CrcArgs,
+}
+
+fn check_main(opt: Check) {
+    fn die(err: impl std::fmt::Display) -> ! {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+
+    let data = fs::read(&opt.file)
+        .unwrap_or_else(|e| die(format!("{}: {}", opt.file.display(), e)));
+    let crc32 = opt.crc.crc32().unwrap_or_else(|e| die(e));
+
+    // narrower CRCs only take up as many bytes as they need
+    let n = crc32.width().div_ceil(8) as u64;
+    let len = data.len() as u64;
+    let off = match opt.crc_offset {
+        Some(off) => off,
+        None => len.saturating_sub(n),
+    };
+    if len < n || off > len - n {
+        die(format!("crc field at {} doesn't fit in {} bytes", off, len));
+    }
+    let mut ranges = opt.covered.clone();
+    if let Some(path) = &opt.covered_file {
+        ranges.extend(parse_ranges_file(path).unwrap_or_else(|e| die(e)));
+    }
+    if ranges.is_empty() && off > 0 {
+        ranges.push(0 ..= off-1);
+    }
+    let covered = covered(data.len(), &ranges)
+        .map(|covered| select(&data, &covered))
+        .unwrap_or_else(|e| die(e));
+
+    let expected = crc32.crc32(crc32.init(), &covered);
+    let field = &data[off as usize .. (off+n) as usize];
+    let (le, be) = (Endian::Little.load(field), Endian::Big.load(field));
+    let w = crc32.width().div_ceil(4) as usize;
+    if le == expected {
+        println!("pass, crc 0x{:0w$x}, little-endian", expected, w=w);
+    } else if be == expected {
+        println!("pass, crc 0x{:0w$x}, big-endian", expected, w=w);
     } else {
-        // brute force find a 32-bit suffix that makes our CRC work
-        for i in 0x0000_0000u32 ..= 0xffff_ffffu32 {
-            if crc32.crc32(0, &i.to_le_bytes()) == target {
-                for b in
-                    opt.prefix.as_bytes().iter().copied()
-                        .chain(i.to_le_bytes())
-                {
-                    if b >= ' ' as u8 && b <= '~' as u8 {
-                        print!("{}", b as char);
-                    } else {
-                        print!("\\x{:02x}", b);
-                    }
-                }
-                println!();
+        println!("fail, expected 0x{:0w$x}, found 0x{:0w$x} little-endian, \
+                0x{:0w$x} big-endian",
+            expected, le, be, w=w);
+        std::process::exit(1);
+    }
+}
 
-                // validate that the checksum matches
-                assert_eq!(
-                    crc32.crc32(crc32.crc32(0,
-                        opt.prefix.as_bytes()),
-                        &i.to_le_bytes()),
-                    opt.target
-                );
-                break;
-            }
+// how messages are given on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MessageFormat {
+    File,
+    Hex,
+    String,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<MessageFormat, String> {
+        match s {
+            "file" => Ok(MessageFormat::File),
+            "hex" => Ok(MessageFormat::Hex),
+            "string" => Ok(MessageFormat::String),
+            _ => Err(format!("unknown message format {:?}", s)),
         }
     }
 }
+
+// how we report progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProgressFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ProgressFormat, String> {
+        match s {
+            "human" => Ok(ProgressFormat::Human),
+            "json" => Ok(ProgressFormat::Json),
+            _ => Err(format!("unknown progress format {:?}", s)),
+        }
+    }
+}
+
+// how external candidates are framed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CandidateFormat {
+    Lines,
+    Length,
+}
+
+impl FromStr for CandidateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CandidateFormat, String> {
+        match s {
+            "lines" => Ok(CandidateFormat::Lines),
+            "length" => Ok(CandidateFormat::Length),
+            _ => Err(format!("unknown candidate format {:?}", s)),
+        }
+    }
+}
+
+// formats we can export constraints in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConstraintFormat {
+    Dimacs,
+    Smtlib,
+}
+
+impl FromStr for ConstraintFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ConstraintFormat, String> {
+        match s {
+            "dimacs" | "cnf" => Ok(ConstraintFormat::Dimacs),
+            "smtlib" | "smt2" => Ok(ConstraintFormat::Smtlib),
+            _ => Err(format!("unknown constraint format {:?}", s)),
+        }
+    }
+}
+
+// samples are message:crc, split on the last colon so paths can contain
+// colons
+fn parse_sample(s: &str, format: MessageFormat) -> Result<Sample, String> {
+    let (message, crc) = s.rsplit_once(':')
+        .ok_or_else(|| format!("{:?}: expected message:crc", s))?;
+    let crc = parse_u32(crc).map_err(|e| format!("{:?}: {}", s, e))?;
+    let message = match format {
+        MessageFormat::File => fs::read(message)
+            .map_err(|e| format!("{}: {}", message, e))?,
+        MessageFormat::Hex => coverage::unhex(message)?,
+        MessageFormat::String => message.as_bytes().to_vec(),
+    };
+    Ok(Sample{message, crc})
+}
+
+// recover CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+enum Recover {
+    /// Solve for an unknown init value from messages and their CRCs, given
+    /// a known --xorout
+    Init {
+        #[structopt(flatten)]
+        crc: CrcArgs,
+
+        /// How messages are given, one of file, hex, or string
+        #[structopt(short, long, default_value="file")]
+        format: MessageFormat,
+
+        /// Print at most this many solutions
+        #[structopt(long, default_value="16")]
+        limit: u64,
+
+        /// Messages and their observed CRCs, as message:crc
+        #[structopt(required=true)]
+        samples: Vec<String>,
+    },
+
+    /// Solve for an unknown xorout from messages and their CRCs, given a
+    /// known --init
+    Xorout {
+        #[structopt(flatten)]
+        crc: CrcArgs,
+
+        /// How messages are given, one of file, hex, or string
+        #[structopt(short, long, default_value="file")]
+        format: MessageFormat,
+
+        /// Messages and their observed CRCs, as message:crc
+        #[structopt(required=true)]
+        samples: Vec<String>,
+    },
+
+    /// Solve for both init and xorout from messages and their CRCs, this
+    /// needs messages of different lengths to tell the two apart
+    InitXorout {
+        #[structopt(flatten)]
+        crc: CrcArgs,
+
+        /// How messages are given, one of file, hex, or string
+        #[structopt(short, long, default_value="file")]
+        format: MessageFormat,
+
+        /// Print at most this many solutions
+        #[structopt(long, default_value="16")]
+        limit: u64,
+
+        /// Messages and their observed CRCs, as message:crc
+        #[structopt(required=true)]
+        samples: Vec<String>,
+    },
+}
+
+fn recover_main(opt: Recover) {
+    fn die(err: impl std::fmt::Display) -> ! {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+
+    // we can't be told what we're recovering
+    let crc32_for = |crc: &CrcArgs, init: bool, xorout: bool| {
+        if init && crc.init.is_some() {
+            die("init is what we're recovering, drop --init");
+        }
+        if xorout && crc.xorout.is_some() {
+            die("xorout is what we're recovering, drop --xorout");
+        }
+        crc.crc32().unwrap_or_else(|e| die(e))
+    };
+    let parse_samples = |samples: &[String], format| {
+        samples.iter()
+            .map(|s| parse_sample(s, format))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|err| die(err))
+    };
+    let unwrap = |what: &str, res: Result<recover::Solutions, recover::Error>| {
+        match res {
+            Ok(solutions) => solutions,
+            Err(recover::Error::NoSamples) => die("no samples"),
+            Err(recover::Error::Unsolvable(i)) => die(format!(
+                "no {} explains sample {}", what, i+1)),
+            Err(recover::Error::Inconsistent(i)) => die(format!(
+                "sample {} is inconsistent with the others", i+1)),
+        }
+    };
+    // list every consistent combination, or as many as we're allowed to
+    let report = |solutions: &recover::Solutions, limit: u64,
+            show: &dyn Fn(u32)| {
+        if solutions.free() > 0 {
+            eprintln!("{} bits undetermined, 2^{} solutions, \
+                    more samples may help",
+                solutions.free(), solutions.free());
+        }
+        for x in solutions.iter().take(limit as usize) {
+            show(x);
+        }
+        if solutions.free() >= 64 || (1u64 << solutions.free()) > limit {
+            eprintln!("...");
+        }
+    };
+
+    match opt {
+        Recover::Init{crc, format, limit, samples} => {
+            let crc32 = crc32_for(&crc, true, false);
+            let w = crc32.width().div_ceil(4) as usize;
+            let samples = parse_samples(&samples, format);
+            let solutions = unwrap("init",
+                recover::init(&crc32, crc32.xorout(), &samples));
+            report(&solutions, limit, &|init| {
+                println!("init 0x{:0w$x}", init, w=w);
+            });
+        }
+        Recover::Xorout{crc, format, samples} => {
+            // init is unreflected, but our CRC only gives us its
+            // finalized form, so take it from our arguments
+            let crc32 = crc32_for(&crc, false, true);
+            let w = crc32.width().div_ceil(4) as usize;
+            let init = crc.init
+                .or(crc.algorithm.map(|alg| alg.init as u32))
+                .unwrap_or(crc32.mask());
+            let samples = parse_samples(&samples, format);
+            let solutions = unwrap("xorout",
+                recover::xorout(&crc32, init, &samples));
+            println!("xorout 0x{:0w$x}", solutions.particular, w=w);
+        }
+        Recover::InitXorout{crc, format, limit, samples} => {
+            let crc32 = crc32_for(&crc, true, true);
+            let w = crc32.width().div_ceil(4) as usize;
+            let samples = parse_samples(&samples, format);
+            let solutions = unwrap("init/xorout",
+                recover::init_xorout(&crc32, &samples));
+            report(&solutions, limit, &|init| {
+                println!("init 0x{:0w$x} xorout 0x{:0w$x}",
+                    init, recover::xorout_for(&crc32, init, &samples[0]),
+                    w=w);
+            });
+        }
+    }
+}
+
+// frame CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+enum FrameCmd {
+    /// Check a message against the CRC stored in it
+    Verify {
+        /// Frame spec
+        #[structopt(parse(from_os_str))]
+        spec: PathBuf,
+
+        /// Message to check
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+
+    /// Store the correct CRC in a message, or in a message built from the
+    /// spec alone
+    Encode {
+        /// Frame spec
+        #[structopt(parse(from_os_str))]
+        spec: PathBuf,
+
+        /// Message to encode, defaults to building one from the spec
+        #[structopt(parse(from_os_str))]
+        file: Option<PathBuf>,
+
+        /// Write the raw frame here instead of printing it
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    /// Change the free fields of a message so it matches its stored CRC,
+    /// useful for patching a message without touching its CRC
+    Solve {
+        /// Frame spec
+        #[structopt(parse(from_os_str))]
+        spec: PathBuf,
+
+        /// Message to patch, defaults to building one from the spec
+        #[structopt(parse(from_os_str))]
+        file: Option<PathBuf>,
+
+        /// CRC we want, defaults to the CRC already stored
+        #[structopt(long, parse(try_from_str=parse_u32))]
+        target: Option<u32>,
+
+        /// Write the raw frame here instead of printing it
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+}
+
+fn frame_main(opt: FrameCmd) {
+    fn die(err: impl std::fmt::Display) -> ! {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+
+    let load = |spec: &PathBuf, file: Option<&PathBuf>| {
+        let frame = fs::read_to_string(spec)
+            .map_err(|e| e.to_string())
+            .and_then(|s| Frame::parse(&s))
+            .unwrap_or_else(|e| die(format!("{}: {}", spec.display(), e)));
+        let data = match file {
+            Some(file) => fs::read(file)
+                .unwrap_or_else(|e| die(format!("{}: {}", file.display(), e))),
+            None => frame.build().unwrap_or_else(|e| die(e)),
+        };
+        (frame, data)
+    };
+    let output = |data: &[u8], path: Option<PathBuf>| match path {
+        Some(path) => fs::write(&path, data)
+            .unwrap_or_else(|e| die(format!("{}: {}", path.display(), e))),
+        None => print_escaped(data.iter().copied()),
+    };
+
+    match opt {
+        FrameCmd::Verify{spec, file} => {
+            let (frame, data) = load(&spec, Some(&file));
+            let (expected, stored) = frame.verify(&data)
+                .unwrap_or_else(|e| die(e));
+            let w = frame.crc32().width().div_ceil(4) as usize;
+            if expected == stored {
+                println!("pass, crc 0x{:0w$x}", expected, w=w);
+            } else {
+                println!("fail, expected 0x{:0w$x}, found 0x{:0w$x}",
+                    expected, stored, w=w);
+                std::process::exit(1);
+            }
+        }
+        FrameCmd::Encode{spec, file, output: path} => {
+            let (frame, mut data) = load(&spec, file.as_ref());
+            frame.encode(&mut data).unwrap_or_else(|e| die(e));
+            output(&data, path);
+        }
+        FrameCmd::Solve{spec, file, target, output: path} => {
+            let (frame, mut data) = load(&spec, file.as_ref());
+            if let Some(target) = target {
+                if target & !frame.crc32().mask() != 0 {
+                    die(format!("target 0x{:x} doesn't fit in {} bits",
+                        target, frame.crc32().width()));
+                }
+                frame.store(&mut data, target).unwrap_or_else(|e| die(e));
+            }
+            if !frame.solve(&mut data).unwrap_or_else(|e| die(e)) {
+                eprintln!("no solution found");
+                std::process::exit(1);
+            }
+            output(&data, path);
+        }
+    }
+}
+
+// blocks CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+struct BlocksCmd {
+    /// What to do, one of verify (report bad blocks), fix (store correct
+    /// CRCs), or forge (change data so it matches the stored CRCs)
+    action: String,
+
+    /// Message made of blocks, each followed by its CRC
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+
+    /// Data bytes in each block, not including the CRC, the last block may
+    /// be shorter
+    #[structopt(short, long)]
+    block_size: usize,
+
+    /// Byte order of each CRC, little or big
+    #[structopt(long, default_value="little")]
+    crc_endian: Endian,
+
+    /// Number of bytes at the end of each block's data we're free to change
+    /// when forging, at most 16
+    #[structopt(long, default_value="4")]
+    free: usize,
+
+    /// Write the fixed or forged message here, defaults to overwriting the
+    /// input
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    crc: CrcArgs,
+}
+
+fn blocks_main(opt: BlocksCmd) {
+    fn die(err: impl std::fmt::Display) -> ! {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+
+    if opt.block_size == 0 {
+        die("block size must be at least 1");
+    }
+    let mut data = fs::read(&opt.file)
+        .unwrap_or_else(|e| die(format!("{}: {}", opt.file.display(), e)));
+    let crc32 = opt.crc.crc32().unwrap_or_else(|e| die(e));
+    let w = crc32.width().div_ceil(4) as usize;
+    let blocks = Blocks{size: opt.block_size, endian: opt.crc_endian};
+
+    let save = |data: &[u8]| {
+        let path = opt.output.as_ref().unwrap_or(&opt.file);
+        fs::write(path, data)
+            .unwrap_or_else(|e| die(format!("{}: {}", path.display(), e)));
+    };
+
+    match opt.action.as_str() {
+        "verify" => {
+            let split = blocks.split(&crc32, &data).unwrap_or_else(|e| die(e));
+            let bad = split.iter().filter(|b| !b.ok()).count();
+            for (i, block) in split.iter().enumerate().filter(|(_, b)| !b.ok()) {
+                println!("block {} at 0x{:x}, expected 0x{:0w$x}, \
+                        found 0x{:0w$x}",
+                    i, block.data.start, block.expected, block.stored, w=w);
+            }
+            println!("{}, {} of {} blocks bad",
+                if bad == 0 { "pass" } else { "fail" },
+                bad, split.len());
+            if bad > 0 {
+                std::process::exit(1);
+            }
+        }
+        "fix" => {
+            let fixed = blocks.fix(&crc32, &mut data)
+                .unwrap_or_else(|e| die(e));
+            save(&data);
+            println!("fixed {} blocks", fixed);
+        }
+        "forge" => {
+            let (forged, failed) = blocks.forge(&crc32, &mut data, opt.free)
+                .unwrap_or_else(|e| die(e));
+            save(&data);
+            println!("forged {} blocks", forged);
+            if !failed.is_empty() {
+                eprintln!("couldn't forge blocks {:?}, try more --free bytes",
+                    failed);
+                std::process::exit(1);
+            }
+        }
+        action => die(format!("unknown action {:?}", action)),
+    }
+}
+
+// inspect CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+struct Inspect {
+    /// Messages to inspect for forged suffixes
+    #[structopt(parse(from_os_str), required=true)]
+    files: Vec<PathBuf>,
+
+    #[structopt(flatten)]
+    crc: CrcArgs,
+}
+
+fn inspect_main(opt: Inspect) {
+    let crc32 = match opt.crc.crc32() {
+        Ok(crc32) => crc32,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let w = crc32.width().div_ceil(4) as usize;
+    let mut suspicious = false;
+    for path in &opt.files {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("error: {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        };
+
+        let findings = inspect::inspect(&crc32, &data);
+        for f in &findings {
+            print!("{}: 0x{:x}..0x{:x} {} ",
+                path.display(), f.region.start, f.region.end, f.class);
+            print_escaped(data[f.region.clone()].iter().copied());
+            println!("{}:   crc 0x{:0w$x}, {}",
+                path.display(), f.crc, f.reason, w=w);
+        }
+        if findings.is_empty() {
+            println!("{}: nothing suspicious", path.display());
+        }
+        suspicious |= !findings.is_empty();
+    }
+
+    if suspicious {
+        std::process::exit(1);
+    }
+}
+
+// wide CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+struct WideCmd {
+    /// Message to CRC, or to append a suffix to with --target
+    message: String,
+
+    /// How the message is given, one of string, hex, or file
+    #[structopt(long, default_value="string")]
+    format: MessageFormat,
+
+    /// Width of the CRC in bits, any width is allowed
+    #[structopt(short, long)]
+    width: usize,
+
+    /// CRC polynomial, its top bit is optional since we know our width
+    #[structopt(short, long)]
+    polynomial: String,
+
+    /// Initial register value
+    #[structopt(long, default_value="0")]
+    init: String,
+
+    /// Value xored into the final CRC
+    #[structopt(long, default_value="0")]
+    xorout: String,
+
+    /// Reflect each input byte
+    #[structopt(long)]
+    refin: bool,
+
+    /// Reflect the final register
+    #[structopt(long)]
+    refout: bool,
+
+    /// Instead of printing the CRC, append a suffix so the CRC matches
+    /// this value
+    #[structopt(short, long)]
+    target: Option<String>,
+
+    /// Number of suffix bytes to solve for, defaults to just enough to
+    /// cover the CRC, growing if our system is singular
+    #[structopt(long)]
+    free: Option<usize>,
+}
+
+fn wide_main(opt: WideCmd) {
+    fn die(err: impl std::fmt::Display) -> ! {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+
+    if opt.width == 0 {
+        die("width must be at least 1");
+    }
+    let message = match opt.format {
+        MessageFormat::File => fs::read(&opt.message)
+            .unwrap_or_else(|e| die(format!("{}: {}", opt.message, e))),
+        MessageFormat::Hex => coverage::unhex(&opt.message)
+            .unwrap_or_else(|e| die(e)),
+        MessageFormat::String => opt.message.as_bytes().to_vec(),
+    };
+    let parse = |s: &str| wide::parse(s, opt.width).unwrap_or_else(|e| die(e));
+    let crc = Wide::new(
+        opt.width,
+        wide::parse_polynomial(&opt.polynomial, opt.width)
+            .unwrap_or_else(|e| die(e)),
+        parse(&opt.init),
+        parse(&opt.xorout),
+        opt.refin,
+        opt.refout);
+
+    let target = match &opt.target {
+        Some(target) => parse(target),
+        None => {
+            println!("{}", wide::hex(&crc.crc(&message), opt.width));
+            return;
+        }
+    };
+
+    // solve for a suffix, if our system is singular, try again with a
+    // few more bytes
+    let min = opt.free.unwrap_or(opt.width.div_ceil(8));
+    let max = opt.free.unwrap_or(min + 8);
+    for n in min ..= max {
+        let mut padded = message.clone();
+        padded.resize(message.len() + n, 0);
+        let free = (message.len() .. padded.len()).collect::<Vec<_>>();
+        if let Some(forged) = crc.solve(&padded, &free, &target) {
+            print_escaped(forged.iter().copied());
+            return;
+        }
+    }
+    die("no solution found, try more --free bytes");
+}
+
+// crc64 CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+struct Crc64Cmd {
+    /// Message to CRC, or prefix to append a suffix to with --target
+    message: String,
+
+    /// How the message is given, one of string, hex, or file
+    #[structopt(long, default_value="string")]
+    format: MessageFormat,
+
+    /// Named algorithm, see list-algorithms, anything else we're given
+    /// overrides its parameters
+    #[structopt(short, long, default_value="CRC-64/XZ")]
+    algorithm: Algorithm,
+
+    /// CRC polynomial, including its top bit, which can be left out if
+    /// we're given --width
+    #[structopt(short, long, parse(try_from_str=parse_u128))]
+    polynomial: Option<u128>,
+
+    /// Width of the CRC in bits, 1 to 64
+    #[structopt(short, long)]
+    width: Option<u32>,
+
+    /// Initial value of the CRC register, unreflected
+    #[structopt(long, parse(try_from_str=parse_u64))]
+    init: Option<u64>,
+
+    /// Value xored into the final CRC
+    #[structopt(long, parse(try_from_str=parse_u64))]
+    xorout: Option<u64>,
+
+    /// Reflect each input byte, true or false
+    #[structopt(long, parse(try_from_str))]
+    refin: Option<bool>,
+
+    /// Reflect the final register, true or false
+    #[structopt(long, parse(try_from_str))]
+    refout: Option<bool>,
+
+    /// Instead of printing the CRC, append a suffix so the CRC matches
+    /// this value
+    #[structopt(short, long, parse(try_from_str=parse_u64))]
+    target: Option<u64>,
+
+    /// Only require the bits in this mask to match the target, brute force
+    /// is only practical if this leaves a few bits
+    #[structopt(long,
+        default_value="0xffffffffffffffff",
+        parse(try_from_str=parse_u64)
+    )]
+    target_mask: u64,
+
+    /// Limit the suffix to a specific charset, one of raw, ascii,
+    /// whitespace, alnum, latin1, dna, octal, hex, base64, or 7bit
+    #[structopt(long, default_value="raw")]
+    charset_preset: Charset,
+
+    /// Search strategy, one of auto, algebraic, or brute. Algebraic only
+    /// works for affine charsets
+    #[structopt(long, default_value="auto")]
+    strategy: Strategy,
+
+    /// Append a newline after the suffix
+    #[structopt(long)]
+    trailing_newline: bool,
+}
+
+fn crc64_main(opt: Crc64Cmd) {
+    fn die(err: impl std::fmt::Display) -> ! {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+
+    let message = match opt.format {
+        MessageFormat::File => fs::read(&opt.message)
+            .unwrap_or_else(|e| die(format!("{}: {}", opt.message, e))),
+        MessageFormat::Hex => coverage::unhex(&opt.message)
+            .unwrap_or_else(|e| die(e)),
+        MessageFormat::String => opt.message.as_bytes().to_vec(),
+    };
+
+    // anything we weren't given comes from our algorithm
+    let alg = opt.algorithm;
+    let (polynomial, width) = match opt.polynomial {
+        Some(p) => resolve_polynomial_upto(p, opt.width, 64),
+        None => resolve_polynomial_upto(alg.poly as u128,
+            Some(opt.width.unwrap_or(alg.width)), 64),
+    }.unwrap_or_else(|e| die(e));
+    let mask = !0u64 >> (64 - width);
+    let fit = |name: &str, x: u64| if x & !mask != 0 {
+        die(format!("{} 0x{:x} doesn't fit in {} bits", name, x, width));
+    };
+    let init = opt.init.unwrap_or(alg.init);
+    let xorout = opt.xorout.unwrap_or(alg.xorout);
+    fit("init", init);
+    fit("xorout", xorout);
+    let crc64 = Crc64::new(polynomial)
+        .with_init(init)
+        .with_xorout(xorout)
+        .with_reflect(
+            opt.refin.unwrap_or(alg.refin),
+            opt.refout.unwrap_or(alg.refout));
+
+    let target = match opt.target {
+        Some(target) => target,
+        None => {
+            println!("0x{:0w$x}", crc64.crc64(crc64.init(), &message),
+                w=width.div_ceil(4) as usize);
+            return;
+        }
+    };
+    fit("target", target);
+
+    let charset = opt.charset_preset;
+    let postfix = if opt.trailing_newline { &b"\n"[..] } else { &[] };
+    let suffix = match opt.strategy {
+        Strategy::Auto | Strategy::Algebraic if charset.is_affine() => {
+            solve64(&crc64, &message, charset, postfix,
+                target, opt.target_mask & mask)
+        }
+        Strategy::Algebraic => die(format!("charset {} isn't affine",
+            charset)),
+        Strategy::Auto | Strategy::Brute => {
+            progress::install();
+            brute64(&crc64, &message, charset, postfix,
+                target, opt.target_mask & mask)
+        }
+        Strategy::Sat => die("crc64 doesn't support sat searches"),
+        Strategy::Mitm => die("crc64 doesn't support mitm searches"),
+    };
+    match suffix {
+        Some(suffix) => print_escaped(message.iter()
+            .chain(&suffix)
+            .chain(postfix)
+            .copied()),
+        None => die("no solution found"),
+    }
+}
+
+// solve for a suffix that makes the masked bits of a 64-bit CRC match
+fn solve64(
+    crc64: &Crc64,
+    prefix: &[u8],
+    charset: Charset,
+    postfix: &[u8],
+    target: u64,
+    mask: u64,
+) -> Option<Vec<u8>> {
+    solve_chars(charset, mask.count_ones() as usize,
+        |suffix| crc64.crc64(
+            crc64.crc64(crc64.crc64(crc64.init(), prefix), suffix),
+            postfix) & mask,
+        target & mask)
+}
+
+// solve for a suffix in an affine charset, where crc gives the up to 64
+// bits we constrain, like solve, but our system can need more bits than
+// fit in a counter, so we pack each character on its own
+fn solve_chars(
+    charset: Charset,
+    rank: usize,
+    crc: impl Fn(&[u8]) -> u64,
+    target: u64,
+) -> Option<Vec<u8>> {
+    // if our system is singular, try again with more characters
+    let bits = charset.symbols().trailing_zeros() as usize;
+    for len in rank.div_ceil(bits) ..= 128/bits {
+        let mut suffix = vec![0; len];
+        for c in suffix.chunks_mut(1) {
+            charset.pack(0, c);
+        }
+        let base = crc(&suffix);
+        let cols = (0..len*bits)
+            .map(|j| {
+                let mut suffix = suffix.clone();
+                charset.pack(1 << (j%bits), &mut suffix[j/bits..j/bits+1]);
+                crc(&suffix) ^ base
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(x) = gf2::solve(&cols, target ^ base) {
+            for (i, c) in suffix.chunks_mut(1).enumerate() {
+                charset.pack((x >> (i*bits)) as u64 & ((1 << bits) - 1), c);
+            }
+            return Some(suffix);
+        }
+    }
+
+    None
+}
+
+// brute force a suffix for a 64-bit CRC, this is only practical if our
+// mask leaves a few bits to match
+fn brute64(
+    crc64: &Crc64,
+    prefix: &[u8],
+    charset: Charset,
+    postfix: &[u8],
+    target: u64,
+    mask: u64,
+) -> Option<Vec<u8>> {
+    let len = charset.suffix_len(mask.count_ones());
+    let start = crc64.crc64(crc64.init(), prefix);
+    let progress = Progress::new(charset.space(len));
+    let mut suffix = vec![0; len];
+    let mut candidates = CharsetIter::new(charset, len);
+    while let Some(i) = candidates.next_into(&mut suffix) {
+        if i & 0xffff == 0 && progress::requested() {
+            progress.report(i, &format!("0x{:x}", i));
+        }
+
+        let crc = crc64.crc64(crc64.crc64(start, &suffix), postfix);
+        if (crc ^ target) & mask == 0 {
+            return Some(suffix);
+        }
+    }
+
+    None
+}
+
+// job queue CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+enum Jobs {
+    /// Run a daemon that queues and runs searches in the background,
+    /// run this under nohup or setsid to survive logging out
+    Daemon {
+        /// Socket to listen on
+        #[structopt(long, parse(from_os_str))]
+        socket: Option<PathBuf>,
+
+        /// Number of searches to run at once
+        #[structopt(short, long, default_value="1")]
+        jobs: usize,
+    },
+
+    /// Queue a search, takes the same arguments as crcbrute, and prints
+    /// the job id
+    #[structopt(setting=structopt::clap::AppSettings::TrailingVarArg)]
+    Submit {
+        /// Socket the daemon is listening on
+        #[structopt(long, parse(from_os_str))]
+        socket: Option<PathBuf>,
+
+        /// Arguments to crcbrute
+        #[structopt(required=true, allow_hyphen_values=true)]
+        args: Vec<String>,
+    },
+
+    /// Show queued, running, and finished searches
+    Status {
+        /// Socket the daemon is listening on
+        #[structopt(long, parse(from_os_str))]
+        socket: Option<PathBuf>,
+    },
+
+    /// Print the output of a finished search
+    Result {
+        /// Socket the daemon is listening on
+        #[structopt(long, parse(from_os_str))]
+        socket: Option<PathBuf>,
+
+        /// Job id
+        id: u64,
+    },
+
+    /// Cancel a queued or running search
+    Cancel {
+        /// Socket the daemon is listening on
+        #[structopt(long, parse(from_os_str))]
+        socket: Option<PathBuf>,
+
+        /// Job id
+        id: u64,
+    },
+}
+
+// check a search someone wants to run through our daemon, the daemon runs
+// searches as whoever started it, so only searches, and nothing that reads
+// or writes files on another user's behalf
+#[cfg(unix)]
+fn check_job(args: &[String]) -> Result<(), String> {
+    if let Some(cmd) = args.first()
+            .filter(|cmd| SUBCOMMANDS.contains(&cmd.as_str())) {
+        return Err(format!("can't submit {:?}", cmd));
+    }
+
+    let opt = Opt::from_iter_ordered(
+            std::iter::once("crcbrute").chain(args.iter().map(|a| a.as_str())))
+        .map_err(|err| err.message.lines().next().unwrap_or("")
+            .trim_start_matches("error: ").to_string())?;
+    let files = [
+        ("--prefix-file", !opt.prefix_file.is_empty()),
+        ("--targets-file", opt.targets_file.is_some()),
+        ("--target-from-file", !opt.target_from_file.is_empty()),
+        ("--profile", opt.profile.is_some()),
+        ("--profiles-file", opt.profiles_file.is_some()),
+        ("--mitm-table", opt.mitm_table.is_some()),
+        ("--found-file", opt.found_file.is_some()),
+        ("--include-file", opt.include_file.is_some()),
+        ("--exclude-coverage", !opt.exclude_coverage.is_empty()),
+        ("--coverage", opt.coverage.is_some()),
+        ("--checkpoint", opt.checkpoint.is_some()),
+        ("--resume", opt.resume.is_some()),
+        ("--candidates", opt.candidates.is_some()),
+        ("--assignment", opt.assignment.is_some()),
+    ];
+    match files.iter().find(|(_, used)| *used) {
+        Some((name, _)) => Err(format!("can't submit searches with {}", name)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn jobs_main(opt: Jobs) {
+    use std::io::Write;
+
+    let (socket, words) = match opt {
+        Jobs::Daemon{socket, jobs} => {
+            let socket = socket.unwrap_or_else(daemon::default_socket);
+            let res = std::env::current_exe()
+                .and_then(|exe| daemon::serve(&socket, exe, jobs, check_job));
+            if let Err(err) = res {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Jobs::Submit{socket, args} => {
+            // the daemon checks this too, but we can give a better error
+            if let Err(err) = check_job(&args) {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
+            (socket, [vec!["submit".into()], args].concat())
+        }
+        Jobs::Status{socket} => (socket, vec!["status".into()]),
+        Jobs::Result{socket, id} => (socket,
+            vec!["result".into(), id.to_string()]),
+        Jobs::Cancel{socket, id} => (socket,
+            vec!["cancel".into(), id.to_string()]),
+    };
+
+    let socket = socket.unwrap_or_else(daemon::default_socket);
+    match daemon::request(&socket, &words) {
+        Ok((code, stdout, stderr)) => {
+            let _ = std::io::stdout().write_all(&stdout);
+            let _ = std::io::stderr().write_all(&stderr);
+            std::process::exit(code);
+        }
+        Err(err) => {
+            eprintln!("error: {}: {}", socket.display(), err);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn jobs_main(_: Jobs) {
+    eprintln!("error: the job queue needs unix sockets");
+    std::process::exit(1);
+}
+
+// batch CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+struct Batch {
+    /// Job file to run, CSV rows of prefix, targets, and options, or JSON
+    /// lines with prefix, target, and options fields, - for stdin. Prints
+    /// one line per job, labeled with its line in the job file
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+
+    /// Number of jobs to run at once, defaults to our number of threads
+    #[structopt(short, long)]
+    jobs: Option<usize>,
+
+    /// Threads shared by every running job, defaults to the number of
+    /// physical cores. Each job gets an equal share, unless it asks for
+    /// its own --threads
+    #[structopt(long)]
+    threads: Option<usize>,
+}
+
+fn batch_main(opt: Batch) {
+    let res = if opt.file == Path::new("-") {
+        let mut s = String::new();
+        io::stdin().read_to_string(&mut s).map(|_| s)
+    } else {
+        fs::read_to_string(&opt.file)
+    };
+    let jobs = match res.map_err(|e| e.to_string())
+            .and_then(|s| batch::parse(&s)) {
+        Ok(jobs) => jobs,
+        Err(err) => {
+            eprintln!("error: {}: {}", opt.file.display(), err);
+            std::process::exit(1);
+        }
+    };
+
+    // parse every job before running any, so a typo doesn't stop us
+    // halfway through
+    let mut opts = vec![];
+    for job in &jobs {
+        let res = Opt::from_iter_ordered(
+            std::iter::once("crcbrute".into()).chain(job.args()));
+        let mut opt_ = match res {
+            Ok(opt_) => opt_,
+            Err(err) => {
+                eprintln!("error: {}: line {}: {}",
+                    opt.file.display(), job.line,
+                    err.message.lines().next().unwrap_or("")
+                        .trim_start_matches("error: "));
+                std::process::exit(1);
+            }
+        };
+
+        // anything that outlives a search, or is shared by our whole
+        // process, would leak into other jobs
+        if opt_.max_time.is_some()
+                || opt_.checkpoint.is_some()
+                || opt_.resume.is_some()
+                || opt_.found_file.is_some()
+                || opt_.progress_interval.is_some() {
+            eprintln!("error: {}: line {}: --max-time, --checkpoint, \
+                    --resume, --found-file, and --progress-interval can't \
+                    be used in batches",
+                opt.file.display(), job.line);
+            std::process::exit(1);
+        }
+
+        // we report progress for the whole batch instead
+        opt_.quiet = true;
+        opts.push(opt_);
+    }
+
+    // workers claim jobs in order, each job gets an equal share of our
+    // threads, and as our batch winds down, later jobs get more of them
+    let threads = opt.threads.unwrap_or_else(physical_cores).max(1);
+    let workers = opt.jobs.unwrap_or(threads).clamp(1, jobs.len().max(1));
+    let queue = Mutex::new(jobs.iter().zip(opts));
+    let started = AtomicUsize::new(0);
+    let finished = AtomicUsize::new(0);
+    let missing = AtomicBool::new(false);
+    let failed = AtomicBool::new(false);
+    let work = || loop {
+        if progress::interrupted() {
+            break;
+        }
+        let (job, mut opt_) = match queue.lock().unwrap().next() {
+            Some(next) => next,
+            None => break,
+        };
+        started.fetch_add(1, Ordering::Relaxed);
+        let left = jobs.len() - finished.load(Ordering::Relaxed);
+        if opt_.threads.is_none() {
+            opt_.threads = Some((threads / workers.min(left)).max(1));
+        }
+
+        let label = format!("{}: ", job.line);
+        // an error only fails this job, the rest of our batch may be fine
+        match search_main(opt_, &label) {
+            Ok(true) => {}
+            Ok(false) => {
+                println!("{}no solution found", label);
+                missing.store(true, Ordering::Relaxed);
+            }
+            Err(err) => {
+                eprintln!("error: {}: line {}: {}",
+                    opt.file.display(), job.line, err);
+                failed.store(true, Ordering::Relaxed);
+            }
+        }
+        finished.fetch_add(1, Ordering::Relaxed);
+    };
+
+    let start = Instant::now();
+    let report = io::stderr().is_terminal();
+    thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|_| scope.spawn(work))
+            .collect::<Vec<_>>();
+
+        // report progress for the whole batch, individual searches are
+        // too short-lived to be interesting
+        let mut last = Instant::now();
+        while !handles.iter().all(|h| h.is_finished()) {
+            thread::sleep(Duration::from_millis(100));
+            if report && last.elapsed() >= Duration::from_secs(5) {
+                last = Instant::now();
+                let finished = finished.load(Ordering::Relaxed);
+                eprintln!("batch: {}/{} jobs done, {} running, elapsed {}",
+                    finished, jobs.len(),
+                    started.load(Ordering::Relaxed) - finished,
+                    progress::format_duration(start.elapsed()));
+            }
+        }
+    });
+
+    let failed = failed.into_inner();
+    let missing = missing.into_inner();
+    if failed {
+        std::process::exit(1);
+    } else if missing {
+        std::process::exit(progress::EXIT_NOT_FOUND);
+    }
+}
+
+// cluster CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+enum Cluster {
+    /// Split a search into shards and hand them out to workers, takes the
+    /// same arguments as crcbrute
+    #[structopt(setting=structopt::clap::AppSettings::TrailingVarArg)]
+    Coordinate {
+        /// Address to listen on, defaults to 127.0.0.1:7453, workers on
+        /// other machines need something like --listen=0.0.0.0:7453
+        #[structopt(long)]
+        listen: Option<String>,
+
+        /// Only accept workers with this token, you probably want this
+        /// when listening beyond localhost
+        #[structopt(long)]
+        token: Option<String>,
+
+        /// Number of shards to split our search into
+        #[structopt(long, default_value="4096")]
+        shards: u64,
+
+        /// Arguments to crcbrute
+        #[structopt(required=true, allow_hyphen_values=true)]
+        args: Vec<String>,
+    },
+
+    /// Search shards handed out by a coordinator, this runs whatever
+    /// search the coordinator asks for, so only use coordinators you trust
+    Worker {
+        /// Coordinator to connect to, host or host:port
+        coordinator: String,
+
+        /// Token our coordinator expects
+        #[structopt(long)]
+        token: Option<String>,
+    },
+}
+
+fn cluster_main(opt: Cluster) {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    match opt {
+        Cluster::Coordinate{listen, token, shards, args} => {
+            if SUBCOMMANDS.contains(&args[0].as_str()) {
+                eprintln!("error: can't coordinate {:?}", args[0]);
+                std::process::exit(1);
+            }
+            if shards == 0 {
+                eprintln!("error: need at least one shard");
+                std::process::exit(1);
+            }
+
+            // catch bad arguments here, before every worker trips over
+            // them
+            let res = std::process::Command::new(&exe)
+                .arg("--shard=0/1")
+                .arg("--dry-run")
+                .args(&args)
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::piped())
+                .output();
+            let out = match res {
+                Ok(out) if out.status.success() => out,
+                Ok(out) => {
+                    let _ = io::Write::write_all(&mut io::stderr(),
+                        &out.stderr);
+                    std::process::exit(1);
+                }
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    std::process::exit(1);
+                }
+            };
+
+            // the dry run also tells us how big our search is, more shards
+            // than candidates would just leave shards with nothing to do
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let size = stderr.lines()
+                .find_map(|line| line.strip_prefix("dry run: range "))
+                .and_then(|range| range.split_once("..="))
+                .and_then(|(lo, hi)| Some(
+                    parse_u64(hi).ok()? as u128 - parse_u64(lo).ok()? as u128
+                        + 1));
+            let shards = match size {
+                Some(size) => shards.min(size.min(u64::MAX as u128) as u64),
+                None if stderr.lines()
+                    .any(|line| line == "dry run: range is empty") => 1,
+                None => shards,
+            };
+
+            let listen = listen.unwrap_or_else(|| format!("127.0.0.1:{}",
+                cluster::DEFAULT_PORT));
+            match cluster::coordinate(&listen, token, args, shards) {
+                Ok(Outcome::Found(found)) => print!("{}", found),
+                Ok(Outcome::Exhausted) => {
+                    eprintln!("no solution found");
+                    std::process::exit(progress::EXIT_NOT_FOUND);
+                }
+                Ok(Outcome::Failed{shard, error}) => {
+                    eprintln!("error: shard {}/{} failed: {}",
+                        shard, shards, error);
+                    std::process::exit(1);
+                }
+                Err(err) => {
+                    eprintln!("error: {}: {}", listen, err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Cluster::Worker{coordinator, token} => {
+            let addr = if coordinator.contains(':') {
+                coordinator
+            } else {
+                format!("{}:{}", coordinator, cluster::DEFAULT_PORT)
+            };
+            if let Err(err) = cluster::work(&addr, token, exe) {
+                eprintln!("error: {}: {}", addr, err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn analyze_main(opt: Analyze) {
+    match opt {
+        Analyze::Simulate{polynomial, len, errors, trials, seed} => {
+            if errors == 0 {
+                eprintln!("error: need at least one error to inject");
+                std::process::exit(1);
+            }
+            let crc32 = match resolve_polynomial(polynomial, None) {
+                Ok((polynomial, _)) => Crc32::new(polynomial),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    std::process::exit(1);
+                }
+            };
+            let bits = 8*len + crc32.width() as usize;
+            if errors > bits {
+                eprintln!("error: more errors than bits in a {}-byte message",
+                    len);
+                std::process::exit(1);
+            }
+
+            let seed = seed.unwrap_or_else(Rng::seed);
+            let mut rng = Rng::new(seed);
+
+            println!("seed 0x{:016x}", seed);
+            let mut lightest = None;
+            for weight in 1..=errors {
+                let sim = analyze::simulate(
+                    &crc32, len, weight, trials, &mut rng);
+                println!("weight {}: trials {}, undetected {} ({:.3e})",
+                    weight,
+                    sim.trials,
+                    sim.undetected,
+                    sim.undetected as f64 / sim.trials as f64);
+                if sim.undetected > 0 {
+                    lightest.get_or_insert(weight);
+                }
+            }
+            // errors beyond our Hamming distance should go undetected at
+            // about the same rate as a random check of our width
+            println!("expected 0 below the Hamming distance, ~{:.3e} above",
+                1.0 / (1u64 << crc32.width()) as f64);
+
+            // compare against the Hamming distance we can prove, counting
+            // undetectable patterns is only practical up to weight 4
+            let max_weight = errors.min(4);
+            let hd = analyze::hamming_distance(
+                polynomial, bits, max_weight);
+            match hd {
+                Some(hd) => println!("hamming distance {} at {} bytes",
+                    hd, len),
+                None => println!("hamming distance > {} at {} bytes",
+                    max_weight, len),
+            }
+            match lightest {
+                Some(weight) => println!("lightest undetected error {}",
+                    weight),
+                None => println!("lightest undetected error none"),
+            }
+            if let Some(weight) = lightest {
+                if hd.is_none_or(|hd| weight < hd) && weight <= max_weight {
+                    eprintln!("error: undetected weight-{} error below our \
+                        Hamming distance", weight);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Analyze::Draw{polynomial} => {
+            if polynomial < 2 {
+                eprintln!("error: polynomial must have degree >= 1");
+                std::process::exit(1);
+            }
+
+            println!("{}", analyze::poly_string(polynomial));
+            println!();
+            println!("Galois form:");
+            print!("{}", analyze::draw_galois(polynomial));
+            println!();
+            println!("Fibonacci form:");
+            print!("{}", analyze::draw_fibonacci(polynomial));
+        }
+
+        Analyze::Export{
+            polynomial, what, format, len, max_weight, output
+        } => {
+            if max_weight > 4 {
+                eprintln!("error: --max-weight is limited to 4");
+                std::process::exit(1);
+            }
+            if polynomial < 2 {
+                eprintln!("error: polynomial must have degree >= 1");
+                std::process::exit(1);
+            }
+
+            let width = analyze::degree(polynomial) as usize;
+            let table = match what.as_str() {
+                "matrix" => Table{
+                    name: "M".into(),
+                    columns: (0..width).map(|j| format!("x{}", j)).collect(),
+                    rows: analyze::transition_matrix(polynomial).iter()
+                        .map(|row| (0..width).map(|j| (row >> j) & 1).collect())
+                        .collect(),
+                    gf2: true,
+                },
+                "weights" => Table{
+                    name: "W".into(),
+                    columns: vec!["weight".into(), "undetected".into()],
+                    rows: analyze::weights(polynomial, 8*len+width, max_weight)
+                        .iter().enumerate()
+                        .skip(1)
+                        .map(|(w, c)| vec![w as u64, *c])
+                        .collect(),
+                    gf2: false,
+                },
+                "hd" => Table{
+                    name: "HD".into(),
+                    columns: vec!["hd".into(), "max_data_bits".into()],
+                    rows: analyze::hd_table(polynomial, max_weight, 8*len)
+                        .iter()
+                        .map(|&(hd, bits)| vec![hd as u64, bits as u64])
+                        .collect(),
+                    gf2: false,
+                },
+                _ => {
+                    eprintln!("error: unknown export {:?}", what);
+                    std::process::exit(1);
+                }
+            };
+
+            let res = match output {
+                Some(path) => fs::File::create(&path)
+                    .and_then(|f| {
+                        let mut f = std::io::BufWriter::new(f);
+                        table.write(&mut f, format)
+                    }),
+                None => table.write(&mut std::io::stdout().lock(), format),
+            };
+            if let Err(err) = res {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+// split a range of CRCs into aligned blocks, each a value and mask, this
+// lets our solvers handle ranges, since each block only constrains some
+// bits
+fn range_blocks(range: &RangeInclusive<u64>) -> Vec<(u32, u32)> {
+    let mut blocks = vec![];
+    let (mut lo, hi) = (*range.start(), *range.end());
+    while lo <= hi {
+        // largest aligned block that fits
+        let mut k = lo.trailing_zeros().min(32);
+        while lo + (1 << k) - 1 > hi {
+            k -= 1;
+        }
+        blocks.push((lo as u32, !((1u64 << k) - 1) as u32));
+        lo += 1 << k;
+    }
+    blocks
+}
+
+// how many numbers in a range have each digit count, either fixed with
+// leading zeros, or natural
+fn digit_counts(range: &RangeInclusive<u64>, digits: Option<usize>)
+        -> Vec<(usize, u64)> {
+    (1 ..= 20)
+        .filter_map(|d| {
+            let (lo, hi) = match digits {
+                Some(digits) if digits != d => return None,
+                Some(_) => (0, u64::MAX),
+                None => (
+                    if d == 1 { 0 } else { 10u64.pow(d as u32 - 1) },
+                    10u64.checked_pow(d as u32).map_or(u64::MAX, |x| x-1),
+                ),
+            };
+            let lo = lo.max(*range.start());
+            let hi = hi.min(*range.end());
+            (lo <= hi).then(|| (d, (hi - lo).saturating_add(1)))
+        })
+        .collect()
+}
+
+// estimate how many CRCs of this width satisfy our predicates by sampling,
+// exact counting would need up to 2^32 tests
+fn predicate_count(predicates: &[Predicate], width: u32) -> f64 {
+    if predicates.is_empty() {
+        return 0.0;
+    }
+    const SAMPLES: u32 = 1 << 20;
+    let mask = (!0u64 >> (64 - width)) as u32;
+    let mut rng = Rng::new(0);
+    let hits = (0..SAMPLES)
+        .filter(|_| {
+            let crc = rng.next_u64() as u32 & mask;
+            predicates.iter().all(|p| p.test(crc, width))
+        })
+        .count();
+    (hits as f64 / SAMPLES as f64) * 2f64.powi(width as i32)
+}
+
+// find how our prefix offsets the CRC of our suffix
+//
+// since CRCs are affine, crc(prefix||suffix) = crc(prefix||zeros)
+// ^ crc(suffix) ^ crc(zeros), so we only need the CRC of our suffix to
+// know the CRC of the whole message
+fn suffix_offset(
+    crc32: &Crc32,
+    prefix: &[u8],
+    len: usize,
+    postfix: &[u8],
+) -> u32 {
+    let zeros = vec![0; len];
+    let x = crc32.crc32(
+        crc32.crc32(crc32.crc32(crc32.init(), prefix), &zeros),
+        postfix);
+    let c = crc32.crc32(crc32.crc32(0, &zeros), postfix);
+    x ^ c
+}
+
+// search lo..=hi in blocks, returning the earliest index and suffix
+// search_block finds in each block
+//
+// Threads claim blocks in order as they go, so a thread stuck on slow
+// blocks doesn't leave the others idle, and stop once they pass the
+// earliest solution found so far. Every block before that has already
+// been claimed and checked, so we always find the earliest solution, no
+// matter how many threads we use.
+fn search_blocks(
+    lo: u64,
+    hi: u64,
+    threads: usize,
+    checkpoint: &(dyn Fn(u64) + Sync),
+    search_block: impl Fn(u64, u64) -> Option<(u64, Vec<u8>)> + Sync,
+) -> Option<(u64, Vec<u8>)> {
+    const BLOCK: u64 = 0x10000;
+    let progress = Progress::new((hi - lo).saturating_add(1));
+    let next = AtomicU64::new(lo);
+    let earliest = AtomicU64::new(u64::MAX);
+    // the block each thread is working on, everything below all of these
+    // has been checked, which is what we checkpoint
+    let working = (0..threads.max(1))
+        .map(|_| AtomicU64::new(u64::MAX))
+        .collect::<Vec<_>>();
+    let search = |t: usize| -> Option<(u64, Vec<u8>)> {
+        loop {
+            // note we mark our next block before we claim it, so no one
+            // thinks it's been checked in between
+            working[t].store(next.load(Ordering::Relaxed), Ordering::Relaxed);
+            let block = next.fetch_update(
+                Ordering::Relaxed, Ordering::Relaxed,
+                |block| (block <= hi).then(|| block.saturating_add(BLOCK)))
+                .ok()?;
+            working[t].store(block, Ordering::Relaxed);
+            if progress::checkpoint_due() {
+                checkpoint(working.iter()
+                    .map(|w| w.load(Ordering::Relaxed))
+                    .fold(next.load(Ordering::Relaxed), u64::min)
+                    .min(hi.saturating_add(1)));
+            }
+
+            if block >= earliest.load(Ordering::Relaxed)
+                    || progress::stopped() {
+                return None;
+            }
+            if progress::requested() {
+                progress.report(block - lo, &format!("0x{:x}", block));
+            }
+
+            let end = block.saturating_add(BLOCK-1).min(hi);
+            if let Some((i, suffix)) = search_block(block, end) {
+                earliest.fetch_min(i, Ordering::Relaxed);
+                return Some((i, suffix));
+            }
+        }
+    };
+
+    let found = if threads <= 1 {
+        search(0)
+    } else {
+        let search = &search;
+        thread::scope(|scope| {
+            let handles = (0..threads)
+                .map(|t| scope.spawn(move || search(t)))
+                .collect::<Vec<_>>();
+            handles.into_iter()
+                .filter_map(|h| h.join().unwrap())
+                .min_by_key(|(i, _)| *i)
+        })
+    };
+
+    // if we were stopped, checkpoint exactly where we got to
+    if found.is_none() && progress::stopped() {
+        checkpoint(working.iter()
+            .map(|w| w.load(Ordering::Relaxed))
+            .fold(next.load(Ordering::Relaxed), u64::min)
+            .min(hi.saturating_add(1)));
+    }
+    found
+}
+
+// a random order for our candidates, index i becomes (a*i + b) mod space,
+// which visits every index as long as a is coprime to our space
+#[derive(Debug, Clone, Copy)]
+struct Stride {
+    a: u64,
+    b: u64,
+    space: u64,
+}
+
+impl Stride {
+    fn random(space: u64, rng: &mut Rng) -> Stride {
+        let gcd = |mut x: u64, mut y: u64| {
+            while y != 0 {
+                (x, y) = (y, x % y);
+            }
+            x
+        };
+        let a = loop {
+            let a = rng.below(space);
+            if gcd(a, space) == 1 {
+                break a;
+            }
+        };
+        Stride{a, b: rng.below(space), space}
+    }
+
+    fn at(&self, i: u64) -> u64 {
+        ((self.a as u128 * i as u128 + self.b as u128)
+            % self.space as u128) as u64
+    }
+
+    // the index after j, this avoids a division in our hot loop
+    fn next(&self, j: u64) -> u64 {
+        if j < self.space - self.a {
+            j + self.a
+        } else {
+            j - (self.space - self.a)
+        }
+    }
+}
+
+// brute force find a suffix whose CRC we accept, only trying candidates
+// in range, returns the index and suffix of our solution
+//
+// with a seed, we search our indices in a random order, see Stride.
+// Affine charsets need to step through their CRCs one bit flip at a time,
+// so for them we use a random invertible matrix over GF(2) instead, which
+// still gives us fixed deltas
+#[allow(clippy::too_many_arguments)]
+fn brute(
+    crc32: &Crc32,
+    prefix: &[u8],
+    charset: Charset,
+    postfix: &[u8],
+    range: RangeInclusive<u64>,
+    threads: usize,
+    seed: Option<u64>,
+    checkpoint: &(dyn Fn(u64) + Sync),
+    accept: impl Fn(u32) -> bool + Sync,
+) -> Option<(u64, Vec<u8>)> {
+    // monomorphize over our suffix length and packer, this lets the
+    // compiler unroll our CRC and specialize our packer, which matters a
+    // lot in this loop
+    #[allow(clippy::too_many_arguments)]
+    fn brute_<const N: usize>(
+        crc32: &Crc32,
+        charset: Charset,
+        pack: impl Fn(u64, &mut [u8]) + Sync,
+        postfix: &[u8],
+        offset: u32,
+        stride: Stride,
+        range: RangeInclusive<u64>,
+        threads: usize,
+        checkpoint: &(dyn Fn(u64) + Sync),
+        accept: impl Fn(u32) -> bool + Sync,
+    ) -> Option<(u64, Vec<u8>)> {
+        let lo = *range.start();
+        let hi = (*range.end()).min(charset.space(N).checked_sub(1)?);
+        if lo > hi {
+            return None;
+        }
+
+        search_blocks(lo, hi, threads, checkpoint, |block, end| {
+            // check candidates four at a time, this lets us use full
+            // vector lanes where available
+            let mut suffixes = [[0; N]; 4];
+            let mut j = stride.at(block);
+            for i in (block ..= end).step_by(4) {
+                // the last batch may only be a partial batch, but
+                // checking a candidate twice is harmless
+                let is = [0, 1, 2, 3].map(|k| (i+k).min(end));
+                for (k, suffix) in suffixes.iter_mut().enumerate() {
+                    pack(j, suffix);
+                    if i+(k as u64) < end {
+                        j = stride.next(j);
+                    }
+                }
+                let crcs = crc32.crc32x4(
+                    crc32.crc32x4([0; 4], suffixes.each_ref().map(|s| &s[..])),
+                    [postfix; 4]);
+                for k in 0..4 {
+                    if accept(crcs[k] ^ offset) {
+                        return Some((is[k], suffixes[k].to_vec()));
+                    }
+                }
+            }
+
+            None
+        })
+    }
+
+    let len = charset.suffix_len(crc32.width());
+    let offset = suffix_offset(crc32, prefix, len, postfix);
+    let space = charset.space(len);
+    let mut rng = seed.map(Rng::new);
+
+    // with an affine charset each bit of our counter changes our CRC by a
+    // fixed delta, and counting from i to i+1 flips the trailing ones of
+    // i and the zero after them, so each candidate only needs one xor
+    if charset.is_affine() {
+        let lo = *range.start();
+        let hi = (*range.end()).min(space.checked_sub(1)?);
+        if lo > hi {
+            return None;
+        }
+
+        // our space is a power of two, so each bit of our counter can map
+        // to a random combination of bits, as long as they're independent
+        let bits = space.trailing_zeros();
+        let mut matrix = (0..bits).map(|k| 1 << k).collect::<Vec<u64>>();
+        if let Some(rng) = &mut rng {
+            let ones = space - 1;
+            loop {
+                matrix.iter_mut().for_each(|c| *c = rng.next_u64() & ones);
+                if gf2::rank(&matrix) == bits as usize {
+                    break;
+                }
+            }
+        }
+        let permute = |i: u64| (0..bits)
+            .filter(|&k| i & (1 << k) != 0)
+            .fold(0, |j, k| j ^ matrix[k as usize]);
+
+        let crc = |j: u64| {
+            let mut suffix = vec![0; len];
+            charset.pack(j, &mut suffix);
+            crc32.crc32(crc32.crc32(0, &suffix), postfix) ^ offset
+        };
+        let mut deltas = [(0, 0); 65];
+        for k in 0..bits as usize {
+            deltas[k+1] = (
+                deltas[k].0 ^ matrix[k],
+                deltas[k].1 ^ crc(matrix[k]) ^ crc(0),
+            );
+        }
+        let deltas = &deltas[1..];
+
+        return search_blocks(lo, hi, threads, checkpoint, |block, end| {
+            let mut j = permute(block);
+            let mut x = crc(j);
+            for i in block ..= end {
+                if accept(x) {
+                    let mut suffix = vec![0; len];
+                    charset.pack(j, &mut suffix);
+                    return Some((i, suffix));
+                }
+
+                let (dj, dx) = deltas[(i.trailing_ones() as usize).min(63)];
+                j ^= dj;
+                x ^= dx;
+            }
+
+            None
+        });
+    }
+
+    let stride = match &mut rng {
+        Some(rng) => Stride::random(space, rng),
+        None => Stride{a: 1, b: 0, space},
+    };
+
+    // every suffix length each charset can have, for widths up to 32
+    macro_rules! brute_n {
+        ($($charset:ident => [$($n:literal)*],)*) => {
+            match (charset, len) {
+                $($((Charset::$charset, $n) => brute_::<$n>(
+                    crc32, charset,
+                    |i, buf| Charset::$charset.pack(i, buf),
+                    postfix, offset, stride, range, threads, checkpoint,
+                    accept),)*)*
+                _ => unreachable!(),
+            }
+        };
+    }
+    brute_n! {
+        Raw => [1 2 3 4],
+        Ascii => [2 3 4 5 6 7 8],
+        Whitespace => [1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16
+            17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32],
+        Alnum => [1 2 3 4 5 6],
+        Latin1 => [1 2 3 4 5],
+        Dna => [5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20],
+        Octal => [1 2 3 4 5 6 7 8 9 10 11],
+        Hex => [3 4 5 6 7 8 9 10],
+        Base64 => [2 3 4 5 6 7],
+        SevenBit => [1 2 3 4 5],
+    }
+}
+
+// number of physical cores, hyperthreads share a core's carry-less
+// multiplier, so they don't buy us much
+fn physical_cores() -> usize {
+    let logical = thread::available_parallelism().map_or(1, |n| n.get());
+
+    // linux lists the package and core id of every logical core
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+    let mut cores = std::collections::BTreeSet::new();
+    let mut package = "";
+    for (key, value) in cpuinfo.lines().filter_map(|l| l.split_once(':')) {
+        match key.trim() {
+            "physical id" => package = value.trim(),
+            "core id" => { cores.insert((package, value.trim())); }
+            _ => {}
+        }
+    }
+
+    match cores.len() {
+        0 => logical,
+        n => n.min(logical),
+    }
+}
+
+// check externally generated candidates, calling found on each suffix
+// we accept, returns the number of matches
+fn check_candidates(
+    crc32: &Crc32,
+    prefix: &[u8],
+    postfix: &[u8],
+    path: &Path,
+    format: CandidateFormat,
+    accept: impl Fn(u32) -> bool,
+    mut found: impl FnMut(&[u8]),
+) -> io::Result<(u64, Stats)> {
+    let file: Box<dyn Read> = if path == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(fs::File::open(path)?)
+    };
+    let mut file = io::BufReader::with_capacity(1 << 20, file);
+
+    // our prefix is shared, so only hash it once
+    let start = Instant::now();
+    let init = crc32.crc32(crc32.init(), prefix);
+    let mut stats = Stats::default();
+    let mut candidates = 0u64;
+    let mut matches = 0u64;
+    let mut suffix = vec![];
+    loop {
+        suffix.clear();
+        match format {
+            CandidateFormat::Lines => {
+                if file.read_until(b'\n', &mut suffix)? == 0 {
+                    break;
+                }
+                if suffix.last() == Some(&b'\n') {
+                    suffix.pop();
+                }
+            }
+            CandidateFormat::Length => {
+                let mut len = [0; 4];
+                match file.read_exact(&mut len) {
+                    Ok(()) => {}
+                    Err(err) if err.kind() == io::ErrorKind::UnexpectedEof
+                        => break,
+                    Err(err) => return Err(err),
+                }
+                suffix.resize(u32::from_le_bytes(len) as usize, 0);
+                file.read_exact(&mut suffix)?;
+            }
+        }
+
+        if candidates & 0xffff == 0 && progress::requested() {
+            eprintln!("progress: {} candidates, {} matches, elapsed {:.0?}",
+                candidates, matches, start.elapsed());
+        }
+        candidates += 1;
+        stats.bytes += (suffix.len() + postfix.len()) as u64;
+        stats.pmuls += crc32.pmuls(suffix.len()) + crc32.pmuls(postfix.len());
+
+        if accept(crc32.crc32(crc32.crc32(init, &suffix), postfix)) {
+            found(&suffix);
+            matches += 1;
+        }
+    }
+
+    stats.candidates.push(candidates);
+    stats.solutions = matches;
+    stats.backend = crc32.backend();
+    stats.elapsed = start.elapsed();
+    Ok((matches, stats))
+}
+
+// brute force find a decimal number whose CRC we accept, our indices are
+// just the numbers themselves
+#[allow(clippy::too_many_arguments)]
+fn brute_numeric(
+    crc32: &Crc32,
+    prefix: &[u8],
+    postfix: &[u8],
+    range: RangeInclusive<u64>,
+    digits: Option<usize>,
+    threads: usize,
+    checkpoint: &(dyn Fn(u64) + Sync),
+    accept: impl Fn(u32) -> bool + Sync,
+) -> Option<(u64, Vec<u8>)> {
+    // largest number with d digits
+    let max = |d: usize| 10u64.checked_pow(d as u32).map_or(u64::MAX, |x| x-1);
+    let lo = *range.start();
+    let hi = match digits {
+        Some(d) => (*range.end()).min(max(d)),
+        None => *range.end(),
+    };
+    if lo > hi {
+        return None;
+    }
+
+    // how many digits a number has changes how our prefix offsets our
+    // CRC, so find these once up front
+    let width = |n: u64| digits.unwrap_or_else(|| {
+        (1..20).find(|&d| n <= max(d)).unwrap_or(20)
+    });
+    let offsets = (0 ..= 20)
+        .map(|d| if (width(lo) ..= width(hi)).contains(&d) {
+            suffix_offset(crc32, prefix, d, postfix)
+        } else {
+            0
+        })
+        .collect::<Vec<_>>();
+
+    search_blocks(lo, hi, threads, checkpoint, |block, end| {
+        // a block may span numbers with different numbers of digits, so
+        // search it in groups
+        let mut n = block;
+        loop {
+            let d = width(n);
+            let last = end.min(max(d));
+            let mut suffix = format!("{:0d$}", n, d=d).into_bytes();
+            for i in n ..= last {
+                if accept(crc32.crc32(crc32.crc32(0, &suffix), postfix)
+                        ^ offsets[d]) {
+                    return Some((i, suffix));
+                }
+
+                // increment our decimal representation in place
+                for c in suffix.iter_mut().rev() {
+                    if *c == b'9' {
+                        *c = b'0';
+                    } else {
+                        *c += 1;
+                        break;
+                    }
+                }
+            }
+
+            if last >= end {
+                return None;
+            }
+            n = last + 1;
+        }
+    })
+}
+
+// build the linear system for an affine charset
+//
+// returns the contribution of each bit of our counter, masked, and the
+// CRC of the whole message when our counter is zero
+fn system(
+    crc32: &Crc32,
+    prefix: &[u8],
+    charset: Charset,
+    postfix: &[u8],
+    len: usize,
+    mask: u32,
+) -> (Vec<u64>, u32) {
+    debug_assert!(charset.symbols().is_power_of_two());
+    let bits = charset.symbols().trailing_zeros() as usize;
+    let offset = suffix_offset(crc32, prefix, len, postfix);
+
+    let mut suffix = vec![0; len];
+    charset.pack(0, &mut suffix);
+    let base = crc32.crc32(crc32.crc32(0, &suffix), postfix);
+
+    // find the contribution of each bit
+    let cols = (0..len*bits)
+        .map(|j| {
+            charset.pack(1 << j, &mut suffix);
+            let crc = crc32.crc32(crc32.crc32(0, &suffix), postfix);
+            ((crc ^ base) & mask) as u64
+        })
+        .collect::<Vec<_>>();
+
+    (cols, base ^ offset)
+}
+
+// shortest suffix our solver tries for an affine charset, we only need
+// enough bits to cover our mask
+fn solve_min_len(charset: Charset, width: u32, mask: u32) -> usize {
+    let bits = charset.symbols().trailing_zeros() as usize;
+    (mask.count_ones() as usize).div_ceil(bits)
+        .min(charset.suffix_len(width))
+}
+
+// solve for a suffix that makes the masked bits of our CRC match
+//
+// this only works if our charset's packer is affine, since then our CRC
+// is affine in the bits of our counter, and we can find the counter with
+// Gaussian elimination
+//
+// with a seed, we pick our free bits at random, by solving for whatever
+// is left after a random guess
+fn solve(
+    crc32: &Crc32,
+    prefix: &[u8],
+    charset: Charset,
+    postfix: &[u8],
+    target: u32,
+    mask: u32,
+    seed: Option<u64>,
+) -> Option<Vec<u8>> {
+    // if our system is singular, try again with more characters
+    let bits = charset.symbols().trailing_zeros() as usize;
+    for len in solve_min_len(charset, crc32.width(), mask) ..= 64/bits {
+        let (cols, base) = system(crc32, prefix, charset, postfix, len, mask);
+        let guess = seed.map_or(0, |seed| {
+            Rng::new(seed).next_u64() & (!0 >> (64 - len*bits))
+        });
+        let guessed = cols.iter().enumerate()
+            .filter(|&(j, _)| guess & (1 << j) != 0)
+            .fold(0, |x, (_, c)| x ^ c);
+        if let Some(x) = gf2::solve(&cols,
+                ((target ^ base) & mask) as u64 ^ guessed) {
+            let mut suffix = vec![0; len];
+            charset.pack(x as u64 ^ guess, &mut suffix);
+            return Some(suffix);
+        }
+    }
+
+    None
+}
+
+// meet in the middle, split our suffix in two, table what each first
+// half contributes to our CRC, and then look up the first half each
+// second half needs
+//
+// our CRC is affine in our suffix, so the halves contribute
+// independently, this turns space(len) work into about space(len/2)
+// memory and space(len/2) lookups
+//
+// note our table doesn't depend on our prefix or target, so it can be
+// reused across searches
+fn mitm_table(
+    crc32: &Crc32,
+    charset: Charset,
+    postfix: &[u8],
+    mask: u32,
+) -> Vec<(u32, u64)> {
+    let len = charset.suffix_len(crc32.width());
+    let half = len / 2;
+    let crc = |suffix: &[u8]| crc32.crc32(crc32.crc32(0, suffix), postfix);
+
+    // with the second half at zero, what does each first half give us?
+    let mut suffix = vec![0; len];
+    charset.pack(0, &mut suffix[half..]);
+    let mut table = Vec::with_capacity(charset.space(half) as usize);
+    let mut firsts = CharsetIter::new(charset, half);
+    while let Some(i) = firsts.next_into(&mut suffix[..half]) {
+        table.push((crc(&suffix) & mask, i));
+    }
+    table
+}
+
+fn mitm(
+    crc32: &Crc32,
+    prefix: &[u8],
+    charset: Charset,
+    postfix: &[u8],
+    table: &table::Table,
+    target: u32,
+    mask: u32,
+) -> Option<Vec<u8>> {
+    let len = charset.suffix_len(crc32.width());
+    let half = len / 2;
+    let offset = suffix_offset(crc32, prefix, len, postfix);
+    let crc = |suffix: &[u8]| crc32.crc32(crc32.crc32(0, suffix), postfix);
+
+    // with the first half at zero, what does each second half need?
+    let mut suffix = vec![0; len];
+    charset.pack(0, &mut suffix[..half]);
+    charset.pack(0, &mut suffix[half..]);
+    let base = crc(&suffix);
+    let progress = Progress::new(charset.space(len - half));
+    let mut seconds = CharsetIter::new(charset, len - half);
+    while let Some(i) = seconds.next_into(&mut suffix[half..]) {
+        if i & 0xffff == 0 && progress::requested() {
+            progress.report(i, &format!("0x{:x}", i));
+        }
+        if i & 0xffff == 0 && progress::stopped() {
+            return None;
+        }
+
+        let want = (target ^ offset ^ base ^ crc(&suffix)) & mask;
+        if let Some(j) = table.get(want) {
+            charset.pack(j, &mut suffix[..half]);
+            return Some(suffix);
+        }
+    }
+
+    None
+}
+
+// load our mitm table from disk, or build it and save it there if it
+// doesn't exist yet
+fn load_mitm_table(
+    opt: &Opt,
+    polynomial: u64,
+    crc32: &Crc32,
+    charset: Charset,
+    postfix: &[u8],
+    mask: u32,
+) -> Result<table::Table, String> {
+    // identify the search so we don't use a table for a different CRC
+    let search = format!("poly=0x{:x} xorout=0x{:x} refin={} refout={} \
+            charset={} postfix={} mask=0x{:08x}",
+        polynomial,
+        crc32.xorout(),
+        crc32.refin(),
+        crc32.refout(),
+        charset,
+        coverage::hex(postfix),
+        mask);
+
+    let path = match &opt.mitm_table {
+        Some(path) => path,
+        None => return Ok(table::Table::new(
+            search, mitm_table(crc32, charset, postfix, mask))),
+    };
+
+    let res = match fs::read(path) {
+        Ok(data) => table::Table::parse(&data)
+            .and_then(|table| if table.search == search {
+                Ok(Some(table))
+            } else {
+                Err(format!("table is for a different search:\n  {}",
+                    table.search))
+            }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.to_string()),
+    };
+    let res = res.and_then(|table| match table {
+        Some(table) => Ok(table),
+        None => {
+            let table = table::Table::new(
+                search, mitm_table(crc32, charset, postfix, mask));
+            fs::write(path, table.to_bytes())
+                .map(|_| table)
+                .map_err(|err| err.to_string())
+        }
+    });
+    res.map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+// build the linear system for padding, where only the bytes at the given
+// positions are free
+//
+// the bits of each free byte are our unknowns, xoring them with the
+// filler byte keeps everything affine
+fn padding_system(
+    crc32: &Crc32,
+    prefix: &[u8],
+    postfix: &[u8],
+    padding: &[u8],
+    positions: &[usize],
+    mask: u32,
+) -> (Vec<u64>, u32) {
+    let crc = |padding: &[u8]| {
+        crc32.crc32(
+            crc32.crc32(crc32.crc32(crc32.init(), prefix), padding),
+            postfix)
+    };
+
+    let base = crc(padding);
+    let mut padding = padding.to_vec();
+    let cols = positions.iter()
+        .flat_map(|&p| (0..8).map(move |b| (p, b)))
+        .map(|(p, b)| {
+            padding[p] ^= 1 << b;
+            let col = crc(&padding) ^ base;
+            padding[p] ^= 1 << b;
+            (col & mask) as u64
+        })
+        .collect::<Vec<_>>();
+    (cols, base)
+}
+
+// solve for padding that makes the masked bits of our CRC match, only
+// changing bytes at the given positions
+fn solve_padding(
+    crc32: &Crc32,
+    prefix: &[u8],
+    postfix: &[u8],
+    padding: &[u8],
+    positions: &[usize],
+    target: u32,
+    mask: u32,
+) -> Option<Vec<u8>> {
+    let (cols, base) = padding_system(
+        crc32, prefix, postfix, padding, positions, mask);
+    let x = gf2::solve(&cols, ((target ^ base) & mask) as u64)?;
+
+    let mut padding = padding.to_vec();
+    for (i, &p) in positions.iter().enumerate() {
+        padding[p] ^= (x >> (8*i)) as u8;
+    }
+    Some(padding)
+}
+
+// print bytes, escaping anything that isn't printable, and backslashes
+// so unescape can undo this
+fn print_escaped(bytes: impl IntoIterator<Item=u8>) {
+    print_labeled("", bytes)
+}
+
+// print_escaped after a label, holding stdout so concurrent searches
+// don't interleave their output
+fn print_labeled(label: &str, bytes: impl IntoIterator<Item=u8>) {
+    use std::io::Write;
+
+    // buffer this ourselves, messages can be huge
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let res = out.write_all(label.as_bytes())
+        .and_then(|_| bytes.into_iter().try_for_each(|b| if b == b'\\' {
+            out.write_all(b"\\\\")
+        } else if (b' '..=b'~').contains(&b) {
+            out.write_all(&[b])
+        } else {
+            write!(out, "\\x{:02x}", b)
+        }))
+        .and_then(|_| writeln!(out))
+        .and_then(|_| out.flush());
+    if let Err(err) = res {
+        panic!("failed printing to stdout: {}", err);
+    }
+}
+
+// undo print_escaped, and a few other common escapes
+fn unescape(s: &str) -> Result<Vec<u8>, String> {
+    let mut data = vec![];
+    let mut rest = s.as_bytes();
+    while let Some((&b, rest_)) = rest.split_first() {
+        rest = rest_;
+        if b != b'\\' {
+            data.push(b);
+            continue;
+        }
+
+        let (&e, rest_) = rest.split_first()
+            .ok_or_else(|| format!("{:?}: trailing backslash", s))?;
+        rest = rest_;
+        data.push(match e {
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'0' => 0,
+            b'\\' => b'\\',
+            b'x' => {
+                let b = rest.get(..2)
+                    .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|h| std::str::from_utf8(h).ok())
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    .ok_or_else(|| format!("{:?}: expected two hex digits \
+                        after \\x", s))?;
+                rest = &rest[2..];
+                b
+            }
+            _ => return Err(format!("{:?}: unknown escape \\{}",
+                s, e as char)),
+        });
+    }
+    Ok(data)
+}
+
+// our message with our suffix in place, if our prefix ends mid-byte
+// everything after it is shifted to pack right behind its bits
+//
+// our prefix can be huge, so we only copy what comes after it
+fn message<'a>(
+    opt: &Opt,
+    prefix: &'a [u8],
+    suffix: &[u8],
+    postfix: &[u8],
+) -> impl Iterator<Item=u8> + Clone + 'a {
+    let rest = suffix.iter().chain(postfix).copied();
+    let bits = match opt.prefix_bits {
+        Some(bits) if bits % 8 != 0 => bits,
+        Some(bits) => return prefix[..bits/8].iter().copied()
+            .chain(rest.collect::<Vec<_>>()),
+        None => return prefix.iter().copied()
+            .chain(rest.collect::<Vec<_>>()),
+    };
+
+    // bits fill each byte in the order our CRC reads them
+    let lsb_first = opt.refin.unwrap_or(true);
+    let shift = bits % 8;
+    let mut tail = vec![prefix[bits/8]];
+    let last = tail.last_mut().unwrap();
+    *last &= if lsb_first { !(0xff << shift) } else { !(0xff >> shift) };
+    for b in rest {
+        let last = tail.last_mut().unwrap();
+        if lsb_first {
+            *last |= b << shift;
+            tail.push(b >> (8-shift));
+        } else {
+            *last |= b >> shift;
+            tail.push(b << (8-shift));
+        }
+    }
+    prefix[..bits/8].iter().copied().chain(tail)
+}
+
+// create the CRC described by our options
+fn crc_for(opt: &Opt, polynomial: u64) -> Crc32 {
+    let mut crc32 = Crc32::new(polynomial);
+    if let Some(init) = opt.init {
+        crc32 = crc32.with_init(init);
+    }
+    if let Some(xorout) = opt.xorout {
+        crc32 = crc32.with_xorout(xorout);
+    }
+    if opt.refin.is_some() || opt.refout.is_some() {
+        crc32 = crc32.with_reflect(
+            opt.refin.unwrap_or(true),
+            opt.refout.unwrap_or(true));
+    }
+    crc32
+}
+
+// the honest operation, compute the CRC of our message and store it
+fn append_main(opt: &Opt, prefix: &[u8], label: &str) -> Result<(), String> {
+    let mut frame = prefix.to_vec();
+    let off = opt.crc_offset.unwrap_or(frame.len());
+
+    let mut include = opt.include.clone();
+    if let Some(path) = &opt.include_file {
+        match parse_ranges_file(path) {
+            Ok(more) => include.extend(more),
+            Err(err) => return Err(err),
+        }
+    }
+    if include.is_empty() && off > 0 {
+        include.push(0 ..= off as u64 - 1);
+    }
+    let covered = match covered(frame.len(), &include) {
+        Ok(covered) => select(&frame, &covered),
+        Err(err) => return Err(err),
+    };
+
+    // narrower CRCs only take up as many bytes as they need
+    let n = (opt.width.unwrap_or(32) as usize).div_ceil(8);
+    if frame.len() < off+n {
+        frame.resize(off+n, 0);
+    }
+    let sweep = opt.polynomial.len() > 1;
+    for &polynomial in &opt.polynomial {
+        let crc32 = crc_for(opt, polynomial);
+        let crc = crc32.crc32(crc32.init(), &wire::apply(&opt.wire, &covered));
+        opt.crc_endian.store(crc, &mut frame[off..off+n]);
+
+        let label = if sweep {
+            format!("{}0x{:x}: ", label, polynomial)
+        } else {
+            label.to_string()
+        };
+        print_labeled(&label, frame.iter().copied());
+    }
+    Ok(())
+}
+
+// list-algorithms CLI arguments
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all="kebab")]
+struct ListAlgorithms {
+    /// Also compute each check value with our own engine
+    #[structopt(long)]
+    verify: bool,
+}
+
+fn list_algorithms_main(opt: ListAlgorithms) {
+    let mut failed = false;
+    for alg in catalog::ALGORITHMS {
+        print!("{}", alg);
+        if !alg.aliases.is_empty() {
+            print!(" alias={}", alg.aliases.join(","));
+        }
+        if opt.verify {
+            let check = if alg.width > 32 {
+                let crc64 = alg.crc64();
+                crc64.crc64(crc64.init(), b"123456789")
+            } else {
+                let crc32 = alg.crc32();
+                crc32.crc32(crc32.init(), b"123456789") as u64
+            };
+            if check == alg.check {
+                print!(" ok");
+            } else {
+                print!(" FAIL 0x{:x}", check);
+                failed = true;
+            }
+        }
+        println!();
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+// subcommands we dispatch before parsing our search arguments
+const SUBCOMMANDS: &[&str] = &[
+    "analyze", "merge", "check", "recover", "frame", "blocks", "wide",
+    "crc64", "inspect", "list-algorithms",
+    "daemon", "submit", "status", "result", "cancel",
+    "coordinate", "worker", "batch",
+];
+
+// entry point
+fn main() {
+    // subcommands, note we can't use structopt's subcommands here without
+    // conflicting with our positional prefix
+    match std::env::args().nth(1).as_deref() {
+        Some("analyze") => {
+            let matches = Analyze::clap()
+                .bin_name("crcbrute analyze")
+                .get_matches_from(std::env::args().skip(1));
+            analyze_main(Analyze::from_clap(&matches));
+            return;
+        }
+        Some("merge") => {
+            let matches = Merge::clap()
+                .bin_name("crcbrute merge")
+                .get_matches_from(std::env::args().skip(1));
+            merge_main(Merge::from_clap(&matches));
+            return;
+        }
+        Some("recover") => {
+            let matches = Recover::clap()
+                .bin_name("crcbrute recover")
+                .get_matches_from(std::env::args().skip(1));
+            recover_main(Recover::from_clap(&matches));
+            return;
+        }
+        Some("frame") => {
+            let matches = FrameCmd::clap()
+                .bin_name("crcbrute frame")
+                .get_matches_from(std::env::args().skip(1));
+            frame_main(FrameCmd::from_clap(&matches));
+            return;
+        }
+        Some("blocks") => {
+            let matches = BlocksCmd::clap()
+                .bin_name("crcbrute blocks")
+                .get_matches_from(std::env::args().skip(1));
+            blocks_main(BlocksCmd::from_clap(&matches));
+            return;
+        }
+        Some("inspect") => {
+            let matches = Inspect::clap()
+                .bin_name("crcbrute inspect")
+                .get_matches_from(std::env::args().skip(1));
+            inspect_main(Inspect::from_clap(&matches));
+            return;
+        }
+        Some("wide") => {
+            let matches = WideCmd::clap()
+                .bin_name("crcbrute wide")
+                .get_matches_from(std::env::args().skip(1));
+            wide_main(WideCmd::from_clap(&matches));
+            return;
+        }
+        Some("crc64") => {
+            let matches = Crc64Cmd::clap()
+                .bin_name("crcbrute crc64")
+                .get_matches_from(std::env::args().skip(1));
+            crc64_main(Crc64Cmd::from_clap(&matches));
+            return;
+        }
+        Some("check") => {
+            let matches = Check::clap()
+                .bin_name("crcbrute check")
+                .get_matches_from(std::env::args().skip(1));
+            check_main(Check::from_clap(&matches));
+            return;
+        }
+        Some("list-algorithms") => {
+            let matches = ListAlgorithms::clap()
+                .bin_name("crcbrute list-algorithms")
+                .get_matches_from(std::env::args().skip(1));
+            list_algorithms_main(ListAlgorithms::from_clap(&matches));
+            return;
+        }
+        Some("batch") => {
+            let matches = Batch::clap()
+                .bin_name("crcbrute batch")
+                .get_matches_from(std::env::args().skip(1));
+            batch_main(Batch::from_clap(&matches));
+            return;
+        }
+        Some("coordinate") | Some("worker") => {
+            let matches = Cluster::clap()
+                .bin_name("crcbrute")
+                .get_matches_from(std::env::args());
+            cluster_main(Cluster::from_clap(&matches));
+            return;
+        }
+        Some(cmd) if SUBCOMMANDS.contains(&cmd) => {
+            let matches = Jobs::clap()
+                .bin_name("crcbrute")
+                .get_matches_from(std::env::args());
+            jobs_main(Jobs::from_clap(&matches));
+            return;
+        }
+        _ => {}
+    }
+
+    let opt = Opt::from_iter_ordered(std::env::args())
+        .unwrap_or_else(|err| err.exit());
+    match search_main(opt, "") {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(if progress::expired() {
+            progress::EXIT_EXPIRED
+        } else {
+            progress::EXIT_NOT_FOUND
+        }),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// a single search, labeling our output with label, returns if we found a
+// solution, or an error if we couldn't search
+fn search_main(mut opt: Opt, label: &str) -> Result<bool, String> {
+    // our prefix can come from options, in which case our first
+    // positional argument is really a target
+    if !opt.prefixes.is_empty() {
+        if let Some(target) = opt.prefix.take() {
+            match parse_target(&target) {
+                Ok(target) => opt.target.insert(0, target),
+                // probably meant as a prefix, but we already have one
+                Err(_) => return Err(format!("{:?}: prefix given twice, \
+                    with --prefix, --prefix-file, --prefix-hex, or \
+                    --prefix-base64 our first argument is a target",
+                    target)),
+            }
+        }
+    } else {
+        opt.prefixes.push(Prefix::Text(opt.prefix.take().unwrap()));
+    }
+
+    // text prefixes may need unescaping and encoding
+    let encoding = opt.encoding.unwrap_or(Encoding::Utf8);
+    let text = |prefix: &str| -> Result<Vec<u8>, String> {
+        if !opt.escapes {
+            return Ok(encoding.encode(prefix));
+        }
+        // escapes may leave us with bytes that aren't text, which is fine
+        // unless we need to encode them
+        let data = unescape(prefix)?;
+        if encoding == Encoding::Utf8 {
+            return Ok(data);
+        }
+        match String::from_utf8(data) {
+            Ok(prefix) => Ok(encoding.encode(&prefix)),
+            Err(_) => Err("can't encode escaped prefix, it isn't valid \
+                UTF-8".into()),
+        }
+    };
+
+    // prefix files can be huge, so we map them instead of reading them,
+    // and only copy them if we need to concatenate them with something
+    let mut mapped = None;
+    let mut owned = vec![];
+    let single = opt.prefixes.len() == 1;
+    for prefix in &opt.prefixes {
+        match prefix {
+            Prefix::Text(prefix) => owned.extend(text(prefix)?),
+            // we take our chances with someone modifying our prefix file
+            // mid-search, same as any tool that maps its inputs
+            Prefix::File(path) => match unsafe { Mmap::open(path) } {
+                Ok(data) if single => mapped = Some(data),
+                Ok(data) => owned.extend_from_slice(&data),
+                Err(err) => return Err(format!("{}: {}", path.display(), err)),
+            },
+            Prefix::Hex(hex) => {
+                // allow whitespace and colons, so hexdumps can be pasted
+                // as is
+                let hex = hex.chars()
+                    .filter(|&c| !c.is_ascii_whitespace() && c != ':')
+                    .collect::<String>();
+                match coverage::unhex(&hex) {
+                    Ok(data) => owned.extend(data),
+                    Err(err) => return Err(err),
+                }
+            }
+            Prefix::Base64(base64) => match charset::unbase64(base64) {
+                Ok(data) => owned.extend(data),
+                Err(err) => return Err(err),
+            },
+        }
+    }
+    let base = mapped.as_deref().unwrap_or(&owned);
+
+    // a dry run is mostly an estimate
+    if opt.dry_run {
+        opt.estimate = true;
+    }
+
+    // --ccitt is just shorthand for its algorithm
+    if opt.ccitt {
+        opt.algorithm = catalog::find("crc-16/ccitt-false").copied();
+    }
+
+    // resuming a checkpoint keeps checkpointing
+    if opt.checkpoint.is_none() {
+        opt.checkpoint = opt.resume.clone();
+    }
+
+    // and --start/--end are just the ends of a --range
+    if opt.start.is_some() || opt.end.is_some() {
+        let start = opt.start.unwrap_or(0);
+        let end = opt.end.unwrap_or(u64::MAX);
+        if end <= start {
+            return Err(format!("empty range 0x{:x}..0x{:x}", start, end));
+        }
+        opt.range = Some(start ..= end-1);
+    }
+
+    // a named algorithm fills in whatever parameters we weren't given
+    if let Some(alg) = opt.algorithm {
+        if alg.width > 32 {
+            return Err(format!("{} is {} bits, try crcbrute crc64 -a {}",
+                alg.name, alg.width, alg.name));
+        }
+        opt.polynomial = vec![alg.polynomial() as u64];
+        opt.width = Some(alg.width);
+        opt.init = opt.init.or(Some(alg.init as u32));
+        opt.xorout = opt.xorout.or(Some(alg.xorout as u32));
+        opt.refin = opt.refin.or(Some(alg.refin));
+        opt.refout = opt.refout.or(Some(alg.refout));
+    }
+
+    // or from one of our own profiles
+    let mut check = None;
+    if let Some(name) = opt.profile.clone() {
+        let path = match opt.profiles_file.clone()
+                .or_else(profile::default_path) {
+            Some(path) => path,
+            None => return Err("no profiles file, try --profiles-file".into()),
+        };
+        let profiles = match fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|s| profile::parse(&s)) {
+            Ok(profiles) => profiles,
+            Err(err) => return Err(format!("{}: {}", path.display(), err)),
+        };
+        let p = match profiles.into_iter().find(|p| p.name == name) {
+            Some(p) => p,
+            None => {
+                return Err(format!("no profile {:?} in {}",
+                    name, path.display()));
+            }
+        };
+        opt.polynomial = vec![p.polynomial.unwrap()];
+        opt.width = p.width;
+        opt.init = opt.init.or(p.init);
+        opt.xorout = opt.xorout.or(p.xorout);
+        opt.refin = opt.refin.or(p.refin);
+        opt.refout = opt.refout.or(p.refout);
+        check = p.check.map(|check| (name, check));
+    }
+
+    // resolve our polynomials, we need to know our width before anything
+    // else, and a sweep only makes sense if every CRC has the same width
+    let mut widths = vec![];
+    for p in opt.polynomial.iter_mut() {
+        // koopman notation is just shifted down by one
+        let named = opt.algorithm.is_some() || opt.profile.is_some();
+        if opt.koopman && !named {
+            *p = (*p << 1) | 1;
+        }
+        if opt.reversed_poly && !named {
+            match unreverse_polynomial(*p, opt.width) {
+                Ok(p_) => *p = p_,
+                Err(err) => return Err(err),
+            }
+        }
+        match resolve_polynomial(*p, opt.width) {
+            Ok((p_, width)) => {
+                *p = p_;
+                widths.push(width);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    if widths.iter().any(|&w| w != widths[0]) {
+        return Err("polynomials in a sweep must have the same width".into());
+    }
+    let width = widths[0];
+    let ones = (!0u64 >> (64 - width)) as u32;
+    opt.width = Some(width);
+    opt.target_mask &= ones;
+    if opt.init.is_some_and(|init| init & !ones != 0) {
+        return Err(format!("init doesn't fit in {} bits", width));
+    }
+    if opt.xorout.is_some_and(|xorout| xorout & !ones != 0) {
+        return Err(format!("xorout doesn't fit in {} bits", width));
+    }
+
+    // a profile's check value catches typos in its parameters
+    if let Some((name, check)) = check {
+        let crc32 = crc_for(&opt, opt.polynomial[0]);
+        let crc = crc32.crc32(crc32.init(), b"123456789");
+        if crc != check {
+            return Err(format!("profile {:?} has check 0x{:x}, but its \
+                    parameters give 0x{:x}",
+                name, check, crc));
+        }
+    }
+
+    if opt.append_crc {
+        if opt.adler32 || opt.fletcher.is_some() || opt.sum.is_some() {
+            return Err("--append-crc only appends CRCs".into());
+        }
+        if opt.prefix_bits.is_some() {
+            return Err("--prefix-bits can't be used with --append-crc".into());
+        }
+        append_main(&opt, base, label)?;
+        return Ok(true);
+    }
+
+    let mut charset = if opt.ascii {
+        Charset::Ascii
+    } else {
+        opt.charset_preset
+    };
+
+    let mut prefix = Cow::Borrowed(base);
+    let mut postfix = vec![];
+    if let Some(comment) = opt.comment {
+        prefix.to_mut().extend_from_slice(comment.leader());
+        postfix.extend_from_slice(comment.closer());
+        // raw bytes could end our comment early, or just not compile, and
+        // latin1 isn't valid UTF-8, which most source files need to be
+        match charset {
+            Charset::Raw => charset = Charset::Ascii,
+            Charset::Latin1 | Charset::SevenBit => {
+                return Err(format!("charset {} can't be used in comments",
+                    charset));
+            }
+            _ => {}
+        }
+    }
+    if let Some(name) = &opt.json_field {
+        match json::field(&prefix, name) {
+            Ok((prefix_, postfix_)) => {
+                prefix = Cow::Owned(prefix_);
+                postfix.splice(0..0, postfix_);
+            }
+            Err(err) => return Err(err),
+        }
+        // JSON strings can't contain control characters, quotes,
+        // backslashes, or invalid UTF-8
+        match charset {
+            Charset::Raw => charset = Charset::Ascii,
+            Charset::Whitespace | Charset::Latin1 | Charset::SevenBit => {
+                return Err(format!("charset {} can't be used in JSON strings",
+                    charset));
+            }
+            _ => {}
+        }
+    }
+    if opt.trailing_newline && postfix.last() != Some(&b'\n') {
+        postfix.push(b'\n');
+    }
+
+    let mut targets = opt.target.clone();
+    if let Some(path) = &opt.targets_file {
+        match parse_targets_file(path) {
+            Ok(more) => targets.extend(more),
+            Err(err) => return Err(err),
+        }
+    }
+    targets.extend(opt.target_of.iter()
+        .map(|msg| Target::Of(msg.as_bytes().to_vec())));
+    for path in &opt.target_from_file {
+        match fs::read(path) {
+            Ok(data) => targets.push(Target::Of(data)),
+            Err(err) => return Err(format!("{}: {}", path.display(), err)),
+        }
+    }
+    if targets.is_empty()
+            && opt.target_range.is_empty()
+            && opt.predicate.is_empty() {
+        return Err("no targets".into());
+    }
+    if opt.target_range.iter().any(|r| *r.end() > ones as u64) {
+        return Err(format!("target ranges must fit in {} bits", width));
+    }
+    // other checksums have their own solvers, none of our CRC machinery
+    // applies
+    if opt.adler32 || opt.fletcher.is_some() || opt.sum.is_some() {
+        return checksum_main(&opt, charset, &prefix, &postfix, &targets,
+            label);
+    }
+
+    // padding replaces our suffix, and is solved directly
+    let mut pad_positions = opt.pad_positions.clone();
+    if let Some(len) = opt.pad_len {
+        pad_positions.sort_unstable();
+        pad_positions.dedup();
+
+        let err = if pad_positions.iter().any(|&p| p >= len) {
+            Some(format!("padding positions must be less than {}", len))
+        } else if pad_positions.len() > 16 {
+            Some("at most 16 padding positions".into())
+        } else if charset != Charset::Raw {
+            Some(format!("padding doesn't support charset {}", charset))
+        } else if opt.numeric
+                || opt.numeric_range.is_some()
+                || opt.numeric_digits.is_some() {
+            Some("padding doesn't support numeric suffixes".into())
+        } else if opt.range.is_some()
+                || opt.shard.is_some()
+                || opt.checkpoint.is_some()
+                || opt.max_iters.is_some()
+                || opt.coverage.is_some()
+                || !opt.exclude.is_empty()
+                || !opt.exclude_coverage.is_empty() {
+            Some("padding can't be split into ranges".into())
+        } else if opt.target_hd > 0 {
+            Some("padding doesn't support --target-hd".into())
+        } else if predicate::as_mask(&opt.predicate, width).is_none() {
+            Some("padding only supports mask predicates".into())
+        } else {
+            None
+        };
+        if let Some(err) = err {
+            return Err(err);
+        }
+    }
+
+    // only some bytes may feed our CRC
+    let mut include = opt.include.clone();
+    if let Some(path) = &opt.include_file {
+        match parse_ranges_file(path) {
+            Ok(more) => include.extend(more),
+            Err(err) => return Err(err),
+        }
+    }
+    let covered = if include.is_empty() {
+        (Cow::Borrowed(&prefix[..]), Cow::Borrowed(&postfix[..]))
+    } else {
+        match covered(prefix.len() + postfix.len(), &include) {
+            Ok(covered) => (
+                Cow::Owned(select(&prefix, &covered[..prefix.len()])),
+                Cow::Owned(select(&postfix, &covered[prefix.len()..])),
+            ),
+            Err(err) => return Err(err),
+        }
+    };
+
+    // if our CRC sees a different view of our message, we can search
+    // over that view instead, as long as our suffix stays word-aligned
+    let covered = if opt.wire.is_empty() {
+        covered
+    } else {
+        let align = wire::width(&opt.wire);
+        let len = opt.pad_len.unwrap_or(charset.suffix_len(width));
+        let err = if opt.numeric
+                || opt.numeric_range.is_some()
+                || opt.numeric_digits.is_some() {
+            Some("--wire doesn't support numeric suffixes".into())
+        } else if opt.candidates.is_some()
+                || opt.export_constraints.is_some()
+                || opt.assignment.is_some() {
+            Some("--wire can't be used with --candidates or constraints".into())
+        } else if opt.wire.contains(&View::BitReverse)
+                && charset != Charset::Raw {
+            Some(format!("bitrev doesn't support charset {}", charset))
+        } else if !covered.0.len().is_multiple_of(align) {
+            Some(format!("covered prefix must be a multiple of {} bytes",
+                align))
+        } else if !len.is_multiple_of(align) {
+            Some(format!("suffix must be a multiple of {} bytes", align))
+        } else {
+            None
+        };
+        if let Some(err) = err {
+            return Err(err);
+        }
+
+        for p in pad_positions.iter_mut() {
+            *p = wire::position(&opt.wire, len, *p);
+        }
+        (Cow::Owned(wire::apply(&opt.wire, &covered.0)),
+            Cow::Owned(wire::apply(&opt.wire, &covered.1)))
+    };
+
+    // a prefix that ends mid-byte can't carry anything byte-oriented
+    if let Some(bits) = opt.prefix_bits {
+        let err = if bits > 8*base.len() {
+            Some(format!("--prefix-bits {} is longer than our prefix", bits))
+        } else if opt.comment.is_some() || opt.json_field.is_some() {
+            Some("--prefix-bits can't be used with --comment or --json-field"
+                .into())
+        } else if !include.is_empty() || !opt.wire.is_empty() {
+            Some("--prefix-bits can't be used with --include or --wire"
+                .into())
+        } else if opt.pad_len.is_some() {
+            Some("--prefix-bits can't be used with padding".into())
+        } else {
+            None
+        };
+        if let Some(err) = err {
+            return Err(err);
+        }
+    }
+
+    // a byte sum on top of our CRC has its own search, built on our
+    // algebraic solver
+    if let Some(SumTarget{sum, target}) = opt.with_sum {
+        let err = if opt.strategy == Strategy::Sat {
+            Some("--with-sum doesn't support the sat strategy".into())
+        } else if !sum.is_byte_sum() {
+            Some("--with-sum only supports sum8, sum16, and lrc".into())
+        } else if !sum.is_valid(target) {
+            Some(format!("0x{:08x} isn't a valid {} checksum", target, sum))
+        } else if !charset.is_affine() {
+            Some(format!("--with-sum doesn't support charset {}", charset))
+        } else if matches!(charset.longest_run(), (lo, hi) if lo == hi) {
+            Some("--with-sum needs a charset with a run of at least two \
+                consecutive bytes".into())
+        } else if !opt.target_range.is_empty()
+                || !opt.predicate.is_empty()
+                || opt.target_hd > 0 {
+            Some("--with-sum only supports exact targets".into())
+        } else if opt.numeric
+                || opt.numeric_range.is_some()
+                || opt.numeric_digits.is_some() {
+            Some("--with-sum doesn't support numeric suffixes".into())
+        } else if opt.pad_len.is_some()
+                || !include.is_empty()
+                || !opt.wire.is_empty()
+                || opt.prefix_bits.is_some() {
+            Some("--with-sum doesn't support padding, --include, --wire, \
+                or --prefix-bits".into())
+        } else if opt.range.is_some()
+                || opt.shard.is_some()
+                || opt.checkpoint.is_some()
+                || opt.max_iters.is_some()
+                || opt.coverage.is_some()
+                || !opt.exclude.is_empty()
+                || !opt.exclude_coverage.is_empty()
+                || opt.candidates.is_some()
+                || opt.export_constraints.is_some()
+                || opt.assignment.is_some() {
+            Some("--with-sum doesn't support ranges, candidates, or \
+                constraints".into())
+        } else {
+            None
+        };
+        if let Some(err) = err {
+            return Err(err);
+        }
+    }
+
+    // so does a second CRC, solving both at once
+    if let Some(and_crc) = opt.and_crc {
+        let width = and_crc.crc32().width();
+        // sat handles any charset
+        let err = if !charset.is_affine() && opt.strategy != Strategy::Sat {
+            Some(format!("--and-crc doesn't support charset {}", charset))
+        } else if opt.polynomial.iter().any(|&p| {
+                matches!(resolve_polynomial(p, opt.width),
+                    Ok((_, w)) if w + width > 64)
+            }) {
+            Some("--and-crc only supports CRCs up to 64 bits combined"
+                .into())
+        } else if !opt.target_range.is_empty()
+                || !opt.predicate.is_empty()
+                || opt.target_hd > 0 {
+            Some("--and-crc only supports exact targets".into())
+        } else if opt.numeric
+                || opt.numeric_range.is_some()
+                || opt.numeric_digits.is_some() {
+            Some("--and-crc doesn't support numeric suffixes".into())
+        } else if opt.pad_len.is_some()
+                || !include.is_empty()
+                || !opt.wire.is_empty()
+                || opt.prefix_bits.is_some() {
+            Some("--and-crc doesn't support padding, --include, --wire, \
+                or --prefix-bits".into())
+        } else if opt.range.is_some()
+                || opt.shard.is_some()
+                || opt.checkpoint.is_some()
+                || opt.max_iters.is_some()
+                || opt.coverage.is_some()
+                || !opt.exclude.is_empty()
+                || !opt.exclude_coverage.is_empty()
+                || opt.candidates.is_some()
+                || opt.export_constraints.is_some()
+                || opt.assignment.is_some() {
+            Some("--and-crc doesn't support ranges, candidates, or \
+                constraints".into())
+        } else {
+            None
+        };
+        if let Some(err) = err {
+            return Err(err);
+        }
+    }
+
+    // each polynomial needs its own prefix hashing, so a sweep is just
+    // a search per polynomial
+    let sweep = opt.polynomial.len() > 1;
+    if sweep && opt.coverage.is_some() {
+        return Err("--coverage only supports one polynomial".into());
+    }
+    if sweep && opt.checkpoint.is_some() {
+        return Err("--checkpoint only supports one polynomial".into());
+    }
+    if sweep && opt.candidates.is_some() {
+        return Err("--candidates only supports one polynomial".into());
+    }
+    if sweep && opt.export_constraints.is_some() {
+        return Err("--export-constraints only supports one polynomial".into());
+    }
+    if opt.export_constraints.is_some() || opt.assignment.is_some() {
+        let err = if opt.pad_len.is_some() {
+            Some("constraints can't be exported with padding")
+        } else if opt.numeric
+                || opt.numeric_range.is_some()
+                || opt.numeric_digits.is_some() {
+            Some("constraints can't be exported for numeric suffixes")
+        } else if opt.target_hd > 0 {
+            Some("constraints can't be exported with --target-hd")
+        } else {
+            None
+        };
+        if let Some(err) = err {
+            return Err(err.into());
+        }
+    }
+    if opt.candidates.is_some() {
+        let err = if opt.pad_len.is_some() {
+            Some("--candidates can't be used with padding")
+        } else if opt.numeric
+                || opt.numeric_range.is_some()
+                || opt.numeric_digits.is_some() {
+            Some("--candidates can't be used with numeric suffixes")
+        } else if opt.range.is_some()
+                || opt.shard.is_some()
+                || opt.checkpoint.is_some()
+                || opt.max_iters.is_some()
+                || opt.coverage.is_some()
+                || !opt.exclude.is_empty()
+                || !opt.exclude_coverage.is_empty() {
+            Some("--candidates can't be split into ranges")
+        } else {
+            None
+        };
+        if let Some(err) = err {
+            return Err(err.into());
+        }
+    }
+
+    // let the user check on long searches, or stop them cleanly
+    progress::install();
+    progress::json(opt.progress == ProgressFormat::Json);
+    progress::install_interrupt();
+
+    // and report on our own every so often if someone is watching
+    let interval = match opt.progress_interval {
+        Some(secs) if !(secs > 0.0 && secs.is_finite())
+            => return Err(format!("invalid progress interval {}", secs)),
+        Some(secs) => Some(secs),
+        None if io::stderr().is_terminal() => Some(5.0),
+        None => None,
+    };
+    if let (Some(secs), false) = (interval, opt.quiet) {
+        progress::every(Duration::from_secs_f64(secs));
+    }
+
+    // give up on searches that run too long
+    if let Some(secs) = opt.max_time {
+        if !(secs >= 0.0 && secs.is_finite()) {
+            return Err(format!("invalid time limit {}", secs));
+        }
+        progress::expire_after(Duration::from_secs_f64(secs));
+    }
+
+    // checkpoint long searches
+    if opt.checkpoint.is_some() {
+        let secs = opt.checkpoint_interval;
+        if !(secs > 0.0 && secs.is_finite()) {
+            return Err(format!("invalid checkpoint interval {}", secs));
+        }
+        progress::checkpoint_every(Duration::from_secs_f64(secs));
+    }
+
+    // and stop once another shard finds a solution, a stat every so
+    // often is cheap
+    if let Some(path) = opt.found_file.clone() {
+        thread::spawn(move || {
+            while !path.exists() {
+                thread::sleep(Duration::from_millis(100));
+            }
+            progress::stop();
+        });
+    }
+
+    let mut found = true;
+    for &polynomial in &opt.polynomial {
+        let label = if sweep {
+            format!("{}0x{:x}: ", label, polynomial)
+        } else {
+            label.to_string()
+        };
+        found &= match (opt.with_sum, opt.and_crc) {
+            (Some(with_sum), _) => forge_with_sum(&opt, polynomial, charset,
+                &prefix, &postfix, &targets, with_sum, &label),
+            (_, Some(and_crc)) => forge_and_crc(&opt, polynomial, charset,
+                &prefix, &postfix, &targets, and_crc, &label),
+            _ => forge(&opt, polynomial, charset, &prefix, &postfix,
+                (&covered.0, &covered.1), &include, &pad_positions,
+                &targets, &label),
+        }?;
+    }
+
+    Ok(found)
+}
+
+// forge an Adler-32, Fletcher, or simple checksum instead of a CRC
+fn checksum_main(
+    opt: &Opt,
+    charset: Charset,
+    prefix: &[u8],
+    postfix: &[u8],
+    targets: &[Target],
+    label: &str,
+) -> Result<bool, String> {
+    let (name, flag) = match (opt.sum, opt.fletcher) {
+        (Some(sum), _) => (sum.to_string(), "--sum"),
+        (_, Some(fletcher)) => (fletcher.to_string(), "--fletcher"),
+        _ => ("adler32".to_string(), "--adler32"),
+    };
+    let checksum: Box<dyn Checksum> = match (opt.sum, opt.fletcher) {
+        (Some(sum), _) => Box::new(sum),
+        (_, Some(fletcher)) => Box::new(fletcher),
+        _ => Box::new(Adler32),
+    };
+
+    let err = if !opt.target_range.is_empty()
+            || !opt.predicate.is_empty()
+            || opt.target_hd > 0 {
+        Some(format!("{} only supports exact targets", flag))
+    } else if targets.iter()
+            .any(|t| matches!(t, Target::Zero | Target::Residue)) {
+        Some("zero and residue targets only make sense for CRCs".into())
+    } else if let Some(Target::Value(t)) = targets.iter()
+            .find(|t| matches!(t, Target::Value(t)
+                if !checksum.is_valid(*t))) {
+        Some(format!("0x{:08x} isn't a valid {} checksum", t, name))
+    } else if opt.numeric
+            || opt.numeric_range.is_some()
+            || opt.numeric_digits.is_some() {
+        Some(format!("{} doesn't support numeric suffixes", flag))
+    } else if opt.pad_len.is_some()
+            || !opt.include.is_empty()
+            || opt.include_file.is_some()
+            || !opt.wire.is_empty()
+            || opt.prefix_bits.is_some() {
+        Some(format!("{} doesn't support padding, --include, --wire, \
+            or --prefix-bits", flag))
+    } else if opt.range.is_some()
+            || opt.shard.is_some()
+            || opt.checkpoint.is_some()
+            || opt.max_iters.is_some()
+            || opt.coverage.is_some()
+            || opt.candidates.is_some()
+            || opt.export_constraints.is_some()
+            || opt.assignment.is_some() {
+        Some(format!("{} doesn't support ranges, candidates, or \
+            constraints", flag))
+    } else {
+        None
+    };
+    if let Some(err) = err {
+        return Err(err);
+    }
+
+    let targets = targets.iter()
+        .map(|t| match t {
+            Target::Value(t) => *t,
+            Target::Of(data) => checksum.checksum(data),
+            _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+
+    // our solvers need a contiguous range of bytes, without one we fall
+    // back to brute force
+    let found = checksum::forge(&*checksum, prefix, postfix, &targets,
+        charset);
+    let (suffix, target) = match found {
+        Some(found) => found,
+        None => {
+            eprintln!("{}no solution found", label);
+            return Ok(false);
+        }
+    };
+    let message = prefix.iter().copied()
+        .chain(suffix.iter().copied())
+        .chain(postfix.iter().copied())
+        .collect::<Vec<_>>();
+    print_labeled(label, message.iter().copied());
+
+    // validate that the checksum matches
+    let sum = checksum.checksum(&message);
+    if sum != target {
+        return Err(format!("found {} 0x{:08x}, but wanted 0x{:08x}",
+            name, sum, target));
+    }
+    if targets.len() > 1 {
+        eprintln!("{}{} 0x{:08x}", label, name, sum);
+    }
+    if opt.verbose {
+        eprintln!("{}{} before suffix 0x{:08x}, after 0x{:08x}",
+            label, name,
+            checksum.checksum(prefix),
+            checksum.checksum(&message[..prefix.len()+suffix.len()]));
+    }
+    Ok(true)
+}
+
+// forge a suffix that hits both a CRC and a byte sum, returns false if we
+// didn't find one
+#[allow(clippy::too_many_arguments)]
+fn forge_with_sum(
+    opt: &Opt,
+    polynomial: u64,
+    charset: Charset,
+    prefix: &[u8],
+    postfix: &[u8],
+    targets: &[Target],
+    with_sum: SumTarget,
+    label: &str,
+) -> Result<bool, String> {
+    let crc32 = crc_for(opt, polynomial);
+    let targets = targets.iter()
+        .map(|t| t.resolve(&crc32))
+        .collect::<Vec<_>>();
+    if let Some(t) = targets.iter().find(|&&t| t & !crc32.mask() != 0) {
+        return Err(format!("target 0x{:x} doesn't fit in {} bits",
+            t, crc32.width()));
+    }
+
+    match solve_with_sum(&crc32, prefix, charset, postfix, &targets,
+            opt.target_mask, with_sum) {
+        Some((suffix, tries)) => {
+            let SumTarget{sum, ..} = with_sum;
+            let whole = [prefix, &suffix, postfix].concat();
+            print_labeled(label, message(opt, prefix, &suffix, postfix));
+            if opt.verbose {
+                print_state(&crc32, prefix, &suffix, label);
+                eprintln!("{}{} 0x{:08x} after {} tries",
+                    label, sum, sum.sum(&whole), tries);
+            }
+            Ok(true)
+        }
+        None => {
+            eprintln!("{}no solution found", label);
+            Ok(false)
+        }
+    }
+}
+
+// find a suffix that hits one of our CRC targets and a byte sum at once,
+// returns the suffix and how many heads we tried
+//
+// Our suffix is a counter, then a run of filler bytes, then a tail our
+// algebraic solver picks to fix our CRC. A byte sum doesn't care where
+// our bytes are, so the filler can make up whatever our counter and tail
+// don't, except we don't know the tail's sum until we've solved for it.
+// Instead we guess its most likely sum, and count up until we're right,
+// which only takes a few hundred tries for a raw CRC-32.
+fn solve_with_sum(
+    crc32: &Crc32,
+    prefix: &[u8],
+    charset: Charset,
+    postfix: &[u8],
+    targets: &[u32],
+    mask: u32,
+    with_sum: SumTarget,
+) -> Option<(Vec<u8>, u64)> {
+    // how long is our tail, and what does it usually add up to?
+    let tail_len = solve(crc32, prefix, charset, postfix,
+        targets[0], mask, None)?.len() as u64;
+    let bytes = (0..=255u8).filter(|&b| charset.contains(b))
+        .collect::<Vec<_>>();
+    let mean = bytes.iter().map(|&b| b as u64).sum::<u64>()
+        / bytes.len() as u64;
+    let guess = tail_len * mean;
+
+    let SumTarget{sum, target: sum_target} = with_sum;
+    let m = sum.modulus();
+    let (lo, hi) = charset.longest_run();
+    let (lo, hi) = (lo as u64, hi as u64);
+    let len = charset.suffix_len(crc32.width());
+    let mut head = vec![0; len];
+    let mut data = prefix.to_vec();
+    for i in 0..charset.space(len) {
+        charset.pack(i, &mut head);
+        data.truncate(prefix.len());
+        data.extend_from_slice(&head);
+
+        // filler makes up the rest of our sum, as few bytes as we can
+        let mut want = (sum.deficit(&[&data[..], postfix].concat(),
+            sum_target) + m - guess % m) % m;
+        let n = loop {
+            let n = want.div_ceil(hi);
+            if n*lo <= want {
+                break n;
+            }
+            want += m;
+        };
+        let mut left = want - n*lo;
+        for _ in 0..n {
+            let t = left.min(hi - lo);
+            data.push((lo + t) as u8);
+            left -= t;
+        }
+
+        for &t in targets {
+            let tail = match solve(crc32, &data, charset, postfix,
+                    t, mask, None) {
+                Some(tail) => tail,
+                None => continue,
+            };
+            let suffix = [&data[prefix.len()..], &tail].concat();
+            let whole = [prefix, &suffix, postfix].concat();
+            if sum.sum(&whole) == sum_target {
+                return Some((suffix, i+1));
+            }
+        }
+    }
+
+    None
+}
+
+// forge a suffix that hits two CRCs at once, returns false if we didn't
+// find one
+//
+// Both CRCs are affine in the bits of our suffix, so stacking their
+// columns gives one system that solves both, as long as we have enough
+// bits, and our charset is affine. Otherwise sat can solve the stacked
+// equations for any charset.
+#[allow(clippy::too_many_arguments)]
+fn forge_and_crc(
+    opt: &Opt,
+    polynomial: u64,
+    charset: Charset,
+    prefix: &[u8],
+    postfix: &[u8],
+    targets: &[Target],
+    and_crc: AndCrc,
+    label: &str,
+) -> Result<bool, String> {
+    let crcs = [crc_for(opt, polynomial), and_crc.crc32()];
+    let masks = [opt.target_mask & crcs[0].mask(), crcs[1].mask()];
+    let shift = crcs[0].width();
+    let crc = |suffix: &[u8]| -> u64 {
+        let [a, b] = [0, 1].map(|i| crcs[i].crc32(
+            crcs[i].crc32(crcs[i].crc32(crcs[i].init(), prefix), suffix),
+            postfix) & masks[i]);
+        a as u64 | ((b as u64) << shift)
+    };
+
+    let rank = (masks[0].count_ones() + masks[1].count_ones()) as usize;
+    for target in targets {
+        let target = target.resolve(&crcs[0]);
+        if target & !crcs[0].mask() != 0 {
+            return Err(format!("target 0x{:x} doesn't fit in {} bits",
+                target, crcs[0].width()));
+        }
+
+        let suffix = match opt.strategy {
+            #[cfg(feature="sat")]
+            Strategy::Sat => {
+                let allowed = (0..=255).filter(|&b| charset.contains(b))
+                    .collect::<Vec<_>>();
+                let len = charset.suffix_len(rank as u32);
+                sat::solve(&Constraints::new(
+                    &crcs[0], prefix, postfix, len, target, masks[0],
+                    &allowed
+                ).and(Constraints::new(
+                    &crcs[1], prefix, postfix, len, and_crc.target, masks[1],
+                    &allowed
+                )))
+            }
+            _ => solve_chars(charset, rank, crc,
+                (target & masks[0]) as u64
+                    | ((and_crc.target as u64) << shift)),
+        };
+        if let Some(suffix) = suffix {
+            print_labeled(label, message(opt, prefix, &suffix, postfix));
+            if opt.verbose {
+                print_state(&crcs[0], prefix, &suffix, label);
+                print_state(&crcs[1], prefix, &suffix, label);
+            }
+            return Ok(true);
+        }
+    }
+
+    // polynomials that share a factor, often x+1, tie some bits of our
+    // CRCs together, so only some pairs of targets are reachable
+    eprintln!("{}no solution found", label);
+    Ok(false)
+}
+
+// forge a suffix for one polynomial, returns false if we didn't find one
+//
+// covered is the part of our prefix and postfix that actually feeds the
+// CRC, as selected by include, and label prefixes our output, so sweeps
+// over multiple polynomials can tell results apart
+#[allow(clippy::too_many_arguments)]
+fn forge(
+    opt: &Opt,
+    polynomial: u64,
+    charset: Charset,
+    dataprefix: &[u8],
+    datapostfix: &[u8],
+    (prefix, postfix): (&[u8], &[u8]),
+    include: &[RangeInclusive<u64>],
+    pad_positions: &[usize],
+    targets: &[Target],
+    label: &str,
+) -> Result<bool, String> {
+    // create our CRC
+    let crc32 = crc_for(opt, polynomial);
+
+    // a partial byte doesn't fit our byte-oriented search, so start from
+    // the CRC of our prefix's bits instead
+    let (crc32, prefix) = match opt.prefix_bits {
+        Some(bits) => {
+            let crc = crc32.crc32_bits(crc32.init(), prefix, bits);
+            (crc32.with_crc(crc), &[][..])
+        }
+        None => (crc32, prefix),
+    };
+
+    // if we weren't told which padding bytes are free, use just enough
+    // bytes at the end of our padding to make our system full rank
+    let pad_byte = wire::apply(&opt.wire, &[opt.pad_byte])[0];
+    let mut pad_positions = pad_positions.to_vec();
+    if let (Some(len), true) = (opt.pad_len, pad_positions.is_empty()) {
+        let bits = opt.target_mask.count_ones() as usize;
+        for n in bits.div_ceil(8) ..= len.min(16) {
+            pad_positions = (len-n .. len).collect();
+            let (cols, _) = padding_system(
+                &crc32, prefix, postfix, &vec![pad_byte; len],
+                &pad_positions, opt.target_mask);
+            if gf2::rank(&cols) == bits {
+                break;
+            }
+        }
+    }
+    let pad_positions = &pad_positions[..];
+
+    let targets = targets.iter()
+        .map(|t| t.resolve(&crc32))
+        .collect::<Vec<_>>();
+    if let Some(t) = targets.iter().find(|&&t| t & !crc32.mask() != 0) {
+        return Err(format!("target 0x{:x} doesn't fit in {} bits",
+            t, crc32.width()));
+    }
+
+    // which of our targets does this CRC hit?
+    let mut exact = targets.iter()
+        .map(|t| t & opt.target_mask)
+        .collect::<Vec<_>>();
+    exact.sort_unstable();
+    exact.dedup();
+    let hit = |crc: u32| -> Option<u32> {
+        targets.iter().copied().find(|t| {
+            ((crc ^ t) & opt.target_mask).count_ones() <= opt.target_hd
+        })
+    };
+
+    // or which of our ranges?
+    let ranges = opt.target_range.iter()
+        .map(|r| (*r.start() as u32, *r.end() as u32))
+        .collect::<Vec<_>>();
+    let in_range = |crc: u32| -> Option<(u32, u32)> {
+        ranges.iter().copied().find(|&(lo, hi)| lo <= crc && crc <= hi)
+    };
+
+    // or do we satisfy our predicates?
+    let predicates = &opt.predicate[..];
+    let satisfies = |crc: u32| -> bool {
+        !predicates.is_empty()
+            && predicates.iter().all(|p| p.test(crc, crc32.width()))
+    };
+
+    // someone else is generating our candidates?
+    if let Some(path) = &opt.candidates {
+        let accept = |crc: u32| {
+            hit(crc).is_some() || in_range(crc).is_some() || satisfies(crc)
+        };
+        let res = check_candidates(
+            &crc32, prefix, postfix,
+            path, opt.candidates_format,
+            accept,
+            |suffix| {
+                print_labeled(label,
+                    message(opt, dataprefix, suffix, datapostfix));
+                if opt.verbose {
+                    print_state(&crc32, prefix, suffix, label);
+                }
+            });
+        return match res {
+            Ok((matches, stats)) => {
+                if opt.stats {
+                    stats.report(label);
+                }
+                if matches == 0 {
+                    eprintln!("{}no solution found", label);
+                }
+                Ok(matches > 0)
+            }
+            Err(err) => Err(format!("{}: {}", path.display(), err)),
+        };
+    }
+
+    // what our solvers work with, each a target and mask, predicates that
+    // only fix some bits become one more of these
+    let mask = predicate::as_mask(predicates, crc32.width());
+    let solvable = targets.iter()
+        .map(|&t| (t, opt.target_mask))
+        .chain(opt.target_range.iter().flat_map(range_blocks))
+        .chain(mask.flatten().filter(|_| !predicates.is_empty()))
+        .collect::<Vec<_>>();
+
+    let numeric = opt.numeric
+        || opt.numeric_range.is_some()
+        || opt.numeric_digits.is_some();
+
+    let ranged = opt.range.is_some()
+        || opt.shard.is_some()
+        || opt.checkpoint.is_some()
+        || opt.max_iters.is_some()
+        || opt.coverage.is_some()
+        || !opt.exclude.is_empty()
+        || !opt.exclude_coverage.is_empty();
+    let predicated = mask.is_none();
+    let requested = match opt.strategy {
+        Strategy::Auto if opt.mitm_table.is_some() => Strategy::Mitm,
+        strategy => strategy,
+    };
+    let seed = match (opt.seed, opt.random) {
+        (Some(seed), _) => Some(seed),
+        (None, true) => Some(Rng::seed()),
+        (None, false) => None,
+    };
+    if seed.is_some() && numeric {
+        return Err("numeric searches can't be random".into());
+    }
+    let strategy = plan(
+        requested, charset, numeric, ranged, predicated, seed.is_some())?;
+
+    // some charsets have exactly one shortest suffix for each CRC, which
+    // leaves our algebraic solver nothing to randomize
+    if strategy == Strategy::Algebraic && seed.is_some() {
+        let len = solve_min_len(charset, crc32.width(), opt.target_mask);
+        let (cols, _) = system(
+            &crc32, prefix, charset, postfix, len, opt.target_mask);
+        if gf2::rank(&cols) == cols.len() {
+            eprintln!("{}warning: --random has no effect, charset {} has \
+                    exactly one {}-character suffix for each CRC",
+                label, charset, len);
+        }
+    }
+
+    if opt.mitm_table.is_some() && strategy != Strategy::Mitm {
+        return Err("--mitm-table needs --strategy=mitm".into());
+    }
+
+    // hand our constraints off to an external solver, or check what it
+    // came back with
+    if opt.export_constraints.is_some() || opt.assignment.is_some() {
+        let (target, mask) = match (&solvable[..], predicated) {
+            (&[solvable], false) => solvable,
+            _ => {
+                return Err("constraints can only be exported for a \
+                    single target, range block, or mask".into());
+            }
+        };
+        let len = charset.suffix_len(crc32.width());
+        let allowed = (0..=255).filter(|&b| charset.contains(b))
+            .collect::<Vec<_>>();
+        let constraints = Constraints::new(
+            &crc32, prefix, postfix, len, target, mask, &allowed);
+
+        if let Some(format) = opt.export_constraints {
+            print!("{}", match format {
+                ConstraintFormat::Dimacs => constraints.dimacs(),
+                ConstraintFormat::Smtlib => constraints.smtlib(),
+            });
+            return Ok(true);
+        }
+
+        let path = opt.assignment.as_ref().unwrap();
+        let suffix = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| sat::parse_assignment(&s, len))
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        print_labeled(label,
+            message(opt, dataprefix, &suffix, datapostfix));
+        let crc = crc32.crc32(crc32.crc32(crc32.crc32(crc32.init(),
+            prefix),
+            &suffix),
+            postfix);
+        let ok = (crc ^ target) & mask == 0
+            && suffix.iter().all(|&b| charset.contains(b));
+        eprintln!("{}crc 0x{:08x}, {}",
+            label, crc, if ok { "pass" } else { "fail" });
+        return Ok(ok);
+    }
+
+    // how many CRCs do we accept? ranges ignore our mask, so scale them to
+    // the bits we constrain
+    let bits = opt.target_mask.count_ones();
+    let accepted = exact.len() as f64
+        * analyze::hamming_ball(bits, opt.target_hd)
+        + ranges.iter()
+            .map(|&(lo, hi)| (hi - lo) as f64 + 1.0)
+            .sum::<f64>()
+            * 2f64.powi(bits as i32 - crc32.width() as i32)
+        + predicate_count(predicates, crc32.width())
+            * 2f64.powi(bits as i32 - crc32.width() as i32);
+
+    if opt.estimate {
+        if let Some(len) = opt.pad_len {
+            let (cols, _) = padding_system(
+                &crc32, prefix, postfix, &vec![pad_byte; len],
+                pad_positions, opt.target_mask);
+            let exists = solvable.iter().any(|&(t, m)| {
+                let (cols, base) = padding_system(
+                    &crc32, prefix, postfix, &vec![pad_byte; len],
+                    pad_positions, m);
+                gf2::solve(&cols, ((t ^ base) & m) as u64).is_some()
+            });
+            eprintln!("{}estimate: rank {} of {} constrained bits \
+                    with {} free bytes, P(success) = {}",
+                label, gf2::rank(&cols), bits, pad_positions.len(),
+                if exists {
+                    "100%"
+                } else {
+                    "0%, will need more free bytes"
+                });
+        } else if strategy == Strategy::Algebraic {
+            // affine charsets can be analyzed exactly
+            let len = solve_min_len(charset, crc32.width(), opt.target_mask);
+            let (cols, _) = system(
+                &crc32, prefix, charset, postfix, len, opt.target_mask);
+            let exists = solvable.iter().any(|&(t, m)| {
+                let (cols, base) = system(
+                    &crc32, prefix, charset, postfix, len, m);
+                gf2::solve(&cols, ((t ^ base) & m) as u64).is_some()
+            });
+            eprintln!("{}estimate: rank {} of {} constrained bits \
+                    with {} characters, P(success) = {}",
+                label, gf2::rank(&cols), bits, len,
+                if exists {
+                    "100%"
+                } else {
+                    "0%, will need more characters"
+                });
+        } else {
+            let candidates = if numeric {
+                let range = opt.numeric_range.clone().unwrap_or(0 ..= u64::MAX);
+                let hi = match opt.numeric_digits {
+                    Some(d) => 10u64.checked_pow(d as u32)
+                        .map_or(u64::MAX, |x| x-1)
+                        .min(*range.end()),
+                    None => *range.end(),
+                };
+                (hi.saturating_sub(*range.start()) as f64) + 1.0
+            } else {
+                charset.space(charset.suffix_len(crc32.width())) as f64
+            };
+            eprintln!("{}estimate: 2^{:.2} candidates, {} constrained bits, \
+                    {} accepted values, P(success) = {:.6}%",
+                label, candidates.log2(), bits, accepted,
+                100.0*analyze::success_probability(candidates, accepted, bits));
+        }
+    }
+
+    // which candidate indices are we searching?
+    let domain = if numeric {
+        let range = opt.numeric_range.clone().unwrap_or(0 ..= u64::MAX);
+        let hi = match opt.numeric_digits {
+            Some(d) => 10u64.checked_pow(d as u32).map_or(u64::MAX, |x| x-1),
+            None => u64::MAX,
+        };
+        *range.start() ..= (*range.end()).min(hi)
+    } else {
+        0 ..= charset.space(charset.suffix_len(crc32.width())).saturating_sub(1)
+    };
+    let range = match &opt.range {
+        Some(range) => {
+            (*range.start()).max(*domain.start())
+                ..= (*range.end()).min(*domain.end())
+        }
+        None => domain.clone(),
+    };
+
+    // each shard gets an equal slice of our range, with more shards than
+    // candidates some shards get nothing, but we still go through the
+    // motions so they report no solution
+    let range = match opt.shard {
+        Some((i, n)) => coverage::shard(&range, i, n)
+            .unwrap_or_else(|| {
+                let lo = (*range.start()).max(1);
+                lo ..= lo-1
+            }),
+        None => range,
+    };
+
+    // identify the search so we don't mix up unrelated coverage files
+    let search = format!("poly=0x{:x} width={} init=0x{:x} xorout=0x{:x} \
+            refin={} refout={} charset={} numeric={} \
+            prefix={} postfix={} targets={} mask=0x{:08x} hd={}{}{}{}{}{}{}",
+        polynomial,
+        crc32.width(),
+        // with --prefix-bits this is the CRC of our partial prefix
+        crc32.init(),
+        crc32.xorout(),
+        crc32.refin(),
+        crc32.refout(),
+        charset,
+        match (numeric, opt.numeric_digits) {
+            (false, _) => "no".into(),
+            (true, None) => "any".into(),
+            (true, Some(d)) => d.to_string(),
+        },
+        // long prefixes would make for huge identities, so we settle for
+        // their length and CRC
+        if prefix.len() <= 4096 {
+            coverage::hex(prefix)
+        } else {
+            format!("{}:0x{:08x}", prefix.len(),
+                crc32.crc32(crc32.init(), prefix))
+        },
+        coverage::hex(postfix),
+        exact.iter()
+            .map(|t| format!("0x{:08x}", t))
+            .chain(ranges.iter()
+                .map(|(lo, hi)| format!("0x{:08x}..=0x{:08x}", lo, hi)))
+            .collect::<Vec<_>>()
+            .join(","),
+        opt.target_mask,
+        opt.target_hd,
+        if predicates.is_empty() {
+            "".into()
+        } else {
+            format!(" predicates={}", predicates.iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(","))
+        },
+        if opt.wire.is_empty() {
+            "".into()
+        } else {
+            format!(" wire={}", opt.wire.iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(","))
+        },
+        match seed {
+            Some(seed) => format!(" seed=0x{:x}", seed),
+            None => "".into(),
+        },
+        if include.is_empty() {
+            "".into()
+        } else {
+            format!(" include={}", include.iter()
+                .map(|r| format!("{}..={}", r.start(), r.end()))
+                .collect::<Vec<_>>()
+                .join(","))
+        },
+        match opt.pad_len {
+            Some(len) => format!(" pad={}:0x{:02x}:{}", len, pad_byte,
+                pad_positions.iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")),
+            None => "".into(),
+        },
+        match opt.prefix_bits {
+            Some(bits) => format!(" prefix-bits={}", bits),
+            None => "".into(),
+        });
+
+    // carve out anything we've been told to skip, what's left is what we
+    // actually search
+    let load = |path: &Path| -> Result<Coverage, String> {
+        fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|s| Coverage::parse(&s))
+            .and_then(|other| if other.search == search {
+                Ok(other)
+            } else {
+                Err(format!("coverage is for a different search:\n  {}",
+                    other.search))
+            })
+            .map_err(|err| format!("{}: {}", path.display(), err))
+    };
+    let mut skip = Coverage::new(search.clone(), range.clone());
+    skip.covered.extend(opt.exclude.iter().cloned());
+    for path in &opt.exclude_coverage {
+        skip.covered.extend(load(path)?.covered);
+    }
+
+    // and anything we already covered before a checkpoint, this stays
+    // in our checkpoints
+    let resumed = opt.resume.as_deref().map(load).transpose()?
+        .map_or(vec![], |resumed| resumed.covered);
+    skip.covered.extend(resumed.iter().cloned());
+    skip.normalize();
+
+    // measure how fast we search a bit of our range, and extrapolate
+    let threads = opt.threads.unwrap_or_else(physical_cores).max(1);
+    if opt.dry_run {
+        // only brute force searches enumerate enough candidates to be
+        // worth calibrating, for everything else say what we'd do
+        let len = charset.suffix_len(crc32.width());
+        let plan = if opt.pad_len.is_some() {
+            Some("padding, solved directly as a linear system".into())
+        } else {
+            match strategy {
+                Strategy::Algebraic => Some(format!(
+                    "algebraic, charset {} is affine, so we solve for our \
+                        suffix directly", charset)),
+                Strategy::Mitm => Some(format!(
+                    "mitm, charset {} isn't affine, so we table {} first \
+                        halves and look up to {} second halves",
+                    charset,
+                    charset.space(len/2),
+                    charset.space(len - len/2))),
+                Strategy::Sat => Some(
+                    "sat, our suffix is handed off to a sat solver".into()),
+                _ => None,
+            }
+        };
+        if let Some(plan) = plan {
+            eprintln!("{}dry run: {}", label, plan);
+            return Ok(true);
+        }
+
+        let remaining = skip.remaining();
+        let total = remaining.iter()
+            .map(|r| (r.end() - r.start()) as f64 + 1.0)
+            .sum::<f64>();
+        let lo = remaining.first().map_or(0, |r| *r.start());
+        let start = Instant::now();
+        let mut checked = 0u64;
+        while start.elapsed() < Duration::from_millis(250)
+                && lo + checked <= *domain.end() {
+            let range = lo + checked
+                ..= (lo + checked).saturating_add(0xfffff).min(*domain.end());
+            checked += range.end() - range.start() + 1;
+
+            // never accept, but don't let the compiler know that, or it
+            // skips our CRCs entirely
+            let accept = |crc: u32| {
+                std::hint::black_box(crc);
+                false
+            };
+            if numeric {
+                brute_numeric(&crc32, prefix, postfix, range,
+                    opt.numeric_digits, 1, &|_| {}, accept);
+            } else {
+                brute(&crc32, prefix, charset, postfix, range,
+                    1, None, &|_| {}, accept);
+            }
+        }
+        let rate = checked as f64 / start.elapsed().as_secs_f64().max(1e-9);
+
+        // candidates until our first solution are about geometric
+        let p = accepted / 2f64.powi(bits as i32);
+        let expected = if p > 0.0 { (1.0 / p).min(total) } else { total };
+        let secs = |n: f64| Duration::from_secs_f64(
+            (n / (rate * threads as f64)).min(u64::MAX as f64));
+        eprintln!("{}dry run: {:.0} candidates/s per thread, {} threads",
+            label, rate, threads);
+        if opt.range.is_some() || opt.shard.is_some() {
+            if range.is_empty() {
+                eprintln!("{}dry run: range is empty", label);
+            } else {
+                eprintln!("{}dry run: range 0x{:x}..=0x{:x}",
+                    label, range.start(), range.end());
+            }
+        }
+        eprintln!("{}dry run: expect a solution after 2^{:.2} candidates, \
+                {}, exhausting 2^{:.2} candidates takes {}",
+            label,
+            expected.log2(), progress::format_duration(secs(expected)),
+            total.log2(), progress::format_duration(secs(total)));
+        return Ok(true);
+    }
+
+    // mitm searches need a table for each mask, loaded from our table
+    // file if we have one
+    let mut tables = vec![];
+    if strategy == Strategy::Mitm {
+        let mut masks = solvable.iter().map(|&(_, m)| m).collect::<Vec<_>>();
+        masks.sort_unstable();
+        masks.dedup();
+        if opt.mitm_table.is_some() && masks.len() > 1 {
+            return Err("--mitm-table only supports a single target \
+                mask".into());
+        }
+        for mask in masks {
+            tables.push((mask, load_mitm_table(
+                opt, polynomial, &crc32, charset, postfix, mask)?));
+        }
+    }
+
+    let search_range = |
+        range: RangeInclusive<u64>,
+        checkpoint: &(dyn Fn(u64) + Sync),
+    | match strategy {
+        Strategy::Algebraic => solvable.iter().find_map(|&(t, m)| solve(
+            &crc32, prefix, charset, postfix, t, m, seed
+        )).map(|suffix| (0, suffix)),
+        Strategy::Mitm => solvable.iter().find_map(|&(t, m)| mitm(
+            &crc32, prefix, charset, postfix,
+            &tables.iter().find(|t| t.0 == m).unwrap().1, t, m
+        )).map(|suffix| (0, suffix)),
+        #[cfg(feature="sat")]
+        Strategy::Sat => {
+            let allowed = (0..=255).filter(|&b| charset.contains(b))
+                .collect::<Vec<_>>();
+            solvable.iter().find_map(|&(t, m)| sat::solve(&Constraints::new(
+                &crc32, prefix, postfix, charset.suffix_len(crc32.width()), t, m, &allowed
+            ))).map(|suffix| (0, suffix))
+        }
+        _ if numeric => brute_numeric(
+            &crc32, prefix, postfix,
+            range,
+            opt.numeric_digits,
+            threads,
+            checkpoint,
+            |crc| hit(crc).is_some() || in_range(crc).is_some()
+                || satisfies(crc)
+        ),
+        _ if !ranges.is_empty() || !predicates.is_empty() => brute(
+            &crc32, prefix, charset, postfix, range, threads, seed, checkpoint,
+            |crc| hit(crc).is_some() || in_range(crc).is_some()
+                || satisfies(crc)
+        ),
+        // for exact matches we can compare directly, or binary search
+        // with many targets, this loop is hot so it matters
+        _ if opt.target_hd == 0 && exact.len() == 1 => brute(
+            &crc32, prefix, charset, postfix, range, threads, seed, checkpoint,
+            |crc| crc & opt.target_mask == exact[0]
+        ),
+        _ if opt.target_hd == 0 => brute(
+            &crc32, prefix, charset, postfix, range, threads, seed, checkpoint,
+            |crc| exact.binary_search(&(crc & opt.target_mask)).is_ok()
+        ),
+        _ => brute(
+            &crc32, prefix, charset, postfix, range, threads, seed, checkpoint,
+            |crc| hit(crc).is_some()
+        ),
+    };
+
+    let mut coverage = Coverage::new(search, domain);
+    let mut found = None;
+    let start = Instant::now();
+    if let Some(len) = opt.pad_len {
+        let padding = vec![pad_byte; len];
+        found = solvable.iter().find_map(|&(t, m)| solve_padding(
+            &crc32, prefix, postfix, &padding, pad_positions, t, m
+        )).map(|suffix| (0, suffix));
+    } else {
+        // checkpoints are everything we've covered so far, plus what we
+        // resumed from
+        let write_checkpoint = |covered: &Coverage| -> Result<(), String> {
+            let path = match &opt.checkpoint {
+                Some(path) => path,
+                None => return Ok(()),
+            };
+            let mut checkpoint = covered.clone();
+            checkpoint.covered.extend(resumed.iter().cloned());
+            checkpoint.normalize();
+            fs::write(path, checkpoint.to_string())
+                .map_err(|err| format!("{}: {}", path.display(), err))
+        };
+        // checkpoints are written from our search threads, so a failed
+        // write stops our search and is reported after
+        let failed = Mutex::new(None);
+
+        // only search as many candidates as we're allowed, noting where
+        // we ran out
+        let mut remaining = skip.remaining();
+        let mut budget = None;
+        if let Some(max) = opt.max_iters {
+            let mut left = max;
+            let mut kept = vec![];
+            for r in remaining {
+                let n = (r.end() - r.start()).saturating_add(1);
+                if left >= n {
+                    left -= n;
+                    kept.push(r);
+                    continue;
+                }
+                if left > 0 {
+                    kept.push(*r.start() ..= r.start() + (left-1));
+                }
+                budget = Some(r.start() + left);
+                break;
+            }
+            remaining = kept;
+        }
+
+        let stopped_at = AtomicU64::new(0);
+        for range in remaining {
+            stopped_at.store(*range.start(), Ordering::Relaxed);
+            found = search_range(range.clone(), &|done| {
+                stopped_at.store(done, Ordering::Relaxed);
+                let mut covered = coverage.clone();
+                if done > *range.start() {
+                    covered.covered.push(*range.start() ..= done-1);
+                }
+                if let Err(err) = write_checkpoint(&covered) {
+                    failed.lock().unwrap().get_or_insert(err);
+                    progress::stop();
+                }
+            });
+            if let Some(err) = failed.lock().unwrap().take() {
+                return Err(err);
+            }
+            // a stopped search only covered up to where it stopped
+            if found.is_none() && progress::stopped() {
+                let at = stopped_at.load(Ordering::Relaxed);
+                if at > *range.start() {
+                    coverage.covered.push(*range.start() ..= at-1);
+                }
+                break;
+            }
+            match &found {
+                Some((i, suffix)) => {
+                    coverage.covered.push(*range.start() ..= *i);
+                    coverage.solutions.insert(*i, suffix.clone());
+                    break;
+                }
+                None => coverage.covered.push(range),
+            }
+        }
+
+        write_checkpoint(&coverage)?;
+        // other searches in our batch may run out of budget, so only
+        // trust our process's flag if our own --max-time set it
+        let mut expired = opt.max_time.is_some() && progress::expired();
+        if let (None, Some(at)) = (&found, budget) {
+            if !progress::stopped() {
+                stopped_at.store(at, Ordering::Relaxed);
+                progress::expire();
+                expired = true;
+            }
+        }
+
+        // when searching by hand, say where we stopped so the next search
+        // knows where to pick up
+        if found.is_none() && (progress::interrupted() || expired) {
+            let why = if progress::interrupted() {
+                "interrupted"
+            } else {
+                "out of budget"
+            };
+
+            // only brute force searches know where they stopped
+            let at = stopped_at.load(Ordering::Relaxed);
+            let resume = match &opt.checkpoint {
+                _ if strategy != Strategy::Brute => None,
+                Some(path) => Some(format!("--resume={}", path.display())),
+                None if range.end() < coverage.domain.end() => Some(format!(
+                    "--start=0x{:x} --end=0x{:x}", at, range.end()+1)),
+                None => Some(format!("--start=0x{:x}", at)),
+            };
+            let mut covered = coverage.clone();
+            covered.normalize();
+            match resume {
+                Some(resume) => eprintln!("{}{} at 0x{:x} after {} \
+                        candidates in {:.0?}, resume with {}",
+                    label, why, at, covered.count(), start.elapsed(),
+                    resume),
+                None => eprintln!("{}{} after {:.0?}",
+                    label, why, start.elapsed()),
+            }
+        } else if found.is_none() && progress::stopped() {
+            eprintln!("{}stopped early, a solution was found elsewhere",
+                label);
+        } else if opt.range.is_some() || opt.shard.is_some() {
+            match &found {
+                _ if range.is_empty() => eprintln!("{}range is empty",
+                    label),
+                Some((i, _)) => eprintln!("{}range 0x{:x}..=0x{:x}, \
+                        stopped at 0x{:x}",
+                    label, range.start(), range.end(), i),
+                None => eprintln!("{}range 0x{:x}..=0x{:x}, exhausted",
+                    label, range.start(), range.end()),
+            }
+        }
+    }
+
+    // our counters can be derived from what we covered, which keeps them
+    // out of our hot loop
+    if opt.stats {
+        let mut stats = Stats{
+            solutions: found.is_some() as u64,
+            backend: crc32.backend(),
+            elapsed: start.elapsed(),
+            ..Stats::default()
+        };
+        if opt.pad_len.is_none() && strategy == Strategy::Brute {
+            let mut covered = coverage.clone();
+            covered.normalize();
+            let lens = if numeric {
+                covered.covered.iter()
+                    .flat_map(|r| digit_counts(r, opt.numeric_digits))
+                    .collect::<Vec<_>>()
+            } else {
+                vec![(charset.suffix_len(crc32.width()), covered.count())]
+            };
+            let candidates = lens.iter().map(|(_, n)| n).sum();
+            stats.candidates.push(candidates);
+            for (len, n) in lens {
+                stats.bytes += n * (len + postfix.len()) as u64;
+                stats.pmuls += n
+                    * (crc32.pmuls(len) + crc32.pmuls(postfix.len()));
+            }
+        }
+        stats.report(label);
+    }
+
+    if let Some(path) = &opt.coverage {
+        coverage.normalize();
+        if let Err(err) = fs::write(path, coverage.to_string()) {
+            return Err(format!("{}: {}", path.display(), err));
+        }
+    }
+
+    // explain our math, padding is a different system, so only for
+    // suffixes
+    if opt.explain && opt.pad_len.is_none() {
+        let len = match &found {
+            Some((_, suffix)) => suffix.len(),
+            None => charset.suffix_len(crc32.width()),
+        };
+        explain(&crc32, prefix, len, postfix, &exact, opt.target_mask,
+            found.as_ref().map(|(_, suffix)| &suffix[..]),
+            label);
+    }
+
+    let suffix = match found {
+        Some((_, suffix)) => suffix,
+        None => {
+            eprintln!("{}no solution found", label);
+            return Ok(false);
+        }
+    };
+
+    // our suffix is in the CRC's view, undo that for our message
+    let msg = message(opt,
+        dataprefix,
+        &wire::unapply(&opt.wire, &suffix),
+        datapostfix);
+    print_labeled(label, msg.clone());
+
+    // let any other shards know we're done
+    if let Some(path) = &opt.found_file {
+        if let Err(err) = fs::write(path, msg.collect::<Vec<_>>()) {
+            return Err(format!("{}: {}", path.display(), err));
+        }
+    }
+
+    // validate that the checksum matches
+    let crc = crc32.crc32(crc32.crc32(crc32.crc32(crc32.init(),
+        prefix),
+        &suffix),
+        postfix);
+    match (hit(crc), in_range(crc), satisfies(crc)) {
+        (Some(target), _, _) => {
+            let hd = ((crc ^ target) & opt.target_mask).count_ones();
+            if targets.len() > 1
+                    || opt.target_hd > 0
+                    || !ranges.is_empty()
+                    || !predicates.is_empty() {
+                eprintln!("{}crc 0x{:08x}, target 0x{:08x}, \
+                        hamming distance {}",
+                    label, crc, target, hd);
+            }
+        }
+        (None, Some((lo, hi)), _) => {
+            eprintln!("{}crc 0x{:08x}, range 0x{:08x}..=0x{:08x}",
+                label, crc, lo, hi);
+        }
+        (None, None, true) => {
+            eprintln!("{}crc 0x{:08x}, predicates {}",
+                label, crc, predicates.iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","));
+        }
+        (None, None, false) => unreachable!(),
+    }
+
+    if opt.verbose {
+        print_state(&crc32, prefix, &suffix, label);
+    }
+
+    Ok(true)
+}
+
+// explain how our prefix and postfix offset the CRC of our suffix, see
+// suffix_offset, and why our suffix works if we found one
+//
+// crc' here is our CRC continued from zero, without our init
+#[allow(clippy::too_many_arguments)]
+fn explain(
+    crc32: &Crc32,
+    prefix: &[u8],
+    len: usize,
+    postfix: &[u8],
+    targets: &[u32],
+    mask: u32,
+    suffix: Option<&[u8]>,
+    label: &str,
+) {
+    let zeros = vec![0; len];
+    let before = crc32.crc32(crc32.init(), prefix);
+    let x = crc32.crc32(crc32.crc32(before, &zeros), postfix);
+    let c = crc32.crc32(crc32.crc32(0, &zeros), postfix);
+    let offset = x ^ c;
+    eprintln!("{}explain: crc(prefix) = 0x{:08x}", label, before);
+    eprintln!("{}explain: crc(prefix || {} zeros || postfix) = 0x{:08x}",
+        label, len, x);
+    eprintln!("{}explain: crc'({} zeros || postfix) = 0x{:08x}",
+        label, len, c);
+    eprintln!("{}explain: crcs are affine, so for any {}-byte suffix s",
+        label, len);
+    eprintln!("{}explain:   crc(prefix || s || postfix) \
+            = 0x{:08x} ^ 0x{:08x} ^ crc'(s || postfix)",
+        label, x, c);
+    eprintln!("{}explain:                               \
+            = 0x{:08x} ^ crc'(s || postfix)",
+        label, offset);
+    let mask = mask & crc32.mask();
+    for &t in targets {
+        if mask == crc32.mask() {
+            eprintln!("{}explain: target 0x{:08x} needs \
+                    crc'(s || postfix) = 0x{:08x}",
+                label, t, t ^ offset);
+        } else {
+            eprintln!("{}explain: target 0x{:08x} needs \
+                    crc'(s || postfix) & 0x{:08x} = 0x{:08x}",
+                label, t, mask, (t ^ offset) & mask);
+        }
+    }
+
+    if let Some(suffix) = suffix {
+        let y = crc32.crc32(crc32.crc32(0, suffix), postfix);
+        eprintln!("{}explain: our suffix has crc'(s || postfix) = 0x{:08x}",
+            label, y);
+        eprintln!("{}explain: and 0x{:08x} ^ 0x{:08x} = 0x{:08x}",
+            label, offset, y, offset ^ y);
+    }
+}
+
+// print the CRC register before and after our suffix, our register is
+// reflected and xored with xorout on the way out, so undo the xorout
+fn print_state(crc32: &Crc32, prefix: &[u8], suffix: &[u8], label: &str) {
+    let before = crc32.crc32(crc32.init(), prefix);
+    let after = crc32.crc32(before, suffix);
+    eprintln!("{}state before 0x{:08x}, after 0x{:08x}",
+        label, before ^ crc32.xorout(), after ^ crc32.xorout());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the CRC of a whole message
+    fn crc(crc32: &Crc32, parts: &[&[u8]]) -> u32 {
+        crc32.crc32(crc32.init(), &parts.concat())
+    }
+
+    #[test]
+    fn solve_round_trips() {
+        let crc32 = Crc32::new(0x104c11db7);
+        for charset in [Charset::Raw, Charset::Whitespace, Charset::Octal,
+                Charset::SevenBit] {
+            for (target, mask) in [(0, !0), (0x12345678, !0),
+                    (0xcafe, 0xffff)] {
+                let suffix = solve(&crc32, b"hello", charset, b"\n", target,
+                    mask, None).unwrap();
+                assert!(suffix.iter().all(|&b| charset.contains(b)),
+                    "{} {:02x?}", charset, suffix);
+                assert_eq!(crc(&crc32, &[b"hello", &suffix, b"\n"]) & mask,
+                    target, "{}", charset);
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_polynomials() {
+        // our top bit can be left out if we know our width
+        assert_eq!(resolve_polynomial(0x104c11db7, None),
+            Ok((0x104c11db7, 32)));
+        assert_eq!(resolve_polynomial(0x04c11db7, Some(32)),
+            Ok((0x104c11db7, 32)));
+        assert_eq!(resolve_polynomial(0x104c11db7, Some(32)),
+            Ok((0x104c11db7, 32)));
+        assert_eq!(resolve_polynomial(0x1021, Some(16)), Ok((0x11021, 16)));
+        assert!(resolve_polynomial(0x1021, Some(8)).is_err());
+        assert!(resolve_polynomial(0x1000000000, None).is_err());
+        assert!(resolve_polynomial(1, None).is_err());
+    }
+
+    #[test]
+    fn unreverse_polynomials() {
+        assert_eq!(unreverse_polynomial(0xedb88320, None), Ok(0x104c11db7));
+        assert_eq!(unreverse_polynomial(0x82f63b78, None), Ok(0x11edc6f41));
+        assert_eq!(unreverse_polynomial(0xa001, None), Ok(0x18005));
+        assert_eq!(unreverse_polynomial(0x8408, None), Ok(0x11021));
+        assert_eq!(unreverse_polynomial(0x8408, Some(16)), Ok(0x11021));
+        assert!(unreverse_polynomial(0x1ffff, Some(16)).is_err());
+        assert!(unreverse_polynomial(0, None).is_err());
+    }
+
+    #[test]
+    fn solve_with_sum_round_trips() {
+        let crc32 = Crc32::new(0x104c11db7);
+        for charset in [Charset::Raw, Charset::Ascii] {
+            for sum in ["sum8=0x42", "sum16=0xbeef", "lrc=0"] {
+                let with_sum = sum.parse::<SumTarget>().unwrap();
+                let (suffix, _) = solve_with_sum(&crc32, b"hello", charset,
+                    b"\n", &[0x12345678], !0, with_sum).unwrap();
+                assert!(suffix.iter().all(|&b| charset.contains(b)),
+                    "{} {:02x?}", charset, suffix);
+                let whole = [&b"hello"[..], &suffix, b"\n"].concat();
+                assert_eq!(crc(&crc32, &[&whole]), 0x12345678, "{}", sum);
+                assert_eq!(with_sum.sum.sum(&whole), with_sum.target,
+                    "{}", sum);
+            }
+        }
+    }
+
+    #[test]
+    fn solve64_round_trips() {
+        // CRC-64/XZ
+        let crc64 = Crc64::new(0x142f0e1eba9ea3693);
+        for charset in [Charset::Raw, Charset::Whitespace, Charset::Octal,
+                Charset::SevenBit] {
+            for (target, mask) in [(0, !0), (0x0123456789abcdef, !0),
+                    (0xcafe, 0xffff)] {
+                let suffix = solve64(&crc64, b"hello", charset, b"\n",
+                    target, mask).unwrap();
+                assert!(suffix.iter().all(|&b| charset.contains(b)),
+                    "{} {:02x?}", charset, suffix);
+                let whole = [&b"hello"[..], &suffix, b"\n"].concat();
+                assert_eq!(crc64.crc64(crc64.init(), &whole) & mask, target,
+                    "{}", charset);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_and_crc() {
+        let and_crc = "crc32c=0x1234".parse::<AndCrc>().unwrap();
+        assert_eq!(and_crc.polynomial, 0x11edc6f41);
+        assert_eq!(and_crc.target, 0x1234);
+        assert_eq!(and_crc.crc32().crc32(and_crc.crc32().init(),
+            b"123456789"), 0xe3069283);
+
+        let and_crc = "0x11021=0xbeef".parse::<AndCrc>().unwrap();
+        // a bare polynomial defaults to all ones and reflected, CRC-16/X-25
+        assert_eq!(and_crc.crc32().crc32(and_crc.crc32().init(),
+            b"123456789"), 0x906e);
+
+        assert!("crc32c".parse::<AndCrc>().is_err());
+        assert!("crc-64/xz=0".parse::<AndCrc>().is_err());
+        assert!("0x11021=0x10000".parse::<AndCrc>().is_err());
+        assert!("bogus=0".parse::<AndCrc>().is_err());
+    }
+
+    #[test]
+    fn and_crc_round_trips() {
+        // stack CRC-32 and CRC-32C as in forge_and_crc
+        let crcs = [Crc32::new(0x104c11db7), Crc32::new(0x11edc6f41)];
+        let both = |suffix: &[u8]| -> u64 {
+            let [a, b] = [0, 1].map(|i| crc(&crcs[i],
+                &[b"hello", suffix, b"\n"]));
+            a as u64 | ((b as u64) << 32)
+        };
+        for charset in [Charset::Raw, Charset::Whitespace] {
+            let suffix = solve_chars(charset, 64, both,
+                0x12345678 | (0x9abcdef0 << 32)).unwrap();
+            assert!(suffix.iter().all(|&b| charset.contains(b)),
+                "{} {:02x?}", charset, suffix);
+            assert_eq!(crc(&crcs[0], &[b"hello", &suffix, b"\n"]),
+                0x12345678);
+            assert_eq!(crc(&crcs[1], &[b"hello", &suffix, b"\n"]),
+                0x9abcdef0);
+        }
+    }
+
+    #[test]
+    fn mitm_round_trips() {
+        let crc32 = Crc32::new(0x104c11db7);
+        for (charset, target, mask) in [(Charset::Raw, 0x12345678, !0),
+                (Charset::Hex, 0xcafe, 0xffff)] {
+            let table = table::Table::new(String::new(),
+                mitm_table(&crc32, charset, b"\n", mask));
+            let suffix = mitm(&crc32, b"hello", charset, b"\n", &table,
+                target, mask).unwrap();
+            assert!(suffix.iter().all(|&b| charset.contains(b)),
+                "{} {:02x?}", charset, suffix);
+            assert_eq!(crc(&crc32, &[b"hello", &suffix, b"\n"]) & mask,
+                target, "{}", charset);
+        }
+    }
+
+    #[test]
+    fn stride_visits_everything() {
+        let mut rng = Rng::new(1);
+        for space in [1, 2, 10, 97, 360, 1 << 12] {
+            let stride = Stride::random(space, &mut rng);
+            let mut seen = vec![false; space as usize];
+            let mut j = stride.at(0);
+            for i in 0..space {
+                assert_eq!(j, stride.at(i));
+                assert!(!seen[j as usize], "space {} visits {} twice",
+                    space, j);
+                seen[j as usize] = true;
+                j = stride.next(j);
+            }
+        }
+    }
+
+    #[test]
+    fn seeded_solve_round_trips() {
+        let crc32 = Crc32::new(0x104c11db7);
+        for charset in [Charset::Whitespace, Charset::SevenBit] {
+            for seed in 0..8 {
+                let suffix = solve(&crc32, b"hello", charset, b"\n",
+                    0x12345678, !0, Some(seed)).unwrap();
+                assert!(suffix.iter().all(|&b| charset.contains(b)),
+                    "{} {:02x?}", charset, suffix);
+                assert_eq!(crc(&crc32, &[b"hello", &suffix, b"\n"]),
+                    0x12345678, "{} seed {}", charset, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn checkpoints_are_searched() {
+        const BLOCK: u64 = 0x10000;
+        progress::checkpoint_every(Duration::from_millis(1));
+        for threads in [1, 3] {
+            // blocks we've finished, and checkpoints we've taken
+            let searched = Mutex::new(vec![]);
+            let checkpoints = Mutex::new(vec![]);
+            let found = search_blocks(0, 16*BLOCK-1, threads,
+                &|i| {
+                    let searched = searched.lock().unwrap();
+                    for block in (0..i).step_by(BLOCK as usize) {
+                        assert!(searched.contains(&block),
+                            "checkpoint 0x{:x} before 0x{:x}", i, block);
+                    }
+                    checkpoints.lock().unwrap().push(i);
+                },
+                |lo, hi| {
+                    thread::sleep(Duration::from_millis(2));
+                    searched.lock().unwrap().push(lo);
+                    (hi == 16*BLOCK-1).then(|| (hi, vec![]))
+                });
+            assert_eq!(found, Some((16*BLOCK-1, vec![])));
+
+            // with more threads, checkpoints can race to our closure
+            let checkpoints = checkpoints.into_inner().unwrap();
+            assert!(!checkpoints.is_empty());
+            assert!(threads > 1
+                    || checkpoints.windows(2).all(|w| w[0] <= w[1]),
+                "{:x?}", checkpoints);
+        }
+    }
+
+    #[test]
+    fn unescape_round_trips() {
+        assert_eq!(unescape(r"a\n\r\t\0\\\x7fz"),
+            Ok(b"a\n\r\t\0\\\x7fz".to_vec()));
+
+        // escape every byte as print_escaped does
+        let data = (0..=255u8).collect::<Vec<_>>();
+        let escaped = data.iter()
+            .map(|&b| match b {
+                b'\\' => r"\\".to_string(),
+                b' '..=b'~' => (b as char).to_string(),
+                _ => format!("\\x{:02x}", b),
+            })
+            .collect::<String>();
+        assert_eq!(unescape(&escaped), Ok(data));
+
+        assert!(unescape(r"trailing\").is_err());
+        assert!(unescape(r"\x4").is_err());
+        assert!(unescape(r"\xzz").is_err());
+        assert!(unescape(r"\q").is_err());
+    }
+
+    #[test]
+    fn encode_prefixes() {
+        // é is two bytes in UTF-8, one unit in UTF-16, and 😀 is a
+        // surrogate pair
+        let s = "hé😀";
+        assert_eq!(Encoding::Utf8.encode(s), s.as_bytes());
+        assert_eq!(Encoding::Utf16le.encode(s),
+            [0x68, 0x00, 0xe9, 0x00, 0x3d, 0xd8, 0x00, 0xde]);
+        assert_eq!(Encoding::Utf16be.encode(s),
+            [0x00, 0x68, 0x00, 0xe9, 0xd8, 0x3d, 0xde, 0x00]);
+
+        assert_eq!("utf-16le".parse(), Ok(Encoding::Utf16le));
+        assert_eq!("utf16be".parse(), Ok(Encoding::Utf16be));
+        assert!("latin1".parse::<Encoding>().is_err());
+    }
+
+    #[test]
+    fn prefixes_keep_their_order() {
+        let opt = Opt::from_iter_ordered(["crcbrute",
+            "--prefix-hex", "6865", "--prefix", "ll", "--ascii",
+            "--prefix-base64=bw==", "--prefix-hex", "0a", "0x1234"]).unwrap();
+        assert_eq!(opt.prefixes, [
+            Prefix::Hex("6865".into()),
+            Prefix::Text("ll".into()),
+            Prefix::Base64("bw==".into()),
+            Prefix::Hex("0a".into()),
+        ]);
+        // with prefix options, our first argument is really a target
+        assert_eq!(opt.prefix.as_deref(), Some("0x1234"));
+
+        let opt = Opt::from_iter_ordered(["crcbrute", "hello"]).unwrap();
+        assert_eq!(opt.prefixes, []);
+        assert_eq!(opt.prefix.as_deref(), Some("hello"));
+    }
+}
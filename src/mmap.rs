@@ -0,0 +1,120 @@
+// Memory-mapped files
+//
+// Prefixes can be multi-gigabyte firmware or disk images. Mapping them
+// lets us hash them without reading them into memory, the kernel pages
+// them in as we go, and since we read front to back, can drop them just
+// as easily, so our memory stays flat.
+//
+// If someone truncates our file while it's mapped we'll crash, and if
+// someone modifies it, our bytes change under us, which Rust considers
+// undefined behavior, so opening a map is unsafe and up to the caller.
+//
+
+use std::fs::{self, File};
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+#[cfg(all(unix, target_pointer_width="64"))]
+mod sys {
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd;
+
+    // avoid pulling in libc for a few functions, these are the same on
+    // linux and the bsds
+    const PROT_READ: i32 = 1;
+    const MAP_PRIVATE: i32 = 2;
+    const MADV_SEQUENTIAL: i32 = 2;
+
+    extern "C" {
+        fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32,
+            fd: i32, off: i64) -> *mut u8;
+        fn munmap(addr: *mut u8, len: usize) -> i32;
+        fn madvise(addr: *mut u8, len: usize, advice: i32) -> i32;
+    }
+
+    pub fn map(file: &File, len: usize) -> Option<*const u8> {
+        let ptr = unsafe {
+            mmap(core::ptr::null_mut(), len, PROT_READ, MAP_PRIVATE,
+                file.as_raw_fd(), 0)
+        };
+        if ptr as isize == -1 {
+            return None;
+        }
+        // this is only a hint, so we don't care if it fails
+        unsafe { madvise(ptr, len, MADV_SEQUENTIAL) };
+        Some(ptr)
+    }
+
+    pub fn unmap(ptr: *const u8, len: usize) {
+        unsafe { munmap(ptr as *mut u8, len) };
+    }
+}
+
+#[cfg(not(all(unix, target_pointer_width="64")))]
+mod sys {
+    use std::fs::File;
+
+    pub fn map(_: &File, _: usize) -> Option<*const u8> {
+        None
+    }
+
+    pub fn unmap(_: *const u8, _: usize) {}
+}
+
+/// A read-only view of a file's bytes, mapped if we can, otherwise read
+/// into memory
+#[derive(Debug)]
+pub struct Mmap {
+    ptr: *const u8,
+    len: usize,
+    data: Vec<u8>,
+}
+
+// we never write through ptr, so sharing it is as safe as sharing a slice
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// Map a file, or read it if we can't
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while mapped, or the
+    /// bytes we hand out may change or disappear under us.
+    pub unsafe fn open(path: &Path) -> io::Result<Mmap> {
+        let file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::other("file too large"))?;
+
+        // empty files can't be mapped, but there's nothing to map anyways
+        match Some(len).filter(|&len| len > 0)
+                .and_then(|len| sys::map(&file, len)) {
+            Some(ptr) => Ok(Mmap{ptr, len, data: vec![]}),
+            None => {
+                let data = fs::read(path)?;
+                Ok(Mmap{ptr: core::ptr::null(), len: data.len(), data})
+            }
+        }
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            &self.data
+        } else {
+            unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
+        }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            sys::unmap(self.ptr, self.len);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-/// Hardware accelerated carry-less multiplication
+//! Hardware accelerated carry-less multiplication
 
 #[inline]
 pub fn pmul64(a: u64, b: u64) -> (u64, u64) {
@@ -34,6 +34,40 @@ pub fn pmul64(a: u64, b: u64) -> (u64, u64) {
         }
     }
 
+    #[cfg(all(
+        not(feature="sw-pmul"),
+        target_arch="s390x",
+        target_feature="vector"
+    ))]
+    {
+        // s390x provides 64-bit xmul via the vgfmg instruction, which
+        // multiplies and xors both doublewords, so we zero one of them
+        //
+        // note vector registers are only stable as clobbers, so we move
+        // everything through general-purpose registers
+        use core::arch::asm;
+        unsafe {
+            let lo: u64;
+            let hi: u64;
+            asm!(
+                "vlvgp %v0, {z}, {a}",
+                "vlvgp %v1, {z}, {b}",
+                "vgfmg %v0, %v0, %v1",
+                "vlgvg {hi}, %v0, 0",
+                "vlgvg {lo}, %v0, 1",
+                a = in(reg) a,
+                b = in(reg) b,
+                z = in(reg) 0u64,
+                lo = lateout(reg) lo,
+                hi = lateout(reg) hi,
+                out("v0") _,
+                out("v1") _,
+                options(pure, nomem, nostack),
+            );
+            (lo, hi)
+        }
+    }
+
     #[cfg(all(
         not(feature="hw-pmul"),
         not(all(
@@ -44,20 +78,67 @@ pub fn pmul64(a: u64, b: u64) -> (u64, u64) {
             not(feature="sw-pmul"),
             target_arch="aarch64",
             target_feature="neon")),
+        not(all(
+            not(feature="sw-pmul"),
+            target_arch="s390x",
+            target_feature="vector")),
     ))]
     {
-        let mut lo = 0;
-        let mut hi = 0;
-        let mut i = 0;
-        while i < 64 {
-            let mask = (((a as i64) << (64-1-i)) >> (64-1)) as u64;
-            lo ^= mask & (b << i);
-            hi ^= mask & (b >> (64-1-i));
-            i += 1;
-        }
-        // note we adjust hi by one here to avoid handlings shifts > word size
-        (lo, hi >> 1)
+        // Karatsuba, this needs 3 32-bit multiplies instead of 4
+        let (a0, a1) = (a as u32, (a >> 32) as u32);
+        let (b0, b1) = (b as u32, (b >> 32) as u32);
+        let lo = pmul32_sw(a0, b0);
+        let hi = pmul32_sw(a1, b1);
+        let mid = pmul32_sw(a0 ^ a1, b0 ^ b1) ^ lo ^ hi;
+        (lo ^ (mid << 32), hi ^ (mid >> 32))
+    }
+}
+
+/// Which carry-less multiply we're using, for comparing machines
+pub const BACKEND: &str = if cfg!(all(
+    not(feature="sw-pmul"),
+    target_arch="x86_64",
+    target_feature="pclmulqdq"
+)) {
+    "pclmulqdq"
+} else if cfg!(all(
+    not(feature="sw-pmul"),
+    target_arch="aarch64",
+    target_feature="neon"
+)) {
+    "pmull"
+} else if cfg!(all(
+    not(feature="sw-pmul"),
+    target_arch="s390x",
+    target_feature="vector"
+)) {
+    "vgfmg"
+} else {
+    "software"
+};
+
+// software 32-bit xmul, used to build our software 64-bit xmul
+//
+// This uses a table of b multiplied by every 4-bit polynomial, so we only
+// need 8 lookups instead of a 32-iteration bit loop. Note the products fit
+// in 35 bits, so everything fits in a u64.
+#[inline]
+fn pmul32_sw(a: u32, b: u32) -> u64 {
+    let b = b as u64;
+    let mut table = [0u64; 16];
+    let mut i = 1;
+    while i < 16 {
+        table[i] = (table[i >> 1] << 1) ^ ((i as u64 & 1).wrapping_neg() & b);
+        i += 1;
     }
+
+    let mut x = 0;
+    let mut i = 0;
+    while i < 32 {
+        x ^= table[((a >> i) & 0xf) as usize] << i;
+        i += 4;
+    }
+    x
 }
 
 #[inline]
@@ -65,3 +146,265 @@ pub fn pmul32(a: u32, b: u32) -> (u32, u32) {
     let (lo, _) = pmul64(a as u64, b as u64);
     (lo as u32, (lo >> 32) as u32)
 }
+
+#[inline]
+pub fn pmul128(a: u128, b: u128) -> (u128, u128) {
+    // Karatsuba, this needs 3 64-bit multiplies instead of 4
+    //
+    // (a1x + a0)(b1x + b0) = a1b1x^2 + ((a1+a0)(b1+b0) - a1b1 - a0b0)x + a0b0
+    //
+    let (a0, a1) = (a as u64, (a >> 64) as u64);
+    let (b0, b1) = (b as u64, (b >> 64) as u64);
+    let (lo_lo, lo_hi) = pmul64(a0, b0);
+    let (hi_lo, hi_hi) = pmul64(a1, b1);
+    let (mid_lo, mid_hi) = pmul64(a0 ^ a1, b0 ^ b1);
+    let mid_lo = mid_lo ^ lo_lo ^ hi_lo;
+    let mid_hi = mid_hi ^ lo_hi ^ hi_hi;
+
+    let lo = (lo_lo as u128)
+        | (((lo_hi ^ mid_lo) as u128) << 64);
+    let hi = ((hi_lo ^ mid_hi) as u128)
+        | ((hi_hi as u128) << 64);
+    (lo, hi)
+}
+
+/// Multiplication in GF(2^width), modulo the given polynomial
+///
+/// Note poly is in normal form, with the x^width term implicit, and a and b
+/// should already be reduced.
+#[inline]
+pub fn gfmul(a: u64, b: u64, poly: u64, width: u32) -> u64 {
+    debug_assert!((1..=64).contains(&width));
+    let (lo, hi) = pmul64(a, b);
+    let mut x = (lo as u128) | ((hi as u128) << 64);
+
+    // reduce by our polynomial
+    let p = (poly as u128) | (1u128 << width);
+    while x >> width != 0 {
+        let shift = (127 - x.leading_zeros()) - width;
+        x ^= p << shift;
+    }
+    x as u64
+}
+
+/// Exponentiation in GF(2^width), modulo the given polynomial
+pub fn gfpow(a: u64, e: u64, poly: u64, width: u32) -> u64 {
+    let mut x = 1;
+    let mut a = a;
+    let mut e = e;
+    while e != 0 {
+        if e & 1 != 0 {
+            x = gfmul(x, a, poly, width);
+        }
+        a = gfmul(a, a, poly, width);
+        e >>= 1;
+    }
+    x
+}
+
+/// Two carry-less multiplications by the same b
+///
+/// This is what batched CRC evaluation needs, since our Barret constants
+/// are shared by every candidate.
+#[inline]
+pub fn pmul64x2(a: [u64; 2], b: u64) -> [(u64, u64); 2] {
+    #[cfg(all(
+        not(feature="sw-pmul"),
+        target_arch="wasm32",
+        target_feature="simd128"
+    ))]
+    {
+        // wasm has no xmul, but with a shared b we can xor shifted copies
+        // of a for each set bit in b, handling both lanes at once
+        use core::arch::wasm32::*;
+        let a = u64x2(a[0], a[1]);
+        let mut lo = u64x2_splat(0);
+        let mut hi = u64x2_splat(0);
+        let mut b = b;
+        while b != 0 {
+            let j = b.trailing_zeros();
+            lo = v128_xor(lo, u64x2_shl(a, j));
+            // shifts are mod 64 in wasm, so shift in two steps
+            hi = v128_xor(hi, u64x2_shr(u64x2_shr(a, 63-j), 1));
+            b &= b - 1;
+        }
+        [
+            (u64x2_extract_lane::<0>(lo), u64x2_extract_lane::<0>(hi)),
+            (u64x2_extract_lane::<1>(lo), u64x2_extract_lane::<1>(hi)),
+        ]
+    }
+
+    #[cfg(all(
+        not(feature="sw-pmul"),
+        target_arch="aarch64",
+        target_feature="neon"
+    ))]
+    {
+        // pmull multiplies the low lanes and pmull2 the high lanes, so
+        // with both candidates in one vector we save moving the second
+        // lane out to a general-purpose register
+        use core::arch::aarch64::*;
+        unsafe {
+            let a_ = vcombine_p64(vcreate_p64(a[0]), vcreate_p64(a[1]));
+            let x0 = vmull_p64(a[0], b);
+            let x1 = vmull_high_p64(a_, vdupq_n_p64(b));
+            [
+                (x0 as u64, (x0 >> 64) as u64),
+                (x1 as u64, (x1 >> 64) as u64),
+            ]
+        }
+    }
+
+    #[cfg(not(any(
+        all(
+            not(feature="sw-pmul"),
+            target_arch="wasm32",
+            target_feature="simd128"),
+        all(
+            not(feature="sw-pmul"),
+            target_arch="aarch64",
+            target_feature="neon"),
+    )))]
+    {
+        [pmul64(a[0], b), pmul64(a[1], b)]
+    }
+}
+
+/// Two 32-bit carry-less multiplications by the same b
+#[inline]
+pub fn pmul32x2(a: [u32; 2], b: u32) -> [(u32, u32); 2] {
+    #[cfg(all(
+        not(feature="sw-pmul"),
+        target_arch="wasm32",
+        target_feature="simd128"
+    ))]
+    {
+        // with 32-bit inputs our products fit in a 64-bit lane, so we
+        // don't need to track the high half
+        use core::arch::wasm32::*;
+        let a = u64x2(a[0] as u64, a[1] as u64);
+        let mut x = u64x2_splat(0);
+        let mut b = b;
+        while b != 0 {
+            x = v128_xor(x, u64x2_shl(a, b.trailing_zeros()));
+            b &= b - 1;
+        }
+        let x0 = u64x2_extract_lane::<0>(x);
+        let x1 = u64x2_extract_lane::<1>(x);
+        [
+            (x0 as u32, (x0 >> 32) as u32),
+            (x1 as u32, (x1 >> 32) as u32),
+        ]
+    }
+
+    #[cfg(all(
+        not(feature="sw-pmul"),
+        target_arch="aarch64",
+        target_feature="neon"
+    ))]
+    {
+        // same here, both products fit in the low half of each lane
+        let [(x0, _), (x1, _)] = pmul64x2(
+            [a[0] as u64, a[1] as u64],
+            b as u64);
+        [
+            (x0 as u32, (x0 >> 32) as u32),
+            (x1 as u32, (x1 >> 32) as u32),
+        ]
+    }
+
+    #[cfg(not(any(
+        all(
+            not(feature="sw-pmul"),
+            target_arch="wasm32",
+            target_feature="simd128"),
+        all(
+            not(feature="sw-pmul"),
+            target_arch="aarch64",
+            target_feature="neon"),
+    )))]
+    {
+        [pmul32(a[0], b), pmul32(a[1], b)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    // a bit at a time, the definition of carry-less multiplication
+    fn naive(a: u128, b: u128) -> (u128, u128) {
+        let (mut lo, mut hi) = (0, 0);
+        for i in 0..128 {
+            if b & (1 << i) != 0 {
+                lo ^= a << i;
+                hi ^= if i == 0 { 0 } else { a >> (128-i) };
+            }
+        }
+        (lo, hi)
+    }
+
+    #[test]
+    fn pmul128_matches_naive() {
+        let mut rng = Rng::new(1);
+        for _ in 0..1000 {
+            let a = ((rng.next_u64() as u128) << 64) | rng.next_u64() as u128;
+            let b = ((rng.next_u64() as u128) << 64) | rng.next_u64() as u128;
+            assert_eq!(pmul128(a, b), naive(a, b), "{:x} {:x}", a, b);
+        }
+        assert_eq!(pmul128(!0, !0), naive(!0, !0));
+    }
+
+    #[test]
+    fn gf256_inverses() {
+        // AES's field, where 0x53 and 0xca are famously inverses
+        assert_eq!(gfmul(0x53, 0xca, 0x1b, 8), 0x01);
+        for a in 1..256 {
+            assert_eq!(gfpow(a, 255, 0x1b, 8), 1, "{:02x}", a);
+            assert_eq!(gfmul(a, gfpow(a, 254, 0x1b, 8), 0x1b, 8), 1);
+        }
+        assert_eq!(gfpow(0, 0, 0x1b, 8), 1);
+        assert_eq!(gfpow(0, 5, 0x1b, 8), 0);
+    }
+
+    #[test]
+    fn gfpow_matches_gfmul() {
+        let mut rng = Rng::new(2);
+        for width in [1, 7, 32, 63, 64] {
+            let mask = !0u64 >> (64 - width);
+            let poly = rng.next_u64() & mask;
+            let a = rng.next_u64() & mask;
+            let mut x = 1;
+            for e in 0..100 {
+                assert_eq!(gfpow(a, e, poly, width), x, "width {}", width);
+                x = gfmul(x, a, poly, width);
+            }
+        }
+    }
+
+    #[test]
+    fn pmul64_matches_naive() {
+        // with sw-pmul this tests our Karatsuba and nibble tables,
+        // otherwise whatever hardware we have
+        let mut rng = Rng::new(3);
+        for _ in 0..1000 {
+            let (a, b) = (rng.next_u64(), rng.next_u64());
+            let (x, _) = naive(a as u128, b as u128);
+            assert_eq!(pmul64(a, b), (x as u64, (x >> 64) as u64));
+            let (a, b) = (a as u32, b as u32);
+            assert_eq!(pmul32_sw(a, b) as u128, naive(a as u128, b as u128).0);
+        }
+    }
+
+    #[test]
+    fn batched_matches_single() {
+        let mut rng = Rng::new(4);
+        for _ in 0..1000 {
+            let (a0, a1, b) = (rng.next_u64(), rng.next_u64(), rng.next_u64());
+            assert_eq!(pmul64x2([a0, a1], b), [pmul64(a0, b), pmul64(a1, b)]);
+            let (a0, a1, b) = (a0 as u32, a1 as u32, b as u32);
+            assert_eq!(pmul32x2([a0, a1], b), [pmul32(a0, b), pmul32(a1, b)]);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+// Predicates on CRC values
+//
+// Sometimes we don't care about a specific CRC, only that it has some
+// property, such as being printable. Some of these only constrain a few
+// bits, which our solvers can handle directly, the rest need brute force.
+//
+// CRCs narrower than 32 bits only have as many bytes and hex digits as
+// their width needs, and their top byte is the top 8 bits of their width.
+//
+
+use core::fmt;
+use core::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub enum Predicate {
+    /// Every byte is printable ascii
+    Printable,
+    /// Every byte is an ascii letter or digit
+    Alnum,
+    /// Every hex digit is 0-9, so the CRC looks decimal in hex
+    Decimal,
+    /// The hex digits read the same backwards
+    Palindrome,
+    /// The top byte equals this
+    TopByte(u8),
+    /// The bottom byte equals this
+    BottomByte(u8),
+    /// At least this many leading zero bits
+    LeadingZeros(u32),
+    /// Anything else
+    Custom(Arc<dyn Fn(u32) -> bool + Send + Sync>),
+}
+
+impl Predicate {
+    /// A predicate from a closure
+    pub fn from_fn(f: impl Fn(u32) -> bool + Send + Sync + 'static)
+            -> Predicate {
+        Predicate::Custom(Arc::new(f))
+    }
+
+    /// Does this CRC, of this width, satisfy our predicate?
+    #[inline]
+    pub fn test(&self, crc: u32, width: u32) -> bool {
+        let bytes = width.div_ceil(8) as usize;
+        let digits = width.div_ceil(4);
+        match self {
+            Predicate::Printable => crc.to_le_bytes()[..bytes].iter()
+                .all(|b| (b' '..=b'~').contains(b)),
+            Predicate::Alnum => crc.to_le_bytes()[..bytes].iter()
+                .all(|b| b.is_ascii_alphanumeric()),
+            Predicate::Decimal => (0..digits)
+                .all(|i| (crc >> (4*i)) & 0xf < 10),
+            Predicate::Palindrome => (0..digits/2)
+                .all(|i| (crc >> (4*i)) & 0xf
+                    == (crc >> (4*(digits-1-i))) & 0xf),
+            Predicate::TopByte(b) => crc >> width.saturating_sub(8)
+                == *b as u32,
+            Predicate::BottomByte(b) => crc & 0xff == *b as u32,
+            Predicate::LeadingZeros(n) => crc.leading_zeros()
+                >= (*n).min(width) + (32 - width),
+            Predicate::Custom(f) => f(crc),
+        }
+    }
+
+    /// If our predicate only fixes some bits of a CRC of this width, the
+    /// value and mask of those bits, which our solvers can handle directly
+    pub fn as_mask(&self, width: u32) -> Option<(u32, u32)> {
+        let crc_mask = !0u64 >> (64 - width);
+        match self {
+            Predicate::TopByte(b) => {
+                let shift = width.saturating_sub(8);
+                Some(((*b as u32) << shift, ((0xff << shift) & crc_mask) as u32))
+            }
+            Predicate::BottomByte(b) => Some((*b as u32,
+                (0xff & crc_mask) as u32)),
+            Predicate::LeadingZeros(n) => Some((0,
+                (crc_mask & !(crc_mask >> (*n).min(width))) as u32)),
+            _ => None,
+        }
+    }
+}
+
+/// Combine predicates that only fix some bits of a CRC of this width into
+/// a single value and mask, returns None if any predicate isn't a mask, or
+/// Some(None) if they conflict, or can't be satisfied at this width
+pub fn as_mask(
+    predicates: &[Predicate],
+    width: u32,
+) -> Option<Option<(u32, u32)>> {
+    let mut value = 0;
+    let mut mask = 0;
+    for p in predicates {
+        let (v, m) = p.as_mask(width)?;
+        if (value ^ v) & mask & m != 0 || v & !m != 0 {
+            return Some(None);
+        }
+        value |= v & m;
+        mask |= m;
+    }
+    Some(Some((value, mask)))
+}
+
+impl fmt::Debug for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Predicate::Custom(_) => write!(f, "Custom(..)"),
+            p => write!(f, "{}", p),
+        }
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Predicate::Printable => write!(f, "printable"),
+            Predicate::Alnum => write!(f, "alnum"),
+            Predicate::Decimal => write!(f, "decimal"),
+            Predicate::Palindrome => write!(f, "palindrome"),
+            Predicate::TopByte(b) => write!(f, "top-byte=0x{:02x}", b),
+            Predicate::BottomByte(b) => write!(f, "bottom-byte=0x{:02x}", b),
+            Predicate::LeadingZeros(n) => write!(f, "leading-zeros={}", n),
+            Predicate::Custom(_) => write!(f, "custom"),
+        }
+    }
+}
+
+impl FromStr for Predicate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Predicate, String> {
+        let (name, arg) = match s.split_once('=') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let int = |arg: Option<&str>| -> Result<u32, String> {
+            let arg = arg.ok_or_else(|| format!("{} needs a value", name))?;
+            let res = match arg.strip_prefix("0x") {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => arg.parse(),
+            };
+            res.map_err(|_| format!("invalid value {:?}", arg))
+        };
+        let byte = |arg: Option<&str>| -> Result<u8, String> {
+            u8::try_from(int(arg)?)
+                .map_err(|_| format!("{} doesn't fit in a byte", s))
+        };
+
+        match name {
+            "printable" => Ok(Predicate::Printable),
+            "alnum" => Ok(Predicate::Alnum),
+            "decimal" => Ok(Predicate::Decimal),
+            "palindrome" => Ok(Predicate::Palindrome),
+            "top-byte" => Ok(Predicate::TopByte(byte(arg)?)),
+            "bottom-byte" => Ok(Predicate::BottomByte(byte(arg)?)),
+            "leading-zeros" => Ok(Predicate::LeadingZeros(int(arg)?.min(32))),
+            _ => Err(format!("unknown predicate {:?}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every predicate's mask should agree with the predicate itself
+    #[test]
+    fn masks_match_tests() {
+        let mut rng = crate::rng::Rng::new(1);
+        for width in [8, 12, 16, 24, 31, 32] {
+            let ones = (!0u64 >> (64 - width)) as u32;
+            for p in ["top-byte=0x5a", "bottom-byte=0xa5",
+                    "leading-zeros=3", "leading-zeros=40"] {
+                let p = p.parse::<Predicate>().unwrap();
+                let (value, mask) = p.as_mask(width).unwrap();
+                assert_eq!(mask & !ones, 0);
+                for _ in 0..1000 {
+                    let crc = (rng.next_u64() as u32 & ones & !mask) | value;
+                    assert!(p.test(crc, width), "{} {} 0x{:x}", p, width, crc);
+                    let crc = crc ^ (mask & rng.next_u64() as u32);
+                    assert_eq!(p.test(crc, width), crc & mask == value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn narrow_widths() {
+        assert!(Predicate::TopByte(0x12).test(0x1234, 16));
+        assert!(Predicate::TopByte(0x12).test(0x123, 12));
+        assert!(Predicate::LeadingZeros(4).test(0x0fff, 16));
+        assert!(!Predicate::LeadingZeros(5).test(0x0fff, 16));
+        assert!(Predicate::Printable.test(0x4142, 16));
+        assert!(Predicate::Alnum.test(0x41, 8));
+        assert!(Predicate::Decimal.test(0x1234, 16));
+        assert!(!Predicate::Decimal.test(0x123a, 16));
+        assert!(Predicate::Palindrome.test(0x1221, 16));
+        assert!(Predicate::Palindrome.test(0x121, 12));
+        assert!(!Predicate::Palindrome.test(0x1231, 16));
+        assert!(Predicate::Palindrome.test(0x12344321, 32));
+
+        // a top byte that doesn't fit can never match
+        assert_eq!(as_mask(&[Predicate::TopByte(0xff)], 4), Some(None));
+        assert_eq!(as_mask(&[Predicate::TopByte(0x0f)], 4),
+            Some(Some((0xf, 0xf))));
+    }
+}
@@ -0,0 +1,104 @@
+// User-defined CRC profiles
+//
+// Proprietary CRCs don't show up in any catalog, so these can be described
+// in a profiles file, written in the same subset of TOML as frame specs:
+//
+//   [[profile]]
+//   name = "acme-bus"
+//   polynomial = 0x11021
+//   init = 0xffff
+//   refin = false
+//   refout = false
+//   xorout = 0x0000
+//   check = 0x29b1
+//
+// Anything left out takes our usual default. A polynomial can leave out
+// its top bit if a width is given, and check, the CRC of "123456789", is
+// optional, but catches typos.
+//
+
+use std::path::PathBuf;
+use crate::frame::{parse_value, strip_comment, Value};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    pub polynomial: Option<u64>,
+    pub width: Option<u32>,
+    pub init: Option<u32>,
+    pub xorout: Option<u32>,
+    pub refin: Option<bool>,
+    pub refout: Option<bool>,
+    pub check: Option<u32>,
+}
+
+/// Where we look for profiles if not told otherwise, $CRCBRUTE_PROFILES,
+/// or ~/.config/crcbrute/profiles.toml
+pub fn default_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("CRCBRUTE_PROFILES") {
+        return Some(path.into());
+    }
+    std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".config/crcbrute/profiles.toml"))
+}
+
+/// Parse a profiles file
+pub fn parse(s: &str) -> Result<Vec<Profile>, String> {
+    let mut profiles: Vec<Profile> = vec![];
+
+    for (i, line) in s.lines().enumerate() {
+        let err = |e: String| format!("line {}: {}", i+1, e);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line == "[[profile]]" {
+            profiles.push(Profile{
+                name: format!("profile{}", profiles.len()),
+                polynomial: None,
+                width: None,
+                init: None,
+                xorout: None,
+                refin: None,
+                refout: None,
+                check: None,
+            });
+            continue;
+        }
+
+        let (key, value) = line.split_once('=')
+            .ok_or_else(|| err(format!("expected key = value, found {:?}",
+                line)))?;
+        let key = key.trim();
+        let value = parse_value(value.trim()).map_err(err)?;
+        let u32_ = |x: u64| u32::try_from(x)
+            .map_err(|_| err(format!("{} 0x{:x} doesn't fit in 32 bits",
+                key, x)));
+        let profile = profiles.last_mut();
+        match (key, value, profile) {
+            ("name", Value::String(s), Some(p)) => p.name = s,
+            ("polynomial", Value::Int(x), Some(p)) => p.polynomial = Some(x),
+            ("width", Value::Int(x), Some(p)) => p.width = Some(u32_(x)?),
+            ("init", Value::Int(x), Some(p)) => p.init = Some(u32_(x)?),
+            ("xorout", Value::Int(x), Some(p)) => p.xorout = Some(u32_(x)?),
+            ("refin", Value::Bool(b), Some(p)) => p.refin = Some(b),
+            ("refout", Value::Bool(b), Some(p)) => p.refout = Some(b),
+            ("check", Value::Int(x), Some(p)) => p.check = Some(u32_(x)?),
+            (key, value, _) => return Err(err(format!(
+                "unexpected {} = {:?}", key, value))),
+        }
+    }
+
+    // sanity check
+    for (i, p) in profiles.iter().enumerate() {
+        if p.polynomial.is_none() {
+            return Err(format!("{}: profiles need a polynomial", p.name));
+        }
+        if profiles[..i].iter().any(|q| q.name == p.name) {
+            return Err(format!("{}: duplicate profile", p.name));
+        }
+    }
+
+    Ok(profiles)
+}
@@ -0,0 +1,309 @@
+// Live progress reports for long searches
+//
+// Searches can run for days, so we let the user ask how things are going
+// with `kill -USR1` or by pressing enter, without interrupting the search.
+// The search loop only polls a flag, so this costs ~nothing when unused.
+//
+
+use std::io::{self, BufRead, IsTerminal};
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// set when someone asks for a progress report
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// set when scripts want our progress reports as JSON
+static JSON: AtomicBool = AtomicBool::new(false);
+
+// set when it's time to write a checkpoint
+static CHECKPOINT: AtomicBool = AtomicBool::new(false);
+
+// set when our search should stop early
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+// set when we were stopped by the user
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// set when we ran out of time or candidates
+static EXPIRED: AtomicBool = AtomicBool::new(false);
+
+// we may run several searches in one process, but only need to listen
+// once
+static INSTALLED: Once = Once::new();
+
+#[cfg(unix)]
+mod sys {
+    use super::*;
+
+    // avoid pulling in libc for one function
+    #[cfg(any(target_os="linux", target_os="android"))]
+    #[cfg(not(any(target_arch="mips", target_arch="mips64")))]
+    const SIGUSR1: i32 = 10;
+    #[cfg(any(target_os="linux", target_os="android"))]
+    #[cfg(any(target_arch="mips", target_arch="mips64"))]
+    const SIGUSR1: i32 = 16;
+    #[cfg(not(any(target_os="linux", target_os="android")))]
+    const SIGUSR1: i32 = 30;
+    const SIGINT: i32 = 2;
+    const SIG_DFL: usize = 0;
+
+    extern "C" {
+        fn signal(signum: i32, handler: usize) -> usize;
+    }
+
+    // only async-signal-safe things in here, an atomic store is fine
+    extern "C" fn handler(_: i32) {
+        REQUESTED.store(true, Ordering::Relaxed);
+    }
+
+    pub fn install() {
+        unsafe { signal(SIGUSR1, handler as extern "C" fn(i32) as usize) };
+    }
+
+    // stop on the first SIGINT, but let a second one kill us in case
+    // we're stuck somewhere that doesn't check
+    extern "C" fn interrupt(_: i32) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        STOPPED.store(true, Ordering::Relaxed);
+        unsafe { signal(SIGINT, SIG_DFL) };
+    }
+
+    pub fn install_interrupt() {
+        unsafe { signal(SIGINT, interrupt as extern "C" fn(i32) as usize) };
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    pub fn install() {}
+    pub fn install_interrupt() {}
+}
+
+/// Listen for progress requests, SIGUSR1 on unix, or enter if stdin is a
+/// terminal
+pub fn install() {
+    INSTALLED.call_once(|| {
+        sys::install();
+
+        if io::stdin().is_terminal() {
+            std::thread::spawn(|| {
+                for line in io::stdin().lock().lines() {
+                    if line.is_err() {
+                        break;
+                    }
+                    REQUESTED.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+}
+
+/// Also request a progress report every interval, for watching long
+/// searches without asking
+pub fn every(interval: Duration) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        REQUESTED.store(true, Ordering::Relaxed);
+    });
+}
+
+/// Report progress as JSON lines instead, for scripts
+pub fn json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// Ask for a checkpoint every interval
+pub fn checkpoint_every(interval: Duration) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        CHECKPOINT.store(true, Ordering::Relaxed);
+    });
+}
+
+/// Check if it's time to write a checkpoint, clearing the request
+#[inline]
+pub fn checkpoint_due() -> bool {
+    CHECKPOINT.load(Ordering::Relaxed)
+        && CHECKPOINT.swap(false, Ordering::Relaxed)
+}
+
+/// Check if someone asked for a progress report, clearing the request
+#[inline]
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Relaxed)
+        && REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// Stop our search cleanly on Ctrl-C, instead of dying mid-search
+pub fn install_interrupt() {
+    sys::install_interrupt();
+}
+
+/// Were we stopped by the user?
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Stop our search once it's run for this long
+pub fn expire_after(limit: Duration) {
+    std::thread::spawn(move || {
+        std::thread::sleep(limit);
+        EXPIRED.store(true, Ordering::Relaxed);
+        STOPPED.store(true, Ordering::Relaxed);
+    });
+}
+
+/// Exit status of a search that ran out of time or candidates
+pub const EXIT_EXPIRED: i32 = 2;
+
+/// Exit status of a search that finished without finding a solution, so
+/// scripts can tell this apart from errors, which exit with 1
+pub const EXIT_NOT_FOUND: i32 = 3;
+
+/// Note our search ran out of candidates it was allowed to try
+pub fn expire() {
+    EXPIRED.store(true, Ordering::Relaxed);
+}
+
+/// Did our search run out of time or candidates?
+pub fn expired() -> bool {
+    EXPIRED.load(Ordering::Relaxed)
+}
+
+/// Ask our search to stop early, searches check this between blocks
+pub fn stop() {
+    STOPPED.store(true, Ordering::Relaxed);
+}
+
+/// Forget why our last search stopped, before starting another one in
+/// the same process, unless we were stopped by the user
+pub fn reset() {
+    EXPIRED.store(false, Ordering::Relaxed);
+    if !interrupted() {
+        STOPPED.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Has our search been asked to stop?
+#[inline]
+pub fn stopped() -> bool {
+    STOPPED.load(Ordering::Relaxed)
+}
+
+/// Tracks the rate of a search over a known number of candidates
+#[derive(Debug, Clone)]
+pub struct Progress {
+    start: Instant,
+    total: u64,
+}
+
+impl Progress {
+    pub fn new(total: u64) -> Progress {
+        Progress{start: Instant::now(), total}
+    }
+
+    /// Print our position, rate, ETA, and a token that can be used to
+    /// resume from this position
+    pub fn report(&self, done: u64, resume: &str) {
+        let elapsed = self.start.elapsed();
+        let rate = done as f64 / elapsed.as_secs_f64().max(1e-9);
+        let eta = if rate > 0.0 {
+            Some(Duration::from_secs_f64(
+                (self.total.saturating_sub(done) as f64 / rate)
+                    .min(u64::MAX as f64)))
+        } else {
+            None
+        };
+
+        if JSON.load(Ordering::Relaxed) {
+            // resume tokens are hex or digits, but escape them anyways
+            let resume = resume.chars()
+                .flat_map(|c| match c {
+                    '"' | '\\' => vec!['\\', c],
+                    c if (c as u32) < 0x20 => format!("\\u{:04x}", c as u32)
+                        .chars().collect(),
+                    c => vec![c],
+                })
+                .collect::<String>();
+            eprintln!("{{\"event\":\"progress\",\"done\":{},\"total\":{},\
+                    \"percent\":{:.3},\"rate\":{:.0},\"elapsed\":{:.3},\
+                    \"eta\":{},\"resume\":\"{}\"}}",
+                done, self.total,
+                100.0 * done as f64 / self.total.max(1) as f64,
+                rate,
+                elapsed.as_secs_f64(),
+                eta.map_or("null".into(), |eta| format!("{:.3}",
+                    eta.as_secs_f64())),
+                resume);
+            return;
+        }
+
+        eprintln!("progress: {}/{} ({:.3}%), {:.0} candidates/s, \
+                elapsed {}, eta {}, resume {}",
+            done, self.total,
+            100.0 * done as f64 / self.total.max(1) as f64,
+            rate,
+            format_duration(elapsed),
+            eta.map_or("?".into(), format_duration),
+            resume);
+    }
+}
+
+/// Counters for a finished search, for comparing backends across machines
+/// more fairly than wall-clock time
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    /// Candidates checked by each thread
+    pub candidates: Vec<u64>,
+    /// Bytes fed through our CRC
+    pub bytes: u64,
+    /// Carry-less multiplies
+    pub pmuls: u64,
+    /// Table lookups, our Barret reduction doesn't need tables
+    pub lookups: u64,
+    /// Candidates rejected by a prefilter, if we have one
+    pub prefiltered: Option<u64>,
+    /// Solutions we found
+    pub solutions: u64,
+    /// Which backend our CRC used
+    pub backend: &'static str,
+    pub elapsed: Duration,
+}
+
+impl Stats {
+    pub fn report(&self, label: &str) {
+        let secs = self.elapsed.as_secs_f64().max(1e-9);
+        let total = self.candidates.iter().sum::<u64>();
+        eprintln!("{}stats: {} candidates in {}, {:.0} candidates/s",
+            label, total, format_duration(self.elapsed), total as f64 / secs);
+        eprintln!("{}stats: {} solutions, {} backend",
+            label, self.solutions, self.backend);
+        eprintln!("{}stats: {} pmuls, {:.0} pmuls/s, {} table lookups",
+            label, self.pmuls, self.pmuls as f64 / secs, self.lookups);
+        match self.prefiltered {
+            Some(prefiltered) => eprintln!("{}stats: prefilter rejected \
+                    {} ({:.3}%)",
+                label, prefiltered,
+                100.0 * prefiltered as f64 / total.max(1) as f64),
+            None => eprintln!("{}stats: no prefilter", label),
+        }
+        for (i, candidates) in self.candidates.iter().enumerate() {
+            eprintln!("{}stats: thread {}: {} candidates, {:.0} candidates/s",
+                label, i, candidates, *candidates as f64 / secs);
+        }
+        eprintln!("{}stats: {} bytes hashed, {:.3} GB/s",
+            label, self.bytes, self.bytes as f64 / secs / 1e9);
+    }
+}
+
+/// h:mm:ss, with days if we need them
+pub fn format_duration(d: Duration) -> String {
+    let s = d.as_secs();
+    if s >= 86400 {
+        format!("{}d{:02}:{:02}:{:02}",
+            s / 86400, (s / 3600) % 24, (s / 60) % 60, s % 60)
+    } else {
+        format!("{}:{:02}:{:02}", s / 3600, (s / 60) % 60, s % 60)
+    }
+}
@@ -0,0 +1,285 @@
+// Recovery of unknown CRC parameters
+//
+// Given messages and their observed CRCs, solve for parameters we don't
+// know. CRCs are affine in both their init and xorout values, so each
+// message gives us a full system of equations, and a couple messages are
+// usually enough.
+//
+// Parameters are reported in the usual Rocksoft/reveng convention, so
+// init is unreflected even though our CRC runs reflected. Our CRC's own
+// width and reflection carry over, but its init and xorout are ignored.
+//
+
+use crate::crc::Crc32;
+use crate::gf2;
+
+/// A message and its observed CRC
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub message: Vec<u8>,
+    pub crc: u32,
+}
+
+/// Why recovery failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// No samples to work with
+    NoSamples,
+    /// No parameters explain this sample
+    Unsolvable(usize),
+    /// Parameters explain the earlier samples, but not this one
+    Inconsistent(usize),
+}
+
+/// Every parameter value consistent with our samples, this is a
+/// particular solution xored with any subset of the basis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solutions {
+    pub particular: u32,
+    pub basis: Vec<u32>,
+}
+
+impl Solutions {
+    /// Number of bits our samples don't determine
+    pub fn free(&self) -> usize {
+        self.basis.len()
+    }
+
+    /// Iterate over every solution, there are 2^free of these
+    pub fn iter(&self) -> impl Iterator<Item=u32> + '_ {
+        (0 .. 1u64 << self.basis.len()).map(move |i| {
+            self.basis.iter().enumerate()
+                .filter(|(j, _)| i & (1 << j) != 0)
+                .fold(self.particular, |x, (_, b)| x ^ b)
+        })
+    }
+}
+
+/// CRC a message with a specific init and xorout
+pub fn crc32_with(
+    crc32: &Crc32,
+    init: u32,
+    xorout: u32,
+    message: &[u8],
+) -> u32 {
+    // our CRC applies its own init and xorout, swap these for ours, note
+    // a reflected output also reflects what we start from
+    let init = if crc32.refout() {
+        init.reverse_bits() >> (32 - crc32.width())
+    } else {
+        init
+    };
+    crc32.crc32(init ^ crc32.xorout(), message) ^ crc32.xorout() ^ xorout
+}
+
+// build equations for the unknown init, each sample contributes one
+// equation per CRC bit
+//
+// if relative is set, we subtract the first sample's equations, which
+// cancels out any unknown xorout
+fn init_rows(
+    crc32: &Crc32,
+    xorout: u32,
+    samples: &[Sample],
+    relative: bool,
+) -> Vec<(u64, bool)> {
+    // our CRC is affine in init, so find the contribution of each bit
+    let system = |message: &[u8]| {
+        let base = crc32_with(crc32, 0, xorout, message);
+        let cols = (0..crc32.width())
+            .map(|j| crc32_with(crc32, 1 << j, xorout, message) ^ base)
+            .collect::<Vec<_>>();
+        (cols, base)
+    };
+
+    let (cols0, base0) = system(&samples[0].message);
+    let mut rows = vec![];
+    for sample in samples.iter().skip(relative as usize) {
+        let (mut cols, mut b) = system(&sample.message);
+        b ^= sample.crc;
+        if relative {
+            for (c, c0) in cols.iter_mut().zip(&cols0) {
+                *c ^= c0;
+            }
+            b ^= base0 ^ samples[0].crc;
+        }
+
+        // transpose into rows
+        for r in 0..crc32.width() {
+            let row = cols.iter().enumerate()
+                .filter(|(_, &c)| c & (1 << r) != 0)
+                .fold(0u64, |row, (j, _)| row | (1 << j));
+            rows.push((row, b & (1 << r) != 0));
+        }
+    }
+    rows
+}
+
+// solve our equations, if they're inconsistent find the first sample
+// responsible
+fn solve_init(
+    crc32: &Crc32,
+    xorout: u32,
+    samples: &[Sample],
+    relative: bool,
+) -> Result<Solutions, Error> {
+    if samples.is_empty() {
+        return Err(Error::NoSamples);
+    }
+
+    let solve = |samples: &[Sample]| {
+        gf2::solve_rows(&init_rows(crc32, xorout, samples, relative),
+            crc32.width() as usize)
+    };
+    match solve(samples) {
+        Some((x, basis)) => Ok(Solutions{
+            particular: x as u32,
+            basis: basis.into_iter().map(|b| b as u32).collect(),
+        }),
+        None => {
+            let i = (1..=samples.len())
+                .find(|&i| solve(&samples[..i]).is_none())
+                .unwrap() - 1;
+            if i == 0 {
+                Err(Error::Unsolvable(i))
+            } else {
+                Err(Error::Inconsistent(i))
+            }
+        }
+    }
+}
+
+/// Solve for the init values that explain all of our samples, given a
+/// known xorout
+pub fn init(
+    crc32: &Crc32,
+    xorout: u32,
+    samples: &[Sample],
+) -> Result<Solutions, Error> {
+    solve_init(crc32, xorout, samples, false)
+}
+
+/// Solve for the xorout that explains all of our samples, given a known
+/// init
+pub fn xorout(
+    crc32: &Crc32,
+    init: u32,
+    samples: &[Sample],
+) -> Result<Solutions, Error> {
+    let first = samples.first().ok_or(Error::NoSamples)?;
+
+    // xorout is applied directly to our CRC, so this is easy
+    let xorout = crc32_with(crc32, init, 0, &first.message) ^ first.crc;
+    for (i, sample) in samples.iter().enumerate().skip(1) {
+        if crc32_with(crc32, init, xorout, &sample.message) != sample.crc {
+            return Err(Error::Inconsistent(i));
+        }
+    }
+
+    Ok(Solutions{particular: xorout, basis: vec![]})
+}
+
+/// Solve for the init/xorout pairs that explain all of our samples
+///
+/// Returns the consistent inits, each init determines its xorout, see
+/// xorout_for. Note samples of the same length can't tell init and xorout
+/// apart, so we need samples of different lengths to pin these down.
+pub fn init_xorout(
+    crc32: &Crc32,
+    samples: &[Sample],
+) -> Result<Solutions, Error> {
+    solve_init(crc32, 0, samples, true)
+}
+
+/// Find the xorout that pairs with an init, given a sample
+pub fn xorout_for(crc32: &Crc32, init: u32, sample: &Sample) -> u32 {
+    crc32_with(crc32, init, 0, &sample.message) ^ sample.crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn samples(
+        crc32: &Crc32,
+        init: u32,
+        xorout: u32,
+        lens: &[usize],
+        rng: &mut Rng,
+    ) -> Vec<Sample> {
+        lens.iter()
+            .map(|&len| {
+                let mut message = vec![0; len];
+                rng.fill(&mut message);
+                let crc = crc32_with(crc32, init, xorout, &message);
+                Sample{message, crc}
+            })
+            .collect()
+    }
+
+    #[test]
+    fn crc32_with_defaults() {
+        // CRC-32's init and xorout are both all ones
+        let crc32 = Crc32::new(0x104c11db7);
+        assert_eq!(crc32_with(&crc32, !0, !0, b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn init_round_trips() {
+        let mut rng = Rng::new(1);
+        let crc32 = Crc32::new(0x104c11db7);
+        for _ in 0..10 {
+            let init_ = rng.next_u64() as u32;
+            let xorout = rng.next_u64() as u32;
+            let samples = samples(&crc32, init_, xorout, &[4, 9], &mut rng);
+            let solutions = init(&crc32, xorout, &samples).unwrap();
+            assert!(solutions.iter().any(|x| x == init_));
+            for x in solutions.iter() {
+                for s in &samples {
+                    assert_eq!(crc32_with(&crc32, x, xorout, &s.message),
+                        s.crc);
+                }
+            }
+        }
+
+        let mut bad = samples(&crc32, 1, 2, &[4, 9], &mut rng);
+        bad[1].crc ^= 1;
+        assert_eq!(init(&crc32, 2, &bad), Err(Error::Inconsistent(1)));
+        assert_eq!(init(&crc32, 2, &[]), Err(Error::NoSamples));
+    }
+
+    #[test]
+    fn xorout_round_trips() {
+        let mut rng = Rng::new(2);
+        let crc32 = Crc32::new(0x11edc6f41);
+        for _ in 0..10 {
+            let init_ = rng.next_u64() as u32;
+            let xorout_ = rng.next_u64() as u32;
+            let samples = samples(&crc32, init_, xorout_, &[3, 8, 20],
+                &mut rng);
+            assert_eq!(xorout(&crc32, init_, &samples).unwrap().particular,
+                xorout_);
+
+            // every pair we find explains every sample
+            let solutions = init_xorout(&crc32, &samples).unwrap();
+            assert!(solutions.iter().any(|x| x == init_));
+            for x in solutions.iter() {
+                let xorout_ = xorout_for(&crc32, x, &samples[0]);
+                for s in &samples {
+                    assert_eq!(crc32_with(&crc32, x, xorout_, &s.message),
+                        s.crc);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_lengths_are_ambiguous() {
+        // with every sample the same length, any init has an xorout
+        let mut rng = Rng::new(3);
+        let crc32 = Crc32::new(0x104c11db7);
+        let samples = samples(&crc32, 1, 2, &[8, 8, 8], &mut rng);
+        assert_eq!(init_xorout(&crc32, &samples).unwrap().free(), 32);
+    }
+}
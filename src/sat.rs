@@ -0,0 +1,425 @@
+// Exporting our constraints to SAT/SMT solvers
+//
+// Our CRC is affine in the bits of our suffix, so a solve is a set of
+// linear equations over GF(2), plus restrictions on which bytes each
+// character can be. Exporting these lets users with more exotic
+// constraints add them and solve externally, or, with the sat feature,
+// solve them with our own solver.
+//
+// Suffix byte i is made up of variables 8*i+1 ..= 8*i+8 in DIMACS, least
+// significant bit first, and of bit-vector s<i> in SMT-LIB.
+//
+
+use std::fmt::Write;
+use crate::crc::Crc32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraints {
+    /// Number of suffix bytes
+    pub len: usize,
+    /// Linear equations, each the suffix bits we xor, and the result
+    pub equations: Vec<(Vec<usize>, bool)>,
+    /// Bytes each character of our suffix is allowed to be
+    pub allowed: Vec<u8>,
+}
+
+impl Constraints {
+    /// Build the constraints that make the masked bits of
+    /// crc(prefix || suffix || postfix) match our target
+    pub fn new(
+        crc32: &Crc32,
+        prefix: &[u8],
+        postfix: &[u8],
+        len: usize,
+        target: u32,
+        mask: u32,
+        allowed: &[u8],
+    ) -> Constraints {
+        let crc = |suffix: &[u8]| {
+            crc32.crc32(
+                crc32.crc32(crc32.crc32(crc32.init(), prefix), suffix),
+                postfix)
+        };
+
+        // find the contribution of each bit
+        let mut suffix = vec![0; len];
+        let base = crc(&suffix);
+        let cols = (0..8*len)
+            .map(|k| {
+                suffix[k/8] ^= 1 << (k%8);
+                let col = crc(&suffix) ^ base;
+                suffix[k/8] ^= 1 << (k%8);
+                col
+            })
+            .collect::<Vec<_>>();
+
+        // and transpose into equations, one per constrained bit
+        let equations = (0..32)
+            .filter(|r| mask & (1 << r) != 0)
+            .map(|r| (
+                (0..8*len).filter(|&k| cols[k] & (1 << r) != 0).collect(),
+                (target ^ base) & (1 << r) != 0,
+            ))
+            .collect();
+
+        Constraints{len, equations, allowed: allowed.to_vec()}
+    }
+
+    /// Also require another set of constraints on the same suffix, such
+    /// as a second CRC
+    pub fn and(mut self, other: Constraints) -> Constraints {
+        assert_eq!(self.len, other.len);
+        self.equations.extend(other.equations);
+        self.allowed.retain(|b| other.allowed.contains(b));
+        self
+    }
+
+    /// Bytes our characters can't be
+    fn forbidden(&self) -> impl Iterator<Item=u8> + '_ {
+        (0..=255u8).filter(|b| !self.allowed.contains(b))
+    }
+
+    /// Encode as DIMACS CNF
+    ///
+    /// Each xor is split into a chain of 2-input xors with auxiliary
+    /// variables, otherwise the number of clauses would explode.
+    pub fn dimacs(&self) -> String {
+        let mut clauses: Vec<Vec<i64>> = vec![];
+        let mut vars = 8*self.len as i64;
+
+        for (bits, rhs) in &self.equations {
+            let mut lits = bits.iter().map(|&k| k as i64 + 1);
+            let mut acc = match lits.next() {
+                Some(lit) => lit,
+                None => {
+                    if *rhs {
+                        clauses.push(vec![]);
+                    }
+                    continue;
+                }
+            };
+            for x in lits {
+                vars += 1;
+                let a = vars;
+                clauses.push(vec![-a, acc, x]);
+                clauses.push(vec![-a, -acc, -x]);
+                clauses.push(vec![a, -acc, x]);
+                clauses.push(vec![a, acc, -x]);
+                acc = a;
+            }
+            clauses.push(vec![if *rhs { acc } else { -acc }]);
+        }
+
+        // rule out every forbidden byte
+        for i in 0..self.len {
+            for b in self.forbidden() {
+                clauses.push((0..8)
+                    .map(|j| {
+                        let var = (8*i + j) as i64 + 1;
+                        if b & (1 << j) != 0 { -var } else { var }
+                    })
+                    .collect());
+            }
+        }
+
+        let mut s = String::new();
+        writeln!(s, "c crcbrute constraints, suffix byte i is variables \
+            8i+1..=8i+8, lsb first").unwrap();
+        writeln!(s, "c suffix length {}", self.len).unwrap();
+        writeln!(s, "p cnf {} {}", vars, clauses.len()).unwrap();
+        for clause in clauses {
+            for lit in clause {
+                write!(s, "{} ", lit).unwrap();
+            }
+            writeln!(s, "0").unwrap();
+        }
+        s
+    }
+
+    /// Encode as SMT-LIB, using the theory of bit-vectors
+    pub fn smtlib(&self) -> String {
+        let mut s = String::new();
+        writeln!(s, "; crcbrute constraints, suffix length {}", self.len)
+            .unwrap();
+        writeln!(s, "(set-logic QF_BV)").unwrap();
+        for i in 0..self.len {
+            writeln!(s, "(declare-const s{} (_ BitVec 8))", i).unwrap();
+        }
+
+        let bit = |k: usize| format!("((_ extract {} {}) s{})", k%8, k%8, k/8);
+        for (bits, rhs) in &self.equations {
+            let rhs = if *rhs { "#b1" } else { "#b0" };
+            match bits.len() {
+                0 if rhs == "#b1" => writeln!(s, "(assert false)").unwrap(),
+                0 => {}
+                1 => writeln!(s, "(assert (= {} {}))", bit(bits[0]), rhs)
+                    .unwrap(),
+                _ => writeln!(s, "(assert (= (bvxor {}) {}))",
+                        bits.iter().map(|&k| bit(k))
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                        rhs)
+                    .unwrap(),
+            }
+        }
+
+        if self.forbidden().next().is_some() {
+            for i in 0..self.len {
+                writeln!(s, "(assert (or {}))",
+                        self.allowed.iter()
+                            .map(|b| format!("(= s{} #x{:02x})", i, b))
+                            .collect::<Vec<_>>()
+                            .join(" "))
+                    .unwrap();
+            }
+        }
+
+        writeln!(s, "(check-sat)").unwrap();
+        writeln!(s, "(get-value ({}))",
+                (0..self.len)
+                    .map(|i| format!("s{}", i))
+                    .collect::<Vec<_>>()
+                    .join(" "))
+            .unwrap();
+        s
+    }
+}
+
+/// Parse a solver's assignment back into a suffix, either a DIMACS model
+/// (v lines) or an SMT-LIB model ((s0 #x41) ...)
+pub fn parse_assignment(s: &str, len: usize) -> Result<Vec<u8>, String> {
+    let mut suffix = vec![0u8; len];
+    let mut seen = vec![false; len];
+
+    if s.contains("#x") || s.contains("#b") {
+        let tokens = s.replace(['(', ')'], " ");
+        let tokens = tokens.split_whitespace().collect::<Vec<_>>();
+        for pair in tokens.windows(2) {
+            let i = match pair[0].strip_prefix('s')
+                    .and_then(|i| i.parse::<usize>().ok()) {
+                Some(i) if i < len => i,
+                _ => continue,
+            };
+            let value = if let Some(hex) = pair[1].strip_prefix("#x") {
+                u8::from_str_radix(hex, 16)
+            } else if let Some(bin) = pair[1].strip_prefix("#b") {
+                u8::from_str_radix(bin, 2)
+            } else {
+                continue;
+            };
+            suffix[i] = value
+                .map_err(|_| format!("invalid value {:?}", pair[1]))?;
+            seen[i] = true;
+        }
+    } else {
+        for line in s.lines() {
+            let line = line.trim();
+            if line.starts_with('c') || line.starts_with('s') {
+                if line.starts_with("s UNSAT") {
+                    return Err("solver says unsatisfiable".into());
+                }
+                continue;
+            }
+            let line = line.strip_prefix('v').unwrap_or(line);
+            for lit in line.split_whitespace() {
+                let lit = lit.parse::<i64>()
+                    .map_err(|_| format!("invalid literal {:?}", lit))?;
+                let k = lit.unsigned_abs() as usize;
+                if k == 0 || k > 8*len {
+                    continue;
+                }
+                let k = k-1;
+                if lit > 0 {
+                    suffix[k/8] |= 1 << (k%8);
+                }
+                seen[k/8] = true;
+            }
+        }
+    }
+
+    match seen.iter().position(|s| !s) {
+        Some(i) => Err(format!("no assignment for suffix byte {}", i)),
+        None => Ok(suffix),
+    }
+}
+
+/// Solve our constraints directly, this is complete, so unlike brute
+/// force it can tell us there's no solution
+///
+/// Rather than going through CNF, we search over the bytes of our suffix,
+/// keeping our equations in echelon form so each one is checked as soon
+/// as its last variable is assigned, the same trick XOR-aware SAT solvers
+/// use.
+#[cfg(feature="sat")]
+pub fn solve(constraints: &Constraints) -> Option<Vec<u8>> {
+    let len = constraints.len;
+    let words = (8*len).div_ceil(64);
+    let top = |r: &[u64]| r.iter().enumerate().rev()
+        .find(|(_, &w)| w != 0)
+        .map(|(i, w)| 64*i + 63 - w.leading_zeros() as usize);
+
+    // reduce our equations into echelon form, indexed by highest variable
+    let mut pivots: Vec<Option<(Vec<u64>, bool)>> = vec![None; 8*len];
+    for (bits, rhs) in &constraints.equations {
+        let mut r = vec![0u64; words];
+        for &k in bits {
+            r[k/64] ^= 1 << (k%64);
+        }
+        let mut rhs = *rhs;
+        loop {
+            match top(&r) {
+                Some(k) => match &pivots[k] {
+                    Some((p, prhs)) => {
+                        for (r, p) in r.iter_mut().zip(p) {
+                            *r ^= p;
+                        }
+                        rhs ^= prhs;
+                    }
+                    None => {
+                        pivots[k] = Some((r, rhs));
+                        break;
+                    }
+                },
+                // inconsistent equations, no solution
+                None if rhs => return None,
+                None => break,
+            }
+        }
+    }
+
+    // group by the byte that completes each equation
+    let mut checks = vec![vec![]; len];
+    for (k, pivot) in pivots.into_iter().enumerate() {
+        if let Some(pivot) = pivot {
+            checks[k/8].push(pivot);
+        }
+    }
+
+    // depth-first search over each byte
+    fn search(
+        i: usize,
+        x: &mut Vec<u64>,
+        allowed: &[u8],
+        checks: &[Vec<(Vec<u64>, bool)>],
+    ) -> bool {
+        if i == checks.len() {
+            return true;
+        }
+        for &b in allowed {
+            x[(8*i)/64] &= !(0xff << ((8*i)%64));
+            x[(8*i)/64] |= (b as u64) << ((8*i)%64);
+            let ok = checks[i].iter().all(|(r, rhs)| {
+                let parity = r.iter().zip(x.iter())
+                    .map(|(r, x)| (r & x).count_ones())
+                    .sum::<u32>() & 1 == 1;
+                parity == *rhs
+            });
+            if ok && search(i+1, x, allowed, checks) {
+                return true;
+            }
+        }
+        false
+    }
+
+    let mut x = vec![0u64; words];
+    if !search(0, &mut x, &constraints.allowed, &checks) {
+        return None;
+    }
+    Some((0..len).map(|i| (x[(8*i)/64] >> ((8*i)%64)) as u8).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn crc(crc32: &Crc32, prefix: &[u8], suffix: &[u8], postfix: &[u8])
+            -> u32 {
+        crc32.crc32(crc32.init(), &[prefix, suffix, postfix].concat())
+    }
+
+    #[test]
+    fn equations_hold_for_real_suffixes() {
+        let mut rng = Rng::new(1);
+        let crc32 = Crc32::new(0x104c11db7);
+        let all = (0..=255).collect::<Vec<u8>>();
+        for mask in [0xffffffff, 0xffff, 0x80000001] {
+            let mut suffix = vec![0; 6];
+            rng.fill(&mut suffix);
+            let target = crc(&crc32, b"hello", &suffix, b"\n");
+            let constraints = Constraints::new(&crc32, b"hello", b"\n",
+                suffix.len(), target, mask, &all);
+            assert_eq!(constraints.equations.len(),
+                mask.count_ones() as usize);
+            for (bits, rhs) in &constraints.equations {
+                let parity = bits.iter()
+                    .filter(|&&k| suffix[k/8] & (1 << (k%8)) != 0)
+                    .count() & 1 == 1;
+                assert_eq!(parity, *rhs);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_assignments() {
+        // DIMACS models can span multiple v lines, and include our
+        // auxiliary variables
+        let dimacs = "c comment\ns SATISFIABLE\n\
+            v 1 -2 -3 -4 -5 -6 7 -8\n\
+            v -9 10 -11 -12 -13 -14 15 -16 17 0\n";
+        assert_eq!(parse_assignment(dimacs, 2), Ok(b"AB".to_vec()));
+        assert!(parse_assignment("s UNSATISFIABLE\n", 2).is_err());
+        assert!(parse_assignment("v 1 -2 -3 -4 -5 -6 7 -8 0\n", 2).is_err());
+
+        let smtlib = "sat\n((s0 #x41)\n (s1 #b01000010))\n";
+        assert_eq!(parse_assignment(smtlib, 2), Ok(b"AB".to_vec()));
+        assert!(parse_assignment("((s0 #x41))", 2).is_err());
+        assert!(parse_assignment("((s0 #xzz) (s1 #x42))", 2).is_err());
+    }
+
+    #[test]
+    fn exports_every_equation() {
+        let crc32 = Crc32::new(0x104c11db7);
+        let digits = (b'0'..=b'9').collect::<Vec<u8>>();
+        let constraints = Constraints::new(&crc32, b"hello", b"", 10,
+            0x12345678, 0xffffffff, &digits);
+        let smtlib = constraints.smtlib();
+        assert_eq!(smtlib.matches("(assert (= ").count(), 32);
+        assert_eq!(smtlib.matches("(assert (or ").count(), 10);
+
+        // every forbidden byte of every character gets a clause
+        let dimacs = constraints.dimacs();
+        let header = dimacs.lines()
+            .find_map(|l| l.strip_prefix("p cnf "))
+            .unwrap();
+        let clauses = header.split(' ').nth(1).unwrap().parse::<usize>()
+            .unwrap();
+        assert_eq!(dimacs.lines().filter(|l| l.ends_with(" 0")).count(),
+            clauses);
+        assert!(clauses >= 10*(256-10) + 32);
+    }
+
+    #[cfg(feature="sat")]
+    #[test]
+    fn solve_round_trips() {
+        let crc32 = Crc32::new(0x104c11db7);
+        let lower = (b'a'..=b'z').collect::<Vec<u8>>();
+        for (target, mask) in [(0, 0xffffffff), (0x12345678, 0xffffffff),
+                (0xcafe, 0xffff)] {
+            let constraints = Constraints::new(&crc32, b"hello", b"\n", 8,
+                target, mask, &lower);
+            let suffix = solve(&constraints).unwrap();
+            assert!(suffix.iter().all(|b| lower.contains(b)));
+            assert_eq!(crc(&crc32, b"hello", &suffix, b"\n") & mask, target);
+        }
+
+        // 3 characters can't reach every 32-bit CRC, but they can reach
+        // every 8-bit one
+        let constraints = Constraints::new(&crc32, b"", b"", 3, 0, 0xffffffff,
+            &lower);
+        assert_eq!(solve(&constraints), None);
+        let constraints = Constraints::new(&crc32, b"", b"", 2, 0x42, 0xff,
+            &lower);
+        assert!(solve(&constraints).is_some());
+    }
+}
@@ -0,0 +1,431 @@
+// Simple checksums, and forging them
+//
+// These only add up the symbols of our message, so the order of our
+// suffix doesn't matter, only what it adds up to. We pick how much each
+// byte position of our suffix's symbols adds, and spread that over bytes
+// our charset allows. Xor is the odd one out, but with only 256 possible
+// sums, a breadth-first search over them finds the shortest suffix.
+//
+
+use core::fmt;
+use core::str::FromStr;
+use crate::charset::Charset;
+use crate::checksum::Checksum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sum {
+    /// RFC 1071 internet checksum, the ones' complement of the ones'
+    /// complement sum of big-endian 16-bit words
+    Internet,
+    /// Sum of bytes mod 256
+    Sum8,
+    /// Sum of bytes mod 2^16
+    Sum16,
+    /// Two's complement of the sum of bytes mod 256, as in Modbus ASCII
+    Lrc,
+    /// Xor of bytes, sometimes also called an LRC
+    Xor8,
+}
+
+impl Sum {
+    /// Bytes in each symbol we sum
+    pub fn symbol_len(&self) -> usize {
+        match self {
+            Sum::Internet => 2,
+            Sum::Sum8 | Sum::Sum16 | Sum::Lrc | Sum::Xor8 => 1,
+        }
+    }
+
+    /// Modulus our symbols are summed in
+    pub fn modulus(&self) -> u64 {
+        match self {
+            // ones' complement addition is addition mod 2^16-1
+            Sum::Internet => 0xffff,
+            Sum::Sum8 | Sum::Lrc | Sum::Xor8 => 0x100,
+            Sum::Sum16 => 0x10000,
+        }
+    }
+
+    // our data as big-endian symbols, padding any partial symbol with
+    // zeros
+    fn symbols<'a>(&self, data: &'a [u8]) -> impl Iterator<Item=u64> + 'a {
+        let n = self.symbol_len();
+        data.chunks(n)
+            .map(move |s| s.iter()
+                .chain(core::iter::repeat(&0))
+                .take(n)
+                .fold(0, |x, &b| (x << 8) | b as u64))
+    }
+
+    // continue our running sum, before any final tweaks
+    fn total(&self, total: u64, data: &[u8]) -> u64 {
+        match self {
+            Sum::Internet => {
+                let mut s = total;
+                for x in self.symbols(data) {
+                    s += x;
+                    s = (s & 0xffff) + (s >> 16);
+                }
+                s
+            }
+            Sum::Sum8 | Sum::Sum16 | Sum::Lrc => {
+                (total + data.iter().map(|&b| b as u64).sum::<u64>())
+                    % self.modulus()
+            }
+            Sum::Xor8 => data.iter().fold(total, |x, &b| x ^ b as u64),
+        }
+    }
+
+    // checksum from our running sum, and the running sum we need for a
+    // checksum
+    fn finish(&self, total: u64) -> u32 {
+        match self {
+            Sum::Internet => !total as u32 & 0xffff,
+            Sum::Lrc => total.wrapping_neg() as u32 & 0xff,
+            Sum::Sum8 | Sum::Sum16 | Sum::Xor8 => total as u32,
+        }
+    }
+
+    fn unfinish(&self, sum: u32) -> u64 {
+        match self {
+            Sum::Internet => !sum as u64 & 0xffff,
+            Sum::Lrc => (sum as u64).wrapping_neg() & 0xff,
+            Sum::Sum8 | Sum::Sum16 | Sum::Xor8 => sum as u64,
+        }
+    }
+
+    /// Checksum of a message
+    pub fn sum(&self, data: &[u8]) -> u32 {
+        self.finish(self.total(0, data))
+    }
+
+    /// Is this a plain sum of bytes? These don't care where our bytes
+    /// are, only what they add up to
+    pub fn is_byte_sum(&self) -> bool {
+        matches!(self, Sum::Sum8 | Sum::Sum16 | Sum::Lrc)
+    }
+
+    /// What any more bytes need to add, mod our modulus, to take a byte
+    /// sum of data to our target
+    pub fn deficit(&self, data: &[u8], target: u32) -> u64 {
+        debug_assert!(self.is_byte_sum());
+        let m = self.modulus();
+        (self.unfinish(target) + m - self.total(0, data)) % m
+    }
+
+    /// Is this a checksum we can actually produce?
+    pub fn is_valid(&self, sum: u32) -> bool {
+        match self {
+            // only a message of all zeros sums to 0x0000, so its checksum
+            // 0xffff isn't something we can forge
+            Sum::Internet => sum < 0xffff,
+            _ => (sum as u64) < self.modulus(),
+        }
+    }
+
+    /// Find the shortest suffix of bytes in lo..=hi that takes
+    /// prefix || suffix || postfix to any of our targets, returning the
+    /// suffix and which target it hit
+    ///
+    /// If our prefix ends mid-symbol, our suffix starts by finishing it.
+    pub fn forge(
+        &self,
+        prefix: &[u8],
+        postfix: &[u8],
+        targets: &[u32],
+        lo: u8,
+        hi: u8,
+    ) -> Option<(Vec<u8>, u32)> {
+        let n = self.symbol_len();
+        let m = self.modulus();
+        let mut suffix = vec![lo; (n - prefix.len() % n) % n];
+        let mut data = prefix.to_vec();
+        data.extend_from_slice(&suffix);
+        data.extend_from_slice(postfix);
+        let total = self.total(0, &data);
+        if *self == Sum::Xor8 {
+            return forge_xor(prefix, postfix, targets,
+                &(lo..=hi).collect::<Vec<_>>());
+        }
+
+        // a symbol's bytes each add to their own position, so n symbols
+        // can add anything made of per-position sums in n*lo..=n*hi
+        let (lo, hi) = (lo as u64, hi as u64);
+        let max_len = m.div_ceil(hi - lo) + 2;
+        for len in 1..=max_len {
+            let (min, max) = (len*lo, len*hi);
+            for &target in targets {
+                let d = (self.unfinish(target) + m - total % m) % m;
+                // try each position's sum, most significant first
+                let found = (0..)
+                    .map(|k| d + k*m)
+                    .take_while(|&v| v <= max * ((1 << (8*n)) - 1) / 255)
+                    .find_map(|v| split(v, n, min, max));
+                let parts = match found {
+                    Some(parts) => parts,
+                    None => continue,
+                };
+
+                // spread each position's sum over our symbols
+                let mut symbols = vec![vec![0u8; n]; len as usize];
+                for (j, &part) in parts.iter().enumerate() {
+                    let mut left = part - min;
+                    for s in symbols.iter_mut() {
+                        let t = left.min(hi - lo);
+                        s[j] = (lo + t) as u8;
+                        left -= t;
+                    }
+                }
+                suffix.extend(symbols.into_iter().flatten());
+                return Some((suffix, target));
+            }
+        }
+
+        None
+    }
+}
+
+impl Checksum for Sum {
+    fn width(&self) -> u32 {
+        match self {
+            Sum::Internet | Sum::Sum16 => 16,
+            Sum::Sum8 | Sum::Lrc | Sum::Xor8 => 8,
+        }
+    }
+
+    fn symbol_len(&self) -> usize {
+        Sum::symbol_len(self)
+    }
+
+    fn init(&self) -> u64 {
+        0
+    }
+
+    fn update(&self, state: u64, data: &[u8]) -> u64 {
+        self.total(state, data)
+    }
+
+    fn finalize(&self, state: u64) -> u32 {
+        self.finish(state)
+    }
+
+    fn is_valid(&self, sum: u32) -> bool {
+        Sum::is_valid(self, sum)
+    }
+
+    fn solve_suffix(
+        &self,
+        prefix: &[u8],
+        postfix: &[u8],
+        targets: &[u32],
+        charset: Charset,
+    ) -> Option<Option<(Vec<u8>, u32)>> {
+        // xor can use any of our bytes
+        if *self == Sum::Xor8 {
+            let bytes = (0..=255u8)
+                .filter(|&b| charset.contains(b))
+                .collect::<Vec<_>>();
+            return Some(forge_xor(prefix, postfix, targets, &bytes));
+        }
+
+        let (lo, hi) = charset.longest_run();
+        (lo < hi).then(|| self.forge(prefix, postfix, targets, lo, hi))
+    }
+}
+
+/// Find the shortest suffix of our bytes that takes an xor8 of
+/// prefix || suffix || postfix to any of our targets, unlike our other
+/// sums these don't need to be a contiguous range
+pub fn forge_xor(
+    prefix: &[u8],
+    postfix: &[u8],
+    targets: &[u32],
+    bytes: &[u8],
+) -> Option<(Vec<u8>, u32)> {
+    let x = prefix.iter().chain(postfix).fold(0, |x, &b| x ^ b);
+
+    // breadth-first, so the first time we see a sum is the shortest way
+    // to get there
+    let mut prev: [Option<(u8, u8)>; 256] = [None; 256];
+    let mut frontier = vec![x];
+    while !frontier.is_empty() {
+        let mut next = vec![];
+        for &s in &frontier {
+            for &b in bytes {
+                if prev[(s ^ b) as usize].is_none() {
+                    prev[(s ^ b) as usize] = Some((s, b));
+                    next.push(s ^ b);
+                }
+            }
+        }
+
+        if let Some(&target) = targets.iter()
+                .find(|&&t| t < 256 && prev[t as usize].is_some()) {
+            // walk back to find our bytes
+            let mut suffix = vec![];
+            let mut s = target as u8;
+            loop {
+                let (s_, b) = prev[s as usize].unwrap();
+                suffix.push(b);
+                s = s_;
+                if s == x {
+                    break;
+                }
+            }
+            suffix.reverse();
+            return Some((suffix, target));
+        }
+        frontier = next;
+    }
+
+    None
+}
+
+// split v into n per-position sums, v = sum part_j*256^(n-1-j), each in
+// min..=max, if we can
+fn split(v: u64, n: usize, min: u64, max: u64) -> Option<Vec<u64>> {
+    if n == 1 {
+        return (min..=max).contains(&v).then(|| vec![v]);
+    }
+
+    // lower positions can make up at most this much
+    let weight = 1 << (8*(n-1));
+    let below = max * (weight - 1) / 255;
+    let hi = (v / weight).min(max);
+    let lo = v.saturating_sub(below).div_ceil(weight).max(min);
+    (lo..=hi).rev().find_map(|part| {
+        let mut parts = split(v - part*weight, n-1, min, max)?;
+        parts.insert(0, part);
+        Some(parts)
+    })
+}
+
+/// A simple sum and the checksum we want it to have, written
+/// <sum>=<target>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SumTarget {
+    pub sum: Sum,
+    pub target: u32,
+}
+
+impl FromStr for Sum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Sum, String> {
+        match s {
+            "internet" | "inet" | "rfc1071" => Ok(Sum::Internet),
+            "sum8" => Ok(Sum::Sum8),
+            "sum16" => Ok(Sum::Sum16),
+            "lrc" => Ok(Sum::Lrc),
+            "xor8" | "xor" => Ok(Sum::Xor8),
+            _ => Err(format!("unknown sum {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Sum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sum::Internet => write!(f, "internet"),
+            Sum::Sum8 => write!(f, "sum8"),
+            Sum::Sum16 => write!(f, "sum16"),
+            Sum::Lrc => write!(f, "lrc"),
+            Sum::Xor8 => write!(f, "xor8"),
+        }
+    }
+}
+
+impl FromStr for SumTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SumTarget, String> {
+        let (sum, target) = s.split_once('=')
+            .ok_or_else(|| format!("expected <sum>=<target>, found {:?}", s))?;
+        let sum = sum.parse::<Sum>()?;
+        let res = match target.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => target.parse(),
+        };
+        let target = res.map_err(|_| format!("invalid target {:?}", target))?;
+        Ok(SumTarget{sum, target})
+    }
+}
+
+impl fmt::Display for SumTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}=0x{:x}", self.sum, self.target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what a split adds up to
+    fn join(parts: &[u64]) -> u64 {
+        parts.iter().fold(0, |v, &p| (v << 8) + p)
+    }
+
+    #[test]
+    fn split_adds_up() {
+        for n in 1..=3 {
+            for (min, max) in [(0, 255), (0x48, 0x57), (3*0x20, 3*0x7e)] {
+                for v in (0..1 << (8*n+2)).step_by(97) {
+                    if let Some(parts) = split(v, n, min, max) {
+                        assert_eq!(parts.len(), n);
+                        assert!(parts.iter().all(|p| (min..=max).contains(p)));
+                        assert_eq!(join(&parts), v);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn split_is_complete() {
+        // compare against trying every pair of parts
+        let (min, max) = (10, 300);
+        for v in 0..0x20000 {
+            let exists = (min..=max)
+                .any(|a| v >= a*256 && (min..=max).contains(&(v - a*256)));
+            assert_eq!(split(v, 2, min, max).is_some(), exists, "v {}", v);
+        }
+    }
+
+    #[test]
+    fn internet_known_values() {
+        // the example from RFC 1071
+        let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        assert_eq!(Sum::Internet.sum(&data), !0xddf2 & 0xffff);
+        // an IPv4 header with its checksum zeroed
+        let header = [0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00,
+            0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01,
+            0xc0, 0xa8, 0x00, 0xc7];
+        assert_eq!(Sum::Internet.sum(&header), 0xb861);
+        // odd lengths are padded with a zero
+        assert_eq!(Sum::Internet.sum(&[0x12]), !0x1200 & 0xffff);
+    }
+
+    #[test]
+    fn forge_hits_target() {
+        for sum in [Sum::Internet, Sum::Sum8, Sum::Sum16, Sum::Lrc,
+                Sum::Xor8] {
+            for (lo, hi) in [(0x00, 0xff), (0x48, 0x57), (b'0', b'9')] {
+                for target in [0, 1, 0x42, 0x1234, 0xfffe] {
+                    // a narrow range can't xor to every byte
+                    if !sum.is_valid(target)
+                            || (sum == Sum::Xor8 && hi - lo < 0x80) {
+                        continue;
+                    }
+                    let (suffix, hit) = sum.forge(b"hello", b"\n", &[target],
+                        lo, hi).unwrap();
+                    assert_eq!(hit, target);
+                    assert!(suffix.iter().all(|b| (lo..=hi).contains(b)),
+                        "{} {:02x?}", sum, suffix);
+                    let whole = [&b"hello"[..], &suffix, b"\n"].concat();
+                    assert_eq!(sum.sum(&whole), target, "{}", sum);
+                }
+            }
+        }
+    }
+}
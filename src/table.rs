@@ -0,0 +1,119 @@
+// Persistent meet-in-the-middle tables
+//
+// Our mitm strategy tables what each first half of our suffix contributes
+// to our CRC. This doesn't depend on our prefix or target, only on our
+// CRC, charset, postfix, and mask, so it can be kept on disk and reused
+// for later targets. The format is a short text header, followed by the
+// entries sorted by contribution, each a 4-byte little-endian
+// contribution and an 8-byte little-endian counter:
+//
+//   # crcbrute table
+//   search poly=0x11edc6f41 xorout=0xffffffff refin=true ...
+//   entries 1048576
+//   <entries>
+//
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    /// Identifies the search, a table can only be reused if these match
+    pub search: String,
+    /// Contribution and counter of each first half, sorted
+    pub entries: Vec<(u32, u64)>,
+}
+
+const MAGIC: &str = "# crcbrute table";
+
+impl Table {
+    /// Build a table, keeping only the first counter for each
+    /// contribution
+    pub fn new(search: String, mut entries: Vec<(u32, u64)>) -> Table {
+        entries.sort_unstable();
+        entries.dedup_by_key(|&mut (x, _)| x);
+        Table{search, entries}
+    }
+
+    /// Find a counter with this contribution
+    pub fn get(&self, x: u32) -> Option<u64> {
+        self.entries.binary_search_by_key(&x, |&(x, _)| x)
+            .ok()
+            .map(|i| self.entries[i].1)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Table, String> {
+        // split off our three header lines
+        let mut header = vec![];
+        let mut rest = data;
+        for _ in 0..3 {
+            let i = rest.iter().position(|&b| b == b'\n')
+                .ok_or("truncated header")?;
+            header.push(core::str::from_utf8(&rest[..i])
+                .map_err(|_| "invalid header")?);
+            rest = &rest[i+1..];
+        }
+
+        if header[0] != MAGIC {
+            return Err("not a crcbrute table".into());
+        }
+        let search = header[1].strip_prefix("search ")
+            .ok_or_else(|| format!("expected search, found {:?}", header[1]))?;
+        let count = header[2].strip_prefix("entries ")
+            .and_then(|n| n.parse::<usize>().ok())
+            .ok_or_else(|| format!("expected entries, found {:?}",
+                header[2]))?;
+        if rest.len() != 12*count {
+            return Err(format!("expected {} entries, found {} bytes",
+                count, rest.len()));
+        }
+
+        let entries = rest.chunks_exact(12)
+            .map(|e| (
+                u32::from_le_bytes(e[..4].try_into().unwrap()),
+                u64::from_le_bytes(e[4..].try_into().unwrap()),
+            ))
+            .collect::<Vec<_>>();
+        if !entries.windows(2).all(|w| w[0].0 < w[1].0) {
+            return Err("entries aren't sorted".into());
+        }
+        Ok(Table{search: search.into(), entries})
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = format!("{}\nsearch {}\nentries {}\n",
+            MAGIC, self.search, self.entries.len()).into_bytes();
+        data.reserve(12*self.entries.len());
+        for &(x, i) in &self.entries {
+            data.extend_from_slice(&x.to_le_bytes());
+            data.extend_from_slice(&i.to_le_bytes());
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let table = Table::new("poly=0x104c11db7".into(),
+            vec![(3, 30), (1, 10), (2, 20), (1, 11)]);
+        assert_eq!(table.entries, [(1, 10), (2, 20), (3, 30)]);
+        assert_eq!(table.get(2), Some(20));
+        assert_eq!(table.get(4), None);
+        assert_eq!(Table::parse(&table.to_bytes()), Ok(table));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let data = Table::new("x".into(), vec![(1, 10), (2, 20)]).to_bytes();
+        assert!(Table::parse(&data[..data.len()-1]).is_err());
+        assert!(Table::parse(&data[..10]).is_err());
+        assert!(Table::parse(&data[1..]).is_err());
+
+        // entries out of order
+        let mut data = data;
+        let n = data.len();
+        data[n-24] = 3;
+        assert!(Table::parse(&data).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+// CRCs wider than 64 bits
+//
+// Our main engine is built around 32-bit carry-less multiplication, which
+// doesn't stretch to algorithms like CRC-82/DARC. Here the register is an
+// array of u64 limbs, least significant first, and we compute a bit at a
+// time. This is slow, but enough to compute, verify, and solve short
+// messages.
+//
+
+use crate::gf2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wide {
+    /// Width of our CRC in bits
+    pub width: usize,
+    /// Polynomial without the implicit top bit, in the usual Rocksoft
+    /// convention
+    pub polynomial: Vec<u64>,
+    pub init: Vec<u64>,
+    pub xorout: Vec<u64>,
+    /// Reflect each input byte
+    pub refin: bool,
+    /// Reflect the final register
+    pub refout: bool,
+}
+
+/// Parse a value with any number of limbs, hex with 0x, octal with 0o,
+/// binary with 0b, otherwise decimal, same as our other numbers
+pub fn parse(s: &str, width: usize) -> Result<Vec<u64>, String> {
+    let (radix, digits) = if let Some(digits) = s.strip_prefix("0x") {
+        (16, digits)
+    } else if let Some(digits) = s.strip_prefix("0o") {
+        (8, digits)
+    } else if let Some(digits) = s.strip_prefix("0b") {
+        (2, digits)
+    } else {
+        (10, s)
+    };
+    let digits = digits.replace('_', "");
+    if digits.is_empty() {
+        return Err(format!("invalid number {:?}", s));
+    }
+
+    let mut limbs = vec![0; width.div_ceil(64)];
+    for c in digits.chars() {
+        let d = c.to_digit(radix)
+            .ok_or_else(|| format!("invalid number {:?}", s))?;
+        // shift in one digit at a time
+        let mut carry = d as u128;
+        for limb in &mut limbs {
+            let x = *limb as u128 * radix as u128 + carry;
+            *limb = x as u64;
+            carry = x >> 64;
+        }
+        if carry != 0 || (!width.is_multiple_of(64)
+                && limbs[limbs.len()-1] >> (width % 64) != 0) {
+            return Err(format!("{} doesn't fit in {} bits", s, width));
+        }
+    }
+    Ok(limbs)
+}
+
+/// Parse a polynomial, with or without its top bit, since we already know
+/// our width
+pub fn parse_polynomial(s: &str, width: usize) -> Result<Vec<u64>, String> {
+    let mut p = parse(s, width+1)?;
+    p.truncate(width.div_ceil(64));
+    if !width.is_multiple_of(64) {
+        let last = p.len()-1;
+        p[last] &= !0 >> (64 - width % 64);
+    }
+    Ok(p)
+}
+
+/// Format a value as hex, zero-padded to our width
+pub fn hex(x: &[u64], width: usize) -> String {
+    let digits = width.div_ceil(4);
+    let s = x.iter().rev().map(|l| format!("{:016x}", l)).collect::<String>();
+    format!("0x{}", &s[s.len()-digits..])
+}
+
+fn bit(x: &[u64], i: usize) -> bool {
+    x[i/64] & (1 << (i%64)) != 0
+}
+
+fn xor(a: &mut [u64], b: &[u64]) {
+    for (a, b) in a.iter_mut().zip(b) {
+        *a ^= b;
+    }
+}
+
+impl Wide {
+    pub fn new(
+        width: usize,
+        polynomial: Vec<u64>,
+        init: Vec<u64>,
+        xorout: Vec<u64>,
+        refin: bool,
+        refout: bool,
+    ) -> Wide {
+        assert!(width > 0);
+        let limbs = width.div_ceil(64);
+        assert!(polynomial.len() == limbs
+            && init.len() == limbs
+            && xorout.len() == limbs);
+        Wide{width, polynomial, init, xorout, refin, refout}
+    }
+
+    fn reflect(&self, x: &[u64]) -> Vec<u64> {
+        let mut y = vec![0; x.len()];
+        for i in 0..self.width {
+            if bit(x, i) {
+                let j = self.width-1-i;
+                y[j/64] |= 1 << (j%64);
+            }
+        }
+        y
+    }
+
+    /// CRC a message
+    pub fn crc(&self, data: &[u8]) -> Vec<u64> {
+        let top = self.width-1;
+        let mut crc = self.init.clone();
+        for &b in data {
+            let b = if self.refin { b.reverse_bits() } else { b };
+            for i in (0..8).rev() {
+                let carry = bit(&crc, top) ^ (b & (1 << i) != 0);
+
+                // shift our limbs left, dropping our top bit
+                for j in (0..crc.len()).rev() {
+                    crc[j] = (crc[j] << 1)
+                        | if j > 0 { crc[j-1] >> 63 } else { 0 };
+                }
+                let last = crc.len()-1;
+                if !self.width.is_multiple_of(64) {
+                    crc[last] &= (1 << (self.width % 64)) - 1;
+                }
+
+                if carry {
+                    xor(&mut crc, &self.polynomial);
+                }
+            }
+        }
+
+        if self.refout {
+            crc = self.reflect(&crc);
+        }
+        xor(&mut crc, &self.xorout);
+        crc
+    }
+
+    /// Solve for the bytes in free so our message's CRC matches target,
+    /// leaves the rest of our message alone
+    ///
+    /// Our CRC is affine in the bits of our message, so this is just a
+    /// linear system, as long as we have enough free bits.
+    pub fn solve(
+        &self,
+        data: &[u8],
+        free: &[usize],
+        target: &[u64],
+    ) -> Option<Vec<u8>> {
+        let mut data = data.to_vec();
+        let base = self.crc(&data);
+        let cols = free.iter()
+            .flat_map(|&i| (0..8).map(move |b| (i, b)))
+            .map(|(i, b)| {
+                data[i] ^= 1 << b;
+                let mut col = self.crc(&data);
+                data[i] ^= 1 << b;
+                xor(&mut col, &base);
+                col
+            })
+            .collect::<Vec<_>>();
+
+        let mut b = target.to_vec();
+        xor(&mut b, &base);
+        let x = gf2::solve_wide(&cols, &b)?;
+        for (k, &i) in free.iter().enumerate() {
+            for b in 0..8 {
+                let j = 8*k + b;
+                if x[j/64] & (1 << (j%64)) != 0 {
+                    data[i] ^= 1 << b;
+                }
+            }
+        }
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn darc() -> Wide {
+        Wide::new(82,
+            parse("0x0308c0111011401440411", 82).unwrap(),
+            vec![0, 0], vec![0, 0], true, true)
+    }
+
+    #[test]
+    fn check_values() {
+        assert_eq!(hex(&darc().crc(b"123456789"), 82),
+            "0x09ea83f625023801fd612");
+
+        let xz = Wide::new(64, vec![0x42f0e1eba9ea3693], vec![!0], vec![!0],
+            true, true);
+        assert_eq!(xz.crc(b"123456789"), vec![0x995dc9bbdf1939fa]);
+
+        let ccitt = Wide::new(16, vec![0x1021], vec![0xffff], vec![0],
+            false, false);
+        assert_eq!(ccitt.crc(b"123456789"), vec![0x29b1]);
+    }
+
+    #[test]
+    fn parse_numbers() {
+        assert_eq!(parse("0x1_0000_0000_0000_0001", 65), Ok(vec![1, 1]));
+        assert_eq!(parse("18446744073709551617", 65), Ok(vec![1, 1]));
+        assert_eq!(parse("0o3", 2), Ok(vec![3]));
+        assert_eq!(parse("0b101", 3), Ok(vec![5]));
+        assert!(parse("0x20000000000000000", 65).is_err());
+        assert!(parse("0x8", 3).is_err());
+        assert!(parse("0x", 8).is_err());
+        assert!(parse("12a", 8).is_err());
+
+        // polynomials can leave out their top bit
+        let p = parse("0x0308c0111011401440411", 82);
+        assert_eq!(parse_polynomial("0x0308c0111011401440411", 82), p);
+        assert_eq!(parse_polynomial("0x4308c0111011401440411", 82), p);
+        assert_eq!(parse_polynomial("0x11021", 16), Ok(vec![0x1021]));
+    }
+
+    #[test]
+    fn solve_round_trips() {
+        let darc = darc();
+        let data = b"hello world, this is a long message".to_vec();
+        let free = (5..16).collect::<Vec<_>>();
+        for target in [[0, 0], [1, 0], [!0, 0x3ffff], [0x1234, 0x5678]] {
+            let solved = darc.solve(&data, &free, &target).unwrap();
+            assert_eq!(darc.crc(&solved), target);
+            assert_eq!(solved[..5], data[..5]);
+            assert_eq!(solved[16..], data[16..]);
+        }
+    }
+}
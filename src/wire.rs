@@ -0,0 +1,154 @@
+// Wire-order views of our message
+//
+// Some link layers don't feed bytes into their CRC the way they're stored,
+// they may serialize bits LSB-first, or swap bytes in word-oriented DMA.
+// These views transform our message into what the CRC actually sees.
+//
+// Every view is its own inverse, and only moves bits around, so CRCs are
+// still affine over any view. A trailing partial word is left alone.
+//
+
+use core::fmt;
+use core::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    /// Reverse the bits in each byte
+    BitReverse,
+    /// Swap the bytes in each 16-bit word
+    Swap16,
+    /// Swap the bytes in each 32-bit word
+    Swap32,
+}
+
+impl View {
+    /// Bytes this view groups together, our message should be aligned to
+    /// this to be split without changing its view
+    pub fn width(&self) -> usize {
+        match self {
+            View::BitReverse => 1,
+            View::Swap16 => 2,
+            View::Swap32 => 4,
+        }
+    }
+
+    /// Where does byte i end up in a message of len bytes?
+    pub fn position(&self, len: usize, i: usize) -> usize {
+        let w = self.width();
+        if (i/w + 1)*w <= len {
+            (i/w)*w + (w-1 - i%w)
+        } else {
+            i
+        }
+    }
+}
+
+/// Apply views to a message, in order
+pub fn apply(views: &[View], data: &[u8]) -> Vec<u8> {
+    let mut data = data.to_vec();
+    for view in views {
+        match view {
+            View::BitReverse => {
+                for b in data.iter_mut() {
+                    *b = b.reverse_bits();
+                }
+            }
+            View::Swap16 | View::Swap32 => {
+                for word in data.chunks_exact_mut(view.width()) {
+                    word.reverse();
+                }
+            }
+        }
+    }
+    data
+}
+
+/// Undo views applied to a message
+pub fn unapply(views: &[View], data: &[u8]) -> Vec<u8> {
+    let views = views.iter().rev().copied().collect::<Vec<_>>();
+    apply(&views, data)
+}
+
+/// Where does byte i end up after all of our views?
+pub fn position(views: &[View], len: usize, i: usize) -> usize {
+    views.iter().fold(i, |i, view| view.position(len, i))
+}
+
+/// Bytes our views group together, the largest of any view
+pub fn width(views: &[View]) -> usize {
+    views.iter().map(|view| view.width()).max().unwrap_or(1)
+}
+
+impl FromStr for View {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<View, String> {
+        match s {
+            "bitrev" | "lsb-first" => Ok(View::BitReverse),
+            "swap16" => Ok(View::Swap16),
+            "swap32" => Ok(View::Swap32),
+            _ => Err(format!("unknown wire view {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for View {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            View::BitReverse => write!(f, "bitrev"),
+            View::Swap16 => write!(f, "swap16"),
+            View::Swap32 => write!(f, "swap32"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEWS: [&[View]; 5] = [
+        &[],
+        &[View::BitReverse],
+        &[View::Swap16],
+        &[View::Swap32, View::BitReverse],
+        &[View::Swap16, View::Swap32],
+    ];
+
+    #[test]
+    fn unapply_undoes_apply() {
+        let data = (0..11).map(|i| 0x11*i + 1).collect::<Vec<u8>>();
+        for views in VIEWS {
+            assert_eq!(unapply(views, &apply(views, &data)), data);
+            assert_eq!(apply(views, &unapply(views, &data)), data);
+        }
+        assert_eq!(apply(&[View::Swap32], b"abcdefghij"), b"dcbahgfeij");
+        assert_eq!(apply(&[View::BitReverse], &[0x01, 0x80]), [0x80, 0x01]);
+    }
+
+    #[test]
+    fn position_follows_apply() {
+        for views in VIEWS {
+            for len in [0, 1, 5, 8, 11] {
+                let data = (0..len as u8).collect::<Vec<_>>();
+                let viewed = apply(views, &data);
+                for (i, &b) in data.iter().enumerate() {
+                    let b = if views.contains(&View::BitReverse) {
+                        b.reverse_bits()
+                    } else {
+                        b
+                    };
+                    assert_eq!(viewed[position(views, len, i)], b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_views() {
+        for view in [View::BitReverse, View::Swap16, View::Swap32] {
+            assert_eq!(view.to_string().parse(), Ok(view));
+        }
+        assert_eq!("lsb-first".parse(), Ok(View::BitReverse));
+        assert!("swap64".parse::<View>().is_err());
+    }
+}